@@ -4,13 +4,17 @@ use std::{fs::File, io::Write, path::PathBuf, str::FromStr};
 
 use svdtools::{
     convert::convert_cli,
+    cstruct::cstruct_cli,
+    enums::enums_cli,
     html::html_cli,
     html::htmlcompare_cli,
     info,
     interrupts::interrupts_cli,
     makedeps::makedeps_cli,
+    merge::merge_cli,
     mmap::mmap_cli,
     patch::{patch_cli, EnumAutoDerive},
+    symbols::symbols_cli,
 };
 
 #[derive(Parser, Debug)]
@@ -41,6 +45,39 @@ enum Command {
         /// Derive level when several identical enumerationValues added in a field
         #[clap(long)]
         enum_derive: Option<EnumAutoDerive>,
+
+        /// Check that patched registers have no fields with same name
+        #[clap(long)]
+        check_duplicate_fields: bool,
+
+        /// Check that patched registers have no overlapping fields
+        #[clap(long)]
+        check_overlaps: bool,
+
+        /// Print peripherals which were not touched by any command of patch
+        #[clap(long)]
+        report_untouched: bool,
+
+        /// Output only peripherals touched by patch (and their bases)
+        #[clap(long)]
+        partial: bool,
+
+        /// Match specs against names ignoring case
+        #[clap(long)]
+        case_insensitive_match: bool,
+
+        /// Fail when deletion matches nothing
+        #[clap(long)]
+        strict: bool,
+
+        /// Print numbers of added, modified and deleted peripherals, registers and fields
+        #[clap(long)]
+        summary: bool,
+
+        /// Resolve relative paths of `_svd`, `_include` and others against this directory
+        /// instead of directory of YAML file
+        #[clap(long)]
+        base_dir: Option<PathBuf>,
     },
     ExpandPatch {
         /// Path to input YAML file
@@ -48,6 +85,56 @@ enum Command {
 
         /// Path to output file. By default it prints to stdout
         out_path: Option<PathBuf>,
+
+        /// Resolve relative paths of `_include` against this directory
+        /// instead of directory of YAML file
+        #[clap(long)]
+        base_dir: Option<PathBuf>,
+    },
+    /// Write patch fragment which adds peripheral from SVD file
+    DumpPeripheral {
+        /// Path to input SVD file
+        svd_file: PathBuf,
+
+        /// Name of peripheral
+        peripheral: String,
+
+        /// Path to output YAML file
+        out_path: PathBuf,
+    },
+    /// Write patch skeleton with `_modify` stubs of all registers and fields of SVD file
+    Skeleton {
+        /// Path to input SVD file
+        svd_file: PathBuf,
+
+        /// Path to output YAML file
+        out_path: PathBuf,
+    },
+    /// Import all peripherals from one SVD file into another
+    Merge {
+        /// Path to base SVD file
+        base: PathBuf,
+
+        /// Path to SVD file with peripherals to import
+        extra: PathBuf,
+
+        /// Path to output SVD file
+        out_path: PathBuf,
+
+        /// Path to format config file
+        ///
+        /// If not specified, the default format config will be used.
+        #[clap(long)]
+        format_config: Option<PathBuf>,
+    },
+    /// Check SVD file for errors
+    Validate {
+        /// Path to input SVD file
+        svd_file: PathBuf,
+
+        /// Also check CMSIS-SVD constraints not enforced by parser
+        #[clap(long)]
+        cmsis: bool,
     },
     /// Generate Make dependency file listing dependencies for a YAML file.
     Makedeps {
@@ -56,6 +143,11 @@ enum Command {
 
         /// Dependencies output file
         deps_file: PathBuf,
+
+        /// Resolve relative paths of `_include` against this directory
+        /// instead of directory of YAML file
+        #[clap(long)]
+        base_dir: Option<PathBuf>,
     },
     /// Print list of all interrupts described by an SVD file
     Interrupts {
@@ -70,6 +162,31 @@ enum Command {
     Mmap {
         /// Path to input SVD file
         svd_file: PathBuf,
+
+        /// Output format (text, JSON or CSV)
+        #[clap(long)]
+        format: Option<mmap_cli::MmapFormat>,
+    },
+    /// Print registers of a peripheral as a C struct
+    Cstruct {
+        /// Path to input SVD file
+        svd_file: PathBuf,
+
+        /// Name of peripheral
+        peripheral: String,
+    },
+    /// Print Rust enum stubs for enumeratedValues of an SVD file
+    Enums {
+        /// Path to input SVD file
+        svd_file: PathBuf,
+    },
+    /// Generate linker script with `PROVIDE` symbols for addresses of all registers
+    Symbols {
+        /// Path to input SVD file
+        svd_file: PathBuf,
+
+        /// Path to output linker script
+        out_path: PathBuf,
     },
     /// Convert SVD representation between file formats
     Convert {
@@ -99,6 +216,10 @@ enum Command {
         #[clap(long)]
         ignore_enums: bool,
 
+        /// Copy default register properties of device into peripherals where they are not set
+        #[clap(long)]
+        inline_defaults: bool,
+
         /// Path to format config file
         ///
         /// If not specified, the default format config will be used.
@@ -115,6 +236,19 @@ enum Command {
         /// Input SVD XML files
         svdfiles: Vec<PathBuf>,
     },
+    /// Prints documentation coverage of every peripheral in SVD file
+    Coverage {
+        /// Path to SVD file
+        svd_file: PathBuf,
+
+        /// Print coverage as JSON
+        #[clap(long)]
+        json: bool,
+
+        /// Write coverage of device as SVG badge to this file instead of printing
+        #[clap(long)]
+        badge: Option<PathBuf>,
+    },
     /// Generates a webpage for a given SVD file containing details on every
     /// peripheral and register and their level of coverage.
     Html {
@@ -123,6 +257,10 @@ enum Command {
 
         /// Path to patched SVD files
         svdfiles: Vec<PathBuf>,
+
+        /// Regenerate pages only when SVD content hash changed instead of comparing mtimes
+        #[clap(long)]
+        content_hash: bool,
     },
     /// Prints informetion and statistics about SVD file
     Info {
@@ -142,7 +280,15 @@ impl Command {
             Self::Interrupts { svd_file, no_gaps } => {
                 interrupts_cli::parse_device(svd_file, !no_gaps)?;
             }
-            Self::Mmap { svd_file } => mmap_cli::parse_device(svd_file)?,
+            Self::Mmap { svd_file, format } => {
+                mmap_cli::parse_device(svd_file, format.unwrap_or_default())?
+            }
+            Self::Cstruct {
+                svd_file,
+                peripheral,
+            } => cstruct_cli::parse_device(svd_file, peripheral)?,
+            Self::Enums { svd_file } => enums_cli::enums(svd_file)?,
+            Self::Symbols { svd_file, out_path } => symbols_cli::symbols(svd_file, out_path)?,
             Self::Patch {
                 yaml_file,
                 out_path,
@@ -150,12 +296,28 @@ impl Command {
                 post_validate,
                 show_patch_on_error,
                 enum_derive,
+                check_duplicate_fields,
+                check_overlaps,
+                report_untouched,
+                partial,
+                case_insensitive_match,
+                strict,
+                summary,
+                base_dir,
             } => {
                 let mut config = svdtools::patch::Config::default();
                 if *post_validate {
                     config.post_validate = svd_rs::ValidateLevel::Strict;
                 }
                 config.show_patch_on_error = *show_patch_on_error;
+                config.check_duplicate_fields = *check_duplicate_fields;
+                config.check_overlaps = *check_overlaps;
+                config.report_untouched = *report_untouched;
+                config.partial_output = *partial;
+                config.case_insensitive_match = *case_insensitive_match;
+                config.strict = *strict;
+                config.summary = *summary;
+                config.base_dir = base_dir.clone();
                 if let Some(enum_derive) = enum_derive.as_ref() {
                     config.enum_derive = *enum_derive;
                 }
@@ -169,8 +331,9 @@ impl Command {
             Self::ExpandPatch {
                 yaml_file,
                 out_path,
+                base_dir,
             } => {
-                let yml = patch_cli::expand_patch(yaml_file)?;
+                let yml = patch_cli::expand_patch(yaml_file, base_dir.as_deref())?;
                 if let Some(out_path) = out_path.as_ref() {
                     let mut f = File::create(out_path)?;
                     f.write_all(yml.as_bytes())?;
@@ -178,10 +341,24 @@ impl Command {
                     println!("{yml}");
                 }
             }
+            Self::DumpPeripheral {
+                svd_file,
+                peripheral,
+                out_path,
+            } => patch_cli::dump_peripheral(svd_file, peripheral, out_path)?,
+            Self::Skeleton { svd_file, out_path } => patch_cli::skeleton(svd_file, out_path)?,
+            Self::Merge {
+                base,
+                extra,
+                out_path,
+                format_config,
+            } => merge_cli::merge(base, extra, out_path, format_config.as_deref())?,
+            Self::Validate { svd_file, cmsis } => patch_cli::validate(svd_file, *cmsis)?,
             Self::Makedeps {
                 yaml_file,
                 deps_file,
-            } => makedeps_cli::makedeps(yaml_file, deps_file)?,
+                base_dir,
+            } => makedeps_cli::makedeps(yaml_file, deps_file, base_dir.as_deref())?,
             Self::Convert {
                 in_path,
                 out_path,
@@ -190,6 +367,7 @@ impl Command {
                 expand,
                 expand_properties,
                 ignore_enums,
+                inline_defaults,
                 format_config,
             } => convert_cli::convert(
                 in_path,
@@ -200,14 +378,26 @@ impl Command {
                     expand: *expand,
                     expand_properties: *expand_properties,
                     ignore_enums: *ignore_enums,
+                    inline_defaults: *inline_defaults,
                 },
                 format_config.as_ref().map(|p| p.as_path()),
             )?,
             Self::Htmlcompare { htmldir, svdfiles } => {
                 htmlcompare_cli::htmlcompare(htmldir, svdfiles)?;
             }
-            Self::Html { htmldir, svdfiles } => {
-                html_cli::svd2html(htmldir, svdfiles)?;
+            Self::Html {
+                htmldir,
+                svdfiles,
+                content_hash,
+            } => {
+                html_cli::svd2html(htmldir, svdfiles, *content_hash)?;
+            }
+            Self::Coverage {
+                svd_file,
+                json,
+                badge,
+            } => {
+                html_cli::coverage(svd_file, *json, badge.as_deref())?;
             }
             Self::Info {
                 in_path,
@@ -219,7 +409,8 @@ impl Command {
                     in_path,
                     *input_format,
                     convert_cli::ParserConfig {
-                        ignore_enums: true,
+                        expand_properties: request.needs_properties(),
+                        ignore_enums: !request.needs_enums(),
                         ..Default::default()
                     },
                 )?;
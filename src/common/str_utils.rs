@@ -26,6 +26,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::mixed_case_hex_literals)]
     fn correctly_format_address() {
         let addr: u32 = 0xde4dBeeF;
         let formatted_addr = format_address(addr as u64);
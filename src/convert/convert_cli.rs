@@ -52,6 +52,7 @@ pub struct ParserConfig {
     pub expand: bool,
     pub expand_properties: bool,
     pub ignore_enums: bool,
+    pub inline_defaults: bool,
 }
 
 pub fn open_svd(
@@ -78,6 +79,9 @@ pub fn open_svd(
         InputFormat::Yaml => serde_yaml::from_str(&input)?,
         InputFormat::Json => serde_json::from_str(&input)?,
     };
+    if parser_config.inline_defaults {
+        inline_defaults(&mut device);
+    }
     if parser_config.expand_properties {
         svd_parser::expand_properties(&mut device);
     }
@@ -89,6 +93,19 @@ pub fn open_svd(
     Ok(device)
 }
 
+/// Copy default register properties of device into peripherals where they are not set
+pub fn inline_defaults(device: &mut Device) {
+    let defaults = device.default_register_properties;
+    for p in &mut device.peripherals {
+        let props = &mut p.default_register_properties;
+        props.size = props.size.or(defaults.size);
+        props.access = props.access.or(defaults.access);
+        props.protection = props.protection.or(defaults.protection);
+        props.reset_value = props.reset_value.or(defaults.reset_value);
+        props.reset_mask = props.reset_mask.or(defaults.reset_mask);
+    }
+}
+
 pub fn convert(
     in_path: &Path,
     out_path: &Path,
@@ -119,3 +136,35 @@ pub fn convert(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn inline_defaults() {
+        let mut device = svd_parser::parse(
+            r"
+<device>
+    <name>dev</name>
+    <size>16</size>
+    <resetValue>0x1</resetValue>
+    <peripherals>
+        <peripheral>
+            <name>PeriphA</name>
+            <baseAddress>0x10000000</baseAddress>
+        </peripheral>
+        <peripheral>
+            <name>PeriphB</name>
+            <baseAddress>0x10001000</baseAddress>
+            <size>32</size>
+        </peripheral>
+    </peripherals>
+</device>",
+        )
+        .unwrap();
+        super::inline_defaults(&mut device);
+        let a = &device.peripherals[0].default_register_properties;
+        assert_eq!((a.size, a.reset_value), (Some(16), Some(1)));
+        let b = &device.peripherals[1].default_register_properties;
+        assert_eq!((b.size, b.reset_value), (Some(32), Some(1)));
+    }
+}
@@ -0,0 +1,160 @@
+use anyhow::{anyhow, Result};
+use std::fmt::Write;
+use std::{fs::File, io::Read, path::Path};
+use svd_parser::expand::{derive_peripheral, Index};
+use svd_parser::svd::{self, Cluster, PeripheralInfo, Register, RegisterCluster};
+
+/// Print C struct describing registers of the peripheral
+pub fn parse_device(svd_file: &Path, pname: &str) -> Result<()> {
+    let mut xml = String::new();
+    File::open(svd_file)?.read_to_string(&mut xml)?;
+    let device = svd_parser::parse_with_config(
+        &xml,
+        &svd_parser::Config::default()
+            .expand_properties(true)
+            .ignore_enums(true),
+    )?;
+    let mut peripheral = device
+        .get_peripheral(pname)
+        .ok_or_else(|| anyhow!("peripheral {pname} not found"))?
+        .clone();
+    if let Some(dfname) = peripheral.derived_from.clone() {
+        let index = Index::create(&device);
+        derive_peripheral(&mut peripheral, &dfname, &index)?;
+    }
+    print!("{}", to_c_struct(&peripheral));
+    Ok(())
+}
+
+/// Generate C struct with `volatile` register members and padding for gaps
+pub fn to_c_struct(peripheral: &PeripheralInfo) -> String {
+    let mut regs = Vec::new();
+    if let Some(children) = peripheral.registers.as_ref() {
+        collect_registers(children, 0, "", &mut regs);
+    }
+    regs.sort_by_key(|r| r.0);
+
+    let mut out = String::new();
+    writeln!(out, "typedef struct {{").unwrap();
+    let mut offset = 0;
+    let mut reserved = 0;
+    for (roffset, size, rname) in regs {
+        if roffset < offset {
+            writeln!(
+                out,
+                "    /* {rname} at 0x{roffset:X} overlaps previous register */"
+            )
+            .unwrap();
+            continue;
+        }
+        if roffset > offset {
+            writeln!(out, "    uint8_t reserved{reserved}[{}];", roffset - offset).unwrap();
+            reserved += 1;
+        }
+        let ctype = match size {
+            8 => "uint8_t",
+            16 => "uint16_t",
+            64 => "uint64_t",
+            _ => "uint32_t",
+        };
+        writeln!(out, "    volatile {ctype} {rname}; /* 0x{roffset:X} */").unwrap();
+        offset = roffset + size as u64 / 8;
+    }
+    writeln!(out, "}} {}_TypeDef;", peripheral.name).unwrap();
+    out
+}
+
+/// Collect `(offset, size, name)` of all registers with expanded arrays and clusters
+pub(crate) fn collect_registers(
+    children: &[RegisterCluster],
+    base: u64,
+    prefix: &str,
+    regs: &mut Vec<(u64, u32, String)>,
+) {
+    for rc in children {
+        match rc {
+            RegisterCluster::Register(r) => {
+                let size = r.properties.size.unwrap_or(32);
+                match r {
+                    Register::Single(r) => regs.push((
+                        base + r.address_offset as u64,
+                        size,
+                        format!("{prefix}{}", r.name),
+                    )),
+                    Register::Array(r, d) => {
+                        for ri in svd::register::expand(r, d) {
+                            regs.push((
+                                base + ri.address_offset as u64,
+                                size,
+                                format!("{prefix}{}", ri.name),
+                            ));
+                        }
+                    }
+                }
+            }
+            RegisterCluster::Cluster(c) => match c {
+                Cluster::Single(c) => {
+                    let prefix = format!("{prefix}{}_", c.name);
+                    let caddr = base + c.address_offset as u64;
+                    collect_registers(&c.children, caddr, &prefix, regs);
+                }
+                Cluster::Array(c, d) => {
+                    for ci in svd::cluster::expand(c, d) {
+                        let prefix = format!("{prefix}{}_", ci.name);
+                        let caddr = base + ci.address_offset as u64;
+                        collect_registers(&c.children, caddr, &prefix, regs);
+                    }
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SVD: &str = r"
+<device>
+    <name>dev</name>
+    <peripherals>
+        <peripheral>
+            <name>PeriphA</name>
+            <baseAddress>0x10000000</baseAddress>
+            <registers>
+                <register>
+                    <name>CR</name>
+                    <addressOffset>0x0</addressOffset>
+                    <size>32</size>
+                </register>
+                <register>
+                    <name>SR</name>
+                    <addressOffset>0x8</addressOffset>
+                    <size>16</size>
+                </register>
+                <register>
+                    <name>DR</name>
+                    <addressOffset>0xC</addressOffset>
+                    <size>32</size>
+                </register>
+            </registers>
+        </peripheral>
+    </peripherals>
+</device>";
+
+    static EXPECTED: &str = r"typedef struct {
+    volatile uint32_t CR; /* 0x0 */
+    uint8_t reserved0[4];
+    volatile uint16_t SR; /* 0x8 */
+    uint8_t reserved1[2];
+    volatile uint32_t DR; /* 0xC */
+} PeriphA_TypeDef;
+";
+
+    #[test]
+    fn c_struct() {
+        let device = svd_parser::parse(SVD).unwrap();
+        let peripheral = device.get_peripheral("PeriphA").unwrap();
+        assert_eq!(to_c_struct(peripheral), EXPECTED);
+    }
+}
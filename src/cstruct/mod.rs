@@ -0,0 +1 @@
+pub mod cstruct_cli;
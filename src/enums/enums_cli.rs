@@ -0,0 +1,239 @@
+use crate::common::svd_reader;
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::path::Path;
+use svd_parser::svd::{Device, EnumeratedValues, RegisterCluster};
+
+/// Name, width and variants identifying unique enum
+type EnumKey = (String, u32, Vec<(String, Option<u64>)>);
+
+/// Print Rust `enum` stubs for every unique enumeratedValues set of SVD file
+pub fn enums(svd_file: &Path) -> Result<()> {
+    let device = svd_reader::device(svd_file)?;
+    print!("{}", to_enums(&device));
+    Ok(())
+}
+
+fn to_enums(device: &Device) -> String {
+    let mut seen = HashSet::new();
+    let mut names = HashMap::new();
+    let mut out = String::new();
+    for p in &device.peripherals {
+        if let Some(regs) = p.registers.as_ref() {
+            collect_enums(regs, &mut seen, &mut names, &mut out);
+        }
+    }
+    out
+}
+
+fn collect_enums(
+    children: &[RegisterCluster],
+    seen: &mut HashSet<EnumKey>,
+    names: &mut HashMap<String, usize>,
+    out: &mut String,
+) {
+    for rc in children {
+        match rc {
+            RegisterCluster::Register(r) => {
+                for f in r.fields() {
+                    for evs in &f.enumerated_values {
+                        if evs.derived_from.is_some() || evs.values.is_empty() {
+                            continue;
+                        }
+                        let name = to_pascal_case(evs.name.as_deref().unwrap_or(&f.name));
+                        let width = f.bit_width();
+                        let key = (
+                            name.clone(),
+                            width,
+                            evs.values
+                                .iter()
+                                .map(|ev| (ev.name.clone(), ev.value))
+                                .collect(),
+                        );
+                        if seen.insert(key) {
+                            // Different sets with same name get numeric suffix
+                            let count = names.entry(name.clone()).or_default();
+                            *count += 1;
+                            let name = if *count > 1 {
+                                format!("{name}{count}")
+                            } else {
+                                name
+                            };
+                            write_enum(&name, width, evs, out);
+                        }
+                    }
+                }
+            }
+            RegisterCluster::Cluster(c) => collect_enums(&c.children, seen, names, out),
+        }
+    }
+}
+
+/// Write `enum` with explicit discriminants and `from_bits` constructor.
+/// Default enumeratedValue becomes catch-all variant
+fn write_enum(name: &str, width: u32, evs: &EnumeratedValues, out: &mut String) {
+    let repr = match width {
+        0..=8 => "u8",
+        9..=16 => "u16",
+        17..=32 => "u32",
+        _ => "u64",
+    };
+    let mut variants = Vec::new();
+    let mut default = None;
+    for ev in &evs.values {
+        let vname = to_pascal_case(&ev.name);
+        match ev.value {
+            Some(value) if !ev.is_default() => {
+                if variants.iter().any(|(_, v, _)| *v == value) {
+                    log::warn!("{name}: skip {vname} with duplicate value {value}");
+                } else {
+                    variants.push((vname, value, ev.description.as_deref()))
+                }
+            }
+            _ => default = Some((vname, ev.description.as_deref())),
+        }
+    }
+    // Default variant takes smallest value not used by others
+    let default = default.map(|(vname, description)| {
+        let value = (0..)
+            .find(|v| variants.iter().all(|(_, value, _)| value != v))
+            .unwrap();
+        (vname, value, description)
+    });
+
+    writeln!(out, "#[derive(Clone, Copy, Debug, PartialEq, Eq)]").unwrap();
+    writeln!(out, "#[repr({repr})]").unwrap();
+    writeln!(out, "pub enum {name} {{").unwrap();
+    for (vname, value, description) in variants.iter().chain(default.iter()) {
+        for line in description.iter().flat_map(|d| d.trim().lines()) {
+            writeln!(out, "    /// {}", line.trim()).unwrap();
+        }
+        writeln!(out, "    {vname} = {value},").unwrap();
+    }
+    writeln!(out, "}}\n").unwrap();
+
+    writeln!(out, "impl {name} {{").unwrap();
+    let ret = if default.is_some() {
+        "Self".to_string()
+    } else {
+        "Option<Self>".to_string()
+    };
+    writeln!(out, "    pub const fn from_bits(bits: {repr}) -> {ret} {{").unwrap();
+    writeln!(out, "        match bits {{").unwrap();
+    for (vname, value, _) in &variants {
+        if default.is_some() {
+            writeln!(out, "            {value} => Self::{vname},").unwrap();
+        } else {
+            writeln!(out, "            {value} => Some(Self::{vname}),").unwrap();
+        }
+    }
+    if let Some((vname, _, _)) = default.as_ref() {
+        writeln!(out, "            _ => Self::{vname},").unwrap();
+    } else {
+        writeln!(out, "            _ => None,").unwrap();
+    }
+    writeln!(out, "        }}\n    }}\n}}\n").unwrap();
+}
+
+/// Convert SVD name to Rust type or variant name
+fn to_pascal_case(name: &str) -> String {
+    let mut out = String::new();
+    for word in name.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            out.push(first.to_ascii_uppercase());
+            out.extend(chars.map(|c| c.to_ascii_lowercase()));
+        }
+    }
+    if out.is_empty() || out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, '_');
+    }
+    if out == "Self" {
+        out.push('_');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SVD: &str = r"
+<device>
+    <name>dev</name>
+    <peripherals>
+        <peripheral>
+            <name>PeriphA</name>
+            <baseAddress>0x10000000</baseAddress>
+            <registers>
+                <register>
+                    <name>CR</name>
+                    <addressOffset>0x0</addressOffset>
+                    <size>32</size>
+                    <fields>
+                        <field>
+                            <name>EN</name>
+                            <bitOffset>0</bitOffset>
+                            <bitWidth>1</bitWidth>
+                            <enumeratedValues>
+                                <name>ENABLE</name>
+                                <enumeratedValue>
+                                    <name>DISABLED</name>
+                                    <description>Channel disabled</description>
+                                    <value>0</value>
+                                </enumeratedValue>
+                                <enumeratedValue>
+                                    <name>ENABLED</name>
+                                    <value>1</value>
+                                </enumeratedValue>
+                            </enumeratedValues>
+                        </field>
+                    </fields>
+                </register>
+            </registers>
+        </peripheral>
+    </peripherals>
+</device>";
+
+    #[test]
+    fn two_value_enum() {
+        let device = svd_parser::parse(SVD).unwrap();
+        let enums = to_enums(&device);
+        assert!(enums.contains("#[repr(u8)]\npub enum Enable {\n"));
+        assert!(enums.contains("    /// Channel disabled\n    Disabled = 0,\n"));
+        assert!(enums.contains("    Enabled = 1,\n}\n"));
+        assert!(enums.contains("pub const fn from_bits(bits: u8) -> Option<Self> {"));
+        assert!(enums.contains("            1 => Some(Self::Enabled),\n"));
+    }
+
+    #[test]
+    fn default_catch_all() {
+        let svd = SVD.replace("<value>1</value>", "<isDefault>true</isDefault>");
+        let device = svd_parser::parse(&svd).unwrap();
+        let enums = to_enums(&device);
+        assert!(enums.contains("    Enabled = 1,\n}\n"));
+        assert!(enums.contains("            _ => Self::Enabled,\n"));
+    }
+
+    #[test]
+    fn same_name_different_values() {
+        let svd = SVD.replace(
+            "</fields>",
+            "<field><name>EN2</name><bitOffset>1</bitOffset><bitWidth>1</bitWidth>\
+            <enumeratedValues><name>ENABLE</name>\
+            <enumeratedValue><name>ON</name><value>1</value></enumeratedValue>\
+            </enumeratedValues></field></fields>",
+        );
+        let device = svd_parser::parse(&svd).unwrap();
+        let enums = to_enums(&device);
+        assert!(enums.contains("pub enum Enable {\n"));
+        assert!(enums.contains("pub enum Enable2 {\n    On = 1,\n}\n"));
+    }
+
+    #[test]
+    fn pascal_case() {
+        assert_eq!(to_pascal_case("NOT_READY"), "NotReady");
+        assert_eq!(to_pascal_case("8bit"), "_8bit");
+    }
+}
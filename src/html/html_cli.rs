@@ -13,7 +13,7 @@ use svd_parser::svd::{BitRange, Field};
 
 use anyhow::{anyhow, Context};
 use liquid::{
-    model::{object, Scalar},
+    model::{object, Scalar, Value},
     Object,
 };
 use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
@@ -122,7 +122,7 @@ fn short_ra(ra: Option<ReadAction>) -> &'static str {
 
 trait GetI64 {
     fn get_i64(&self, key: &str) -> Option<i64>;
-    fn get_str(&self, key: &str) -> Option<Cow<str>>;
+    fn get_str(&self, key: &str) -> Option<Cow<'_, str>>;
 }
 
 impl GetI64 for Object {
@@ -131,7 +131,7 @@ impl GetI64 for Object {
             .and_then(|v| v.as_view().as_scalar())
             .and_then(|s| s.to_integer())
     }
-    fn get_str(&self, key: &str) -> Option<Cow<str>> {
+    fn get_str(&self, key: &str) -> Option<Cow<'_, str>> {
         self.get(key)
             .and_then(|v| v.as_view().as_scalar())
             .map(|s| s.into_cow_str())
@@ -433,10 +433,18 @@ fn parse_device(svdfile: impl AsRef<Path>) -> anyhow::Result<Object> {
     let temp = file.metadata()?.last_write_time() as i64;
     let mut xml = String::new();
     file.read_to_string(&mut xml)?;
-    let device = svd_parser::parse_with_config(
-        &xml,
-        &svd_parser::Config::default().expand_properties(true),
-    )?;
+    let mut device = parse_device_xml(&xml)?;
+    device.insert("last-modified".into(), Value::scalar(temp));
+    device.insert(
+        "svdfile".into(),
+        Value::scalar(svdfile.to_str().unwrap().to_string()),
+    );
+    Ok(device)
+}
+
+fn parse_device_xml(xml: &str) -> anyhow::Result<Object> {
+    let device =
+        svd_parser::parse_with_config(xml, &svd_parser::Config::default().expand_properties(true))?;
     let index = Index::create(&device);
     let mut peripherals = Vec::new();
     let mut device_fields_total = 0;
@@ -500,8 +508,6 @@ fn parse_device(svdfile: impl AsRef<Path>) -> anyhow::Result<Object> {
         "peripherals": peripherals,
         "fields_total": device_fields_total,
         "fields_documented": device_fields_documented,
-        "last-modified": temp,
-        "svdfile": svdfile.to_str().unwrap(),
         "progress": progress(device_fields_documented, device_fields_total),
     }))
 }
@@ -535,50 +541,218 @@ fn generate_if_newer(
     };
 
     if !filename.is_file() || file_mtime < device.get_i64("last-modified").unwrap() {
-        println!("Generating {pagename}");
-        let svdfile = device.get_str("svdfile").unwrap();
-        let svdfile = Path::new(svdfile.as_ref());
-        let svdfile_name = svdfile.file_name().unwrap();
-        let mut file = std::fs::File::create(filename)?;
-        generate_device_page(template, device, &mut file)?;
-        std::fs::copy(svdfile, htmldir.join(svdfile_name))?;
+        write_device_page(template, device, htmldir, &pagename)?;
     }
 
     Ok(())
 }
 
-pub fn svd2html(htmldir: &Path, svdfiles: &[PathBuf]) -> anyhow::Result<()> {
-    let svdfiles = svdfiles.iter().filter(|&f| f.is_file()).collect::<Vec<_>>();
+/// Name of manifest file with content hashes of processed SVD files
+const HASHES_FILE: &str = ".hashes";
 
-    if !htmldir.exists() {
-        std::fs::create_dir(htmldir)?;
+/// FNV-1a hash. Stable across platforms and toolchains unlike `DefaultHasher`
+fn content_hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |h, &b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn read_hashes(htmldir: &Path) -> BTreeMap<String, u64> {
+    std::fs::read_to_string(htmldir.join(HASHES_FILE))
+        .map(|s| {
+            s.lines()
+                .filter_map(|l| {
+                    let (page, hash) = l.split_once(' ')?;
+                    Some((page.to_string(), u64::from_str_radix(hash, 16).ok()?))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn write_hashes(htmldir: &Path, hashes: &BTreeMap<String, u64>) -> anyhow::Result<()> {
+    let mut file = File::create(htmldir.join(HASHES_FILE))?;
+    for (page, hash) in hashes {
+        writeln!(file, "{page} {hash:016x}")?;
     }
+    Ok(())
+}
+
+/// Generate device page if SVD content hash differs from one stored in manifest.
+///
+/// Returns page name and new hash for manifest
+fn generate_if_changed(
+    template: &liquid::Template,
+    device: &Object,
+    htmldir: &Path,
+    hashes: &BTreeMap<String, u64>,
+) -> anyhow::Result<(String, u64)> {
+    let pagename = format!("{}.html", device.get_str("name").unwrap());
+    let svdfile = device.get_str("svdfile").unwrap();
+    let hash = content_hash(&std::fs::read(svdfile.as_ref())?);
+
+    if !htmldir.join(&pagename).is_file() || hashes.get(&pagename) != Some(&hash) {
+        write_device_page(template, device, htmldir, &pagename)?;
+    }
+
+    Ok((pagename, hash))
+}
+
+fn write_device_page(
+    template: &liquid::Template,
+    device: &Object,
+    htmldir: &Path,
+    pagename: &str,
+) -> anyhow::Result<()> {
+    println!("Generating {pagename}");
+    let svdfile = device.get_str("svdfile").unwrap();
+    let svdfile = Path::new(svdfile.as_ref());
+    let svdfile_name = svdfile.file_name().unwrap();
+    let mut file = std::fs::File::create(htmldir.join(pagename))?;
+    generate_device_page(template, device, &mut file)?;
+    std::fs::copy(svdfile, htmldir.join(svdfile_name))?;
+    Ok(())
+}
+
+fn device_template() -> liquid::Template {
     let template_file = include_str!("template.html");
-    let template = liquid::ParserBuilder::with_stdlib()
+    liquid::ParserBuilder::with_stdlib()
         .build()
         .unwrap()
         .parse(template_file)
-        .unwrap();
-    let mut devices = svdfiles
+        .unwrap()
+}
+
+/// Generate HTML pages for SVD files.
+///
+/// When `use_hashes` is set, device pages are regenerated only when content of SVD file
+/// changed since last run instead of comparing modification times
+pub fn svd2html(htmldir: &Path, svdfiles: &[PathBuf], use_hashes: bool) -> anyhow::Result<()> {
+    let svdfiles = svdfiles.iter().filter(|&f| f.is_file()).collect::<Vec<_>>();
+
+    if !htmldir.exists() {
+        std::fs::create_dir(htmldir)?;
+    }
+    let template = device_template();
+    let old_hashes = if use_hashes {
+        read_hashes(htmldir)
+    } else {
+        BTreeMap::new()
+    };
+    let (mut devices, hashes): (Vec<_>, Vec<_>) = svdfiles
         .par_iter()
         .map(|f| {
             let device = process_svd(f).unwrap();
-            generate_if_newer(&template, &device, htmldir).unwrap();
-            object!({
-                "name": device.get("name"),
-                "progress": device.get("progress"),
-                "fields_documented": device.get("fields_documented"),
-                "fields_total": device.get("fields_total"),
-            })
+            let hash = if use_hashes {
+                Some(generate_if_changed(&template, &device, htmldir, &old_hashes).unwrap())
+            } else {
+                generate_if_newer(&template, &device, htmldir).unwrap();
+                None
+            };
+            (
+                object!({
+                    "name": device.get("name"),
+                    "progress": device.get("progress"),
+                    "fields_documented": device.get("fields_documented"),
+                    "fields_total": device.get("fields_total"),
+                }),
+                hash,
+            )
         })
-        .collect::<Vec<_>>();
+        .unzip();
     devices.sort_by_key(|d| d.get_str("name").map(|s| s.to_lowercase()));
 
+    if use_hashes {
+        let mut new_hashes = old_hashes;
+        new_hashes.extend(hashes.into_iter().flatten());
+        write_hashes(htmldir, &new_hashes)?;
+    }
+
     let mut file = std::fs::File::create(htmldir.join("index.html"))?;
     generate_index_page(&devices, &mut file)?;
     Ok(())
 }
 
+/// Collect documentation coverage of each peripheral as
+/// `{peripheral: {documented, total, percent}}`
+fn peripherals_coverage(device: &Object) -> serde_json::Value {
+    let mut coverage = serde_json::Map::new();
+    if let Some(Value::Array(peripherals)) = device.get("peripherals") {
+        for p in peripherals {
+            if let Value::Object(p) = p {
+                let documented = p.get_i64("fields_documented").unwrap();
+                let total = p.get_i64("fields_total").unwrap();
+                let percent = if total > 0 {
+                    100. * (documented as f64 / total as f64)
+                } else {
+                    100.
+                };
+                coverage.insert(
+                    p.get_str("name").unwrap().into_owned(),
+                    serde_json::json!({
+                        "documented": documented,
+                        "total": total,
+                        "percent": percent,
+                    }),
+                );
+            }
+        }
+    }
+    serde_json::Value::Object(coverage)
+}
+
+/// Render shields.io-style SVG badge with documentation coverage of device
+fn coverage_badge(device: &Object) -> anyhow::Result<String> {
+    let documented = device.get_i64("fields_documented").unwrap();
+    let total = device.get_i64("fields_total").unwrap();
+    let percent = if total > 0 {
+        (100. * (documented as f64 / total as f64)).floor() as u32
+    } else {
+        100
+    };
+    let color = match percent {
+        90.. => "#4c1",
+        75.. => "#97ca00",
+        50.. => "#dfb317",
+        25.. => "#fe7d37",
+        _ => "#e05d44",
+    };
+    let template = liquid::ParserBuilder::with_stdlib()
+        .build()
+        .unwrap()
+        .parse(include_str!("badge.template.svg"))
+        .unwrap();
+    let globals = liquid::object!({ "percent": percent, "color": color });
+    Ok(template.render(&globals)?)
+}
+
+/// Print documentation coverage of peripherals in SVD file
+/// or write it as SVG badge if `badge` is specified
+pub fn coverage(svdfile: &Path, json: bool, badge: Option<&Path>) -> anyhow::Result<()> {
+    let device = parse_device(svdfile)?;
+    if let Some(badge) = badge {
+        std::fs::write(badge, coverage_badge(&device)?)?;
+    } else if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&peripherals_coverage(&device))?
+        );
+    } else if let Some(Value::Array(peripherals)) = device.get("peripherals") {
+        for p in peripherals {
+            if let Value::Object(p) = p {
+                println!(
+                    "{}: {}/{} ({}%)",
+                    p.get_str("name").unwrap(),
+                    p.get_i64("fields_documented").unwrap(),
+                    p.get_i64("fields_total").unwrap(),
+                    p.get_str("progress").unwrap(),
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
 fn enums_to_map(evs: &EnumeratedValues) -> BTreeMap<u64, &EnumeratedValue> {
     let mut map = BTreeMap::new();
     for ev in &evs.values {
@@ -592,3 +766,107 @@ fn enums_to_map(evs: &EnumeratedValues) -> BTreeMap<u64, &EnumeratedValue> {
 fn minimal_hole(map: &BTreeMap<u64, &EnumeratedValue>, width: u32) -> Option<u64> {
     (0..(1u64 << width)).find(|&v| !map.contains_key(&v))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SVD: &str = r"
+<device>
+    <name>dev</name>
+    <peripherals>
+        <peripheral>
+            <name>PeriphA</name>
+            <baseAddress>0x10000000</baseAddress>
+            <registers>
+                <register>
+                    <name>REG1</name>
+                    <addressOffset>0x0</addressOffset>
+                    <size>32</size>
+                    <access>read-write</access>
+                    <fields>
+                        <field>
+                            <name>F1</name>
+                            <bitOffset>0</bitOffset>
+                            <bitWidth>2</bitWidth>
+                            <writeConstraint>
+                                <range>
+                                    <minimum>0</minimum>
+                                    <maximum>2</maximum>
+                                </range>
+                            </writeConstraint>
+                        </field>
+                        <field>
+                            <name>F2</name>
+                            <bitOffset>2</bitOffset>
+                            <bitWidth>2</bitWidth>
+                        </field>
+                        <field>
+                            <name>F3</name>
+                            <bitOffset>4</bitOffset>
+                            <bitWidth>1</bitWidth>
+                            <access>read-only</access>
+                        </field>
+                        <field>
+                            <name>F4</name>
+                            <bitOffset>5</bitOffset>
+                            <bitWidth>1</bitWidth>
+                        </field>
+                    </fields>
+                </register>
+            </registers>
+        </peripheral>
+    </peripherals>
+</device>";
+
+    #[test]
+    fn coverage_json() -> anyhow::Result<()> {
+        let device = parse_device_xml(SVD)?;
+        assert_eq!(
+            peripherals_coverage(&device),
+            serde_json::json!({
+                "PeriphA": { "documented": 2, "total": 4, "percent": 50.0 }
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn coverage_badge_svg() -> anyhow::Result<()> {
+        let device = parse_device_xml(SVD)?;
+        let svg = coverage_badge(&device)?;
+        assert!(svg.contains(">50%</text>"));
+        assert!(svg.contains(r##"fill="#dfb317""##));
+        Ok(())
+    }
+
+    #[test]
+    fn no_regeneration_when_content_unchanged() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let htmldir = tmp.path().join("html");
+        std::fs::create_dir(&htmldir)?;
+        let svdfile = tmp.path().join("device.svd");
+        std::fs::copy("res/add/stm32l4x2.svd", &svdfile)?;
+        let template = device_template();
+
+        let device = process_svd(&svdfile)?;
+        let (pagename, hash) = generate_if_changed(&template, &device, &htmldir, &BTreeMap::new())?;
+        let hashes = BTreeMap::from([(pagename.clone(), hash)]);
+        write_hashes(&htmldir, &hashes)?;
+        assert_eq!(read_hashes(&htmldir), hashes);
+
+        // Mark page so regeneration can be detected and move mtime of SVD file
+        // forward without changing its contents
+        std::fs::write(htmldir.join(&pagename), "stale")?;
+        let mtime = filetime::FileTime::from_last_modification_time(&svdfile.metadata()?);
+        filetime::set_file_mtime(
+            &svdfile,
+            filetime::FileTime::from_unix_time(mtime.unix_seconds() + 10, 0),
+        )?;
+
+        let device = process_svd(&svdfile)?;
+        generate_if_changed(&template, &device, &htmldir, &hashes)?;
+        assert_eq!(std::fs::read_to_string(htmldir.join(&pagename))?, "stale");
+        Ok(())
+    }
+}
@@ -1,12 +1,48 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
 use std::str::FromStr;
 
 use anyhow::Ok;
-use svd_rs::Device;
+use svd_parser::expand::{derive_peripheral, BlockPath, FieldPath, Index, RegisterPath};
+use svd_rs::{
+    Cluster, Device, DimElement, Field, FieldInfo, Peripheral, PeripheralInfo, Register,
+    RegisterCluster, RegisterInfo, Usage,
+};
+
+use crate::cstruct::cstruct_cli::collect_registers;
+use crate::mmap::mmap_cli::get_periph_registers;
+use crate::patch::peripheral::alias_candidates;
+use crate::patch::register::{
+    access_inconsistencies, check_usage, duplicate_fields, enums_out_of_range, misaligned_offsets,
+};
+use crate::patch::{dim_index_mismatch, matchname};
 
 #[derive(Clone, Debug)]
 #[non_exhaustive]
 pub enum Request {
     DeviceName,
+    DeviceVersion,
+    PeripheralCount,
+    InterruptTable,
+    CheckEnumConstraints,
+    CheckInterruptBounds,
+    Size(String),
+    CheckClusterSize,
+    CheckDuplicateFields,
+    CheckHeaderPrefix,
+    CheckResetInEnum,
+    CheckEnumDescriptions,
+    SuggestArrays,
+    CheckAccessConsistency,
+    InterruptEnables,
+    SuggestSplits(String),
+    WriteMask(String),
+    CheckEnumUsage,
+    CheckDerivedRegisters,
+    SuggestFieldArrays,
+    CheckPeripheralArrays,
+    CheckOffsetAlignment,
+    SuggestAliases,
 }
 
 impl FromStr for Request {
@@ -14,15 +50,1295 @@ impl FromStr for Request {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "device-name" => Ok(Self::DeviceName),
-            _ => Err(anyhow::anyhow!("Unknown info request: {s}")),
+            "device-version" => Ok(Self::DeviceVersion),
+            "peripheral-count" => Ok(Self::PeripheralCount),
+            "interrupt-table" => Ok(Self::InterruptTable),
+            "check-enum-constraints" => Ok(Self::CheckEnumConstraints),
+            "check-interrupt-bounds" => Ok(Self::CheckInterruptBounds),
+            "check-cluster-size" => Ok(Self::CheckClusterSize),
+            "check-duplicate-fields" => Ok(Self::CheckDuplicateFields),
+            "check-header-prefix" => Ok(Self::CheckHeaderPrefix),
+            "check-reset-in-enum" => Ok(Self::CheckResetInEnum),
+            "check-enum-descriptions" => Ok(Self::CheckEnumDescriptions),
+            "suggest-arrays" => Ok(Self::SuggestArrays),
+            "check-access-consistency" => Ok(Self::CheckAccessConsistency),
+            "interrupt-enables" => Ok(Self::InterruptEnables),
+            "check-enum-usage" => Ok(Self::CheckEnumUsage),
+            "check-derived-registers" => Ok(Self::CheckDerivedRegisters),
+            "suggest-field-arrays" => Ok(Self::SuggestFieldArrays),
+            "check-peripheral-arrays" => Ok(Self::CheckPeripheralArrays),
+            "check-offset-alignment" => Ok(Self::CheckOffsetAlignment),
+            "suggest-aliases" => Ok(Self::SuggestAliases),
+            "suggest-splits" => Ok(Self::SuggestSplits("*".into())),
+            _ => {
+                if let Some(pname) = s.strip_prefix("size:") {
+                    Ok(Self::Size(pname.into()))
+                } else if let Some(rpath) = s.strip_prefix("write-mask:") {
+                    Ok(Self::WriteMask(rpath.into()))
+                } else if let Some(pspec) = s.strip_prefix("suggest-splits:") {
+                    Ok(Self::SuggestSplits(pspec.into()))
+                } else {
+                    Err(anyhow::anyhow!("Unknown info request: {s}"))
+                }
+            }
         }
     }
 }
 
 impl Request {
+    /// Does request need enumeratedValues to be parsed
+    pub fn needs_enums(&self) -> bool {
+        matches!(
+            self,
+            Self::CheckEnumConstraints
+                | Self::CheckResetInEnum
+                | Self::CheckEnumDescriptions
+                | Self::SuggestSplits(_)
+                | Self::CheckEnumUsage
+        )
+    }
+
+    /// Does request need register properties to be inherited from parents
+    pub fn needs_properties(&self) -> bool {
+        matches!(
+            self,
+            Self::Size(_)
+                | Self::CheckClusterSize
+                | Self::CheckResetInEnum
+                | Self::CheckAccessConsistency
+                | Self::WriteMask(_)
+                | Self::CheckEnumUsage
+                | Self::CheckOffsetAlignment
+        )
+    }
+
     pub fn process(&self, device: &Device) -> anyhow::Result<String> {
         match self {
             Self::DeviceName => Ok(device.name.to_string()),
+            Self::DeviceVersion => Ok(device.version.to_string()),
+            Self::PeripheralCount => {
+                let count = device
+                    .peripherals
+                    .iter()
+                    .map(|p| match p {
+                        Peripheral::Single(_) => 1,
+                        Peripheral::Array(_, d) => d.dim as usize,
+                    })
+                    .sum::<usize>();
+                Ok(count.to_string())
+            }
+            Self::InterruptTable => {
+                let mut table: BTreeMap<u32, (&str, Vec<&str>)> = BTreeMap::new();
+                for p in &device.peripherals {
+                    for i in &p.interrupt {
+                        let (name, pnames) = table.entry(i.value).or_insert((&i.name, Vec::new()));
+                        if *name != i.name {
+                            return Err(anyhow::anyhow!(
+                                "Interrupt {} has conflicting names {name} and {}",
+                                i.value,
+                                i.name
+                            ));
+                        }
+                        pnames.push(&p.name);
+                    }
+                }
+                let mut out = String::new();
+                for (value, (name, pnames)) in table {
+                    writeln!(out, "{value}\t{name}\t{}", pnames.join(",")).unwrap();
+                }
+                Ok(out)
+            }
+            Self::CheckEnumConstraints => {
+                let mut out = String::new();
+                for_each_register(device, |rpath, r| {
+                    for f in r.fields() {
+                        let fpath = rpath.new_field(&f.name);
+                        for ev in enums_out_of_range(f) {
+                            writeln!(
+                                out,
+                                "{fpath}: enumeratedValue {} ({:#x}) is out of writeConstraint range",
+                                ev.name,
+                                ev.value.unwrap_or_default(),
+                            )
+                            .unwrap();
+                        }
+                    }
+                });
+                Ok(out)
+            }
+            Self::CheckInterruptBounds => {
+                let mut out = String::new();
+                if let Some(num) = device.cpu.as_ref().and_then(|c| c.device_num_interrupts) {
+                    for p in &device.peripherals {
+                        for i in &p.interrupt {
+                            if i.value >= num {
+                                writeln!(
+                                    out,
+                                    "{}.{}: interrupt value {} is out of deviceNumInterrupts ({num})",
+                                    p.name, i.name, i.value,
+                                )
+                                .unwrap();
+                            }
+                        }
+                    }
+                }
+                Ok(out)
+            }
+            Self::Size(pname) => {
+                let mut p = device
+                    .get_peripheral(pname)
+                    .ok_or_else(|| anyhow::anyhow!("peripheral {pname} not found"))?
+                    .clone();
+                if let Some(dpath) = p.derived_from.clone() {
+                    derive_peripheral(&mut p, &dpath, &Index::create(device))?;
+                }
+                let size = p.registers.as_deref().map(footprint).unwrap_or(0);
+                Ok(size.to_string())
+            }
+            Self::WriteMask(path) => {
+                let mut mask = None;
+                for_each_register(device, |rpath, r| {
+                    if rpath.to_string() == *path {
+                        mask = Some((write_mask(r), r.properties.size.unwrap_or(32)));
+                    }
+                });
+                let (mask, size) =
+                    mask.ok_or_else(|| anyhow::anyhow!("register {path} not found"))?;
+                Ok(format!("{mask:#0w$x}", w = size as usize / 4 + 2))
+            }
+            Self::CheckClusterSize => {
+                let mut out = String::new();
+                for_each_cluster(device, |cpath, c| {
+                    if let Cluster::Array(c, d) = c {
+                        let size = footprint(&c.children);
+                        if size > d.dim_increment as u64 {
+                            writeln!(
+                                out,
+                                "{cpath}: children occupy {size:#x} bytes, more than dimIncrement {:#x}",
+                                d.dim_increment,
+                            )
+                            .unwrap();
+                        }
+                    }
+                });
+                Ok(out)
+            }
+            Self::CheckDuplicateFields => {
+                let mut out = String::new();
+                for_each_register(device, |rpath, r| {
+                    for fname in duplicate_fields(r) {
+                        writeln!(out, "{rpath}: field {fname} is duplicated").unwrap();
+                    }
+                });
+                Ok(out)
+            }
+            Self::CheckHeaderPrefix => {
+                let mut out = String::new();
+                if let Some(prefix) = device.header_definitions_prefix.as_deref() {
+                    for p in &device.peripherals {
+                        if !p.name.starts_with(prefix) {
+                            writeln!(
+                                out,
+                                "{}: name does not start with headerDefinitionsPrefix `{prefix}`",
+                                p.name
+                            )
+                            .unwrap();
+                        }
+                    }
+                }
+                Ok(out)
+            }
+            Self::CheckResetInEnum => {
+                let mut out = String::new();
+                for_each_register(device, |rpath, r| {
+                    let Some(reset) = r.properties.reset_value else {
+                        return;
+                    };
+                    for f in r.fields() {
+                        let fields = match f {
+                            Field::Single(f) => vec![f.clone()],
+                            Field::Array(f, d) => svd_rs::field::expand(f, d).collect(),
+                        };
+                        for f in fields {
+                            let value =
+                                (reset >> f.bit_offset()) & (u64::MAX >> (64 - f.bit_width()));
+                            if !reset_in_enums(&f, value) {
+                                let fpath = rpath.new_field(&f.name);
+                                writeln!(
+                                    out,
+                                    "{fpath}: reset value {value:#x} is not in enumeratedValues"
+                                )
+                                .unwrap();
+                            }
+                        }
+                    }
+                });
+                Ok(out)
+            }
+            Self::CheckEnumDescriptions => {
+                let mut out = String::new();
+                for_each_register(device, |rpath, r| {
+                    for f in r.fields() {
+                        let fpath = rpath.new_field(&f.name);
+                        for ev in f.enumerated_values.iter().flat_map(|evs| &evs.values) {
+                            if ev.description.as_deref().unwrap_or("").trim().is_empty() {
+                                writeln!(
+                                    out,
+                                    "{fpath}: enumeratedValue {} has no description",
+                                    ev.name
+                                )
+                                .unwrap();
+                            }
+                        }
+                    }
+                });
+                Ok(out)
+            }
+            Self::CheckAccessConsistency => {
+                let mut out = String::new();
+                for_each_register(device, |rpath, r| {
+                    for f in r.fields() {
+                        let access = f.access.or(r.properties.access).unwrap_or_default();
+                        for problem in access_inconsistencies(f, access) {
+                            let fpath = rpath.new_field(&f.name);
+                            writeln!(out, "{fpath}: {} field has {problem}", access.as_str())
+                                .unwrap();
+                        }
+                    }
+                });
+                Ok(out)
+            }
+            Self::CheckEnumUsage => {
+                let mut out = String::new();
+                for_each_register(device, |rpath, r| {
+                    for f in r.fields() {
+                        let access = f.access.or(r.properties.access).unwrap_or_default();
+                        for evs in &f.enumerated_values {
+                            if let Err(e) = check_usage(access, evs.usage) {
+                                writeln!(out, "{}: {e}", rpath.new_field(&f.name)).unwrap();
+                            }
+                        }
+                    }
+                });
+                Ok(out)
+            }
+            Self::CheckDerivedRegisters => {
+                let mut out = String::new();
+                for p in &device.peripherals {
+                    let (Some(d), Some(regs)) = (p.derived_from.as_ref(), p.registers.as_ref())
+                    else {
+                        continue;
+                    };
+                    if regs.is_empty() {
+                        continue;
+                    }
+                    let names = regs.iter().map(|rc| rc.name().as_str()).collect::<Vec<_>>();
+                    writeln!(
+                        out,
+                        "{}: derived from {d}, but has own registers or clusters: {}",
+                        p.name,
+                        names.join(", ")
+                    )
+                    .unwrap();
+                }
+                Ok(out)
+            }
+            Self::InterruptEnables => {
+                let mut out = String::new();
+                for p in &device.peripherals {
+                    if p.interrupt.is_empty() {
+                        continue;
+                    }
+                    let Some(regs) = get_periph_registers(p, &device.peripherals) else {
+                        continue;
+                    };
+                    let mut enables = Vec::new();
+                    walk_enable_fields(regs, &BlockPath::new(&p.name), &mut enables);
+                    for i in &p.interrupt {
+                        for (fpath, stem) in &enables {
+                            if interrupt_matches(&i.name, &p.name, stem) {
+                                writeln!(out, "{} ({}): {fpath}", i.name, i.value).unwrap();
+                            }
+                        }
+                    }
+                }
+                Ok(out)
+            }
+            Self::SuggestArrays => {
+                let mut out = String::new();
+                for p in &device.peripherals {
+                    if let Some(regs) = p.registers.as_ref() {
+                        suggest_arrays(&BlockPath::new(&p.name), regs, &mut out);
+                    }
+                }
+                for_each_cluster(device, |cpath, c| {
+                    suggest_arrays(cpath, &c.children, &mut out);
+                });
+                Ok(out)
+            }
+            Self::SuggestAliases => {
+                let mut out = String::new();
+                let mut suggest = |bpath: &BlockPath, children: &[RegisterCluster]| {
+                    for (canon, alias) in alias_candidates(children) {
+                        writeln!(
+                            out,
+                            "{bpath}: register {alias} duplicates {canon} and can be marked with `_alias: {{{canon}: {alias}}}`"
+                        )
+                        .unwrap();
+                    }
+                };
+                for p in &device.peripherals {
+                    if let Some(regs) = p.registers.as_ref() {
+                        suggest(&BlockPath::new(&p.name), regs);
+                    }
+                }
+                for_each_cluster(device, |cpath, c| suggest(cpath, &c.children));
+                Ok(out)
+            }
+            Self::CheckPeripheralArrays => {
+                let mut out = String::new();
+                for p in &device.peripherals {
+                    if let Peripheral::Array(info, dim) = p {
+                        check_peripheral_array(info, dim, &device.peripherals, &mut out);
+                    }
+                }
+                Ok(out)
+            }
+            Self::CheckOffsetAlignment => {
+                let mut out = String::new();
+                for_each_register(device, |rpath, r| {
+                    let Some(size) = r.properties.size else {
+                        return;
+                    };
+                    for problem in misaligned_offsets(r, size) {
+                        writeln!(out, "{rpath}: {problem}").unwrap();
+                    }
+                });
+                Ok(out)
+            }
+            Self::SuggestFieldArrays => {
+                let mut out = String::new();
+                for_each_register(device, |rpath, r| suggest_field_arrays(rpath, r, &mut out));
+                Ok(out)
+            }
+            Self::SuggestSplits(pspec) => {
+                let mut out = String::new();
+                for_each_register(device, |rpath, r| {
+                    if !matchname(&rpath.block.peripheral, pspec) {
+                        return;
+                    }
+                    for f in r.fields() {
+                        let flags = bit_flags(f);
+                        if !flags.is_empty() {
+                            writeln!(
+                                out,
+                                "{}: enumeratedValues look like bit flags ({}), consider `_split`",
+                                rpath.new_field(&f.name),
+                                flags.join(", ")
+                            )
+                            .unwrap();
+                        }
+                    }
+                });
+                Ok(out)
+            }
+        }
+    }
+}
+
+/// Bits of register which can be written by software
+fn write_mask(r: &RegisterInfo) -> u64 {
+    let access = r.properties.access.unwrap_or_default();
+    if r.fields.is_none() {
+        let size = r.properties.size.unwrap_or(32);
+        return if access.can_write() {
+            u64::MAX >> (64 - size)
+        } else {
+            0
+        };
+    }
+    r.fields()
+        .filter(|f| f.access.unwrap_or(access).can_write())
+        .fold(0, |mask, f| match f {
+            Field::Single(f) => mask | f.bitmask(),
+            Field::Array(f, d) => {
+                (0..d.dim).fold(mask, |mask, i| mask | f.bitmask() << (i * d.dim_increment))
+            }
+        })
+}
+
+/// Returns names of enumerated values of multibit field
+/// if each nonzero value of it has exactly one distinct bit set
+fn bit_flags(f: &FieldInfo) -> Vec<&str> {
+    if f.bit_range.width < 2 {
+        return Vec::new();
+    }
+    let mut bits = 0u64;
+    let mut flags = Vec::new();
+    for ev in f.enumerated_values.iter().flat_map(|evs| evs.values.iter()) {
+        match ev.value {
+            None | Some(0) => {}
+            Some(v) if v.is_power_of_two() && bits & v == 0 => {
+                bits |= v;
+                flags.push(ev.name.as_str());
+            }
+            Some(_) => return Vec::new(),
+        }
+    }
+    if flags.len() < 2 {
+        flags.clear();
+    }
+    flags
+}
+
+/// Checks that `dimIndex` of peripheral array matches `dim` and that peripherals
+/// derived from array and named as its elements are placed at element addresses
+fn check_peripheral_array(
+    info: &PeripheralInfo,
+    dim: &DimElement,
+    peripherals: &[Peripheral],
+    out: &mut String,
+) {
+    let name = &info.name;
+    if let Some(problem) = dim_index_mismatch(dim) {
+        writeln!(out, "{name}: {problem}").unwrap();
+    }
+    let indexes = dim.indexes().collect::<Vec<_>>();
+    let (pre, post) = match name.split_once("%s") {
+        Some((pre, post)) => (pre.trim_end_matches('['), post.trim_start_matches(']')),
+        None => (name.as_str(), ""),
+    };
+    for p in peripherals {
+        if p.derived_from.as_ref() != Some(name) {
+            continue;
+        }
+        let Some(idx) = p.name.strip_prefix(pre).and_then(|s| s.strip_suffix(post)) else {
+            continue;
+        };
+        let Some(i) = indexes.iter().position(|i| i.as_ref() == idx) else {
+            writeln!(out, "{}: index {idx} is not in dimIndex of {name}", p.name).unwrap();
+            continue;
+        };
+        let expected = info.base_address + i as u64 * dim.dim_increment as u64;
+        if p.base_address != expected {
+            writeln!(
+                out,
+                "{}: baseAddress {:#x} doesn't match element address {expected:#x} of {name} (dimIncrement {:#x})",
+                p.name, p.base_address, dim.dim_increment
+            )
+            .unwrap();
+        }
+    }
+}
+
+/// Replaces numbers in `name` with `*`. Returns `None` if there are no numbers
+fn index_spec(name: &str) -> Option<String> {
+    let mut spec = String::new();
+    let mut has_index = false;
+    for c in name.chars() {
+        if c.is_ascii_digit() {
+            if !spec.ends_with('*') {
+                spec.push('*');
+            }
+            has_index = true;
+        } else {
+            spec.push(c);
+        }
+    }
+    has_index.then_some(spec)
+}
+
+/// Finds single fields in register which differ only by index in name,
+/// have same width and are placed with constant bit stride
+fn suggest_field_arrays(rpath: &RegisterPath, r: &RegisterInfo, out: &mut String) {
+    let mut groups: BTreeMap<_, Vec<&FieldInfo>> = BTreeMap::new();
+    for f in r.fields() {
+        let Field::Single(f) = f else {
+            continue;
+        };
+        let Some(spec) = index_spec(&f.name) else {
+            continue;
+        };
+        groups.entry((spec, f.bit_width())).or_default().push(f);
+    }
+    for ((spec, _), mut fields) in groups {
+        if fields.len() < 2 {
+            continue;
+        }
+        fields.sort_by_key(|f| f.bit_offset());
+        let stride = fields[1].bit_offset() - fields[0].bit_offset();
+        if stride == 0
+            || fields
+                .windows(2)
+                .any(|w| w[1].bit_offset() - w[0].bit_offset() != stride)
+        {
+            continue;
+        }
+        let names = fields.iter().map(|f| f.name.as_str()).collect::<Vec<_>>();
+        writeln!(
+            out,
+            "{rpath}: fields {} can be collected with `_array: {spec}` (dimIncrement {stride})",
+            names.join(", "),
+        )
+        .unwrap();
+    }
+}
+
+/// Finds single registers in block which differ only by index in name,
+/// have same size and field layout and are placed with constant stride
+fn suggest_arrays(bpath: &BlockPath, children: &[RegisterCluster], out: &mut String) {
+    let mut groups: BTreeMap<_, Vec<&RegisterInfo>> = BTreeMap::new();
+    for rc in children {
+        let RegisterCluster::Register(Register::Single(r)) = rc else {
+            continue;
+        };
+        let Some(spec) = index_spec(&r.name) else {
+            continue;
+        };
+        let layout = r
+            .fields()
+            .map(|f| (f.bit_offset(), f.bit_width()))
+            .collect::<Vec<_>>();
+        groups
+            .entry((spec, r.properties.size, layout))
+            .or_default()
+            .push(r);
+    }
+    for ((spec, _, _), mut regs) in groups {
+        if regs.len() < 2 {
+            continue;
+        }
+        regs.sort_by_key(|r| r.address_offset);
+        let stride = regs[1].address_offset - regs[0].address_offset;
+        if stride == 0
+            || regs
+                .windows(2)
+                .any(|w| w[1].address_offset - w[0].address_offset != stride)
+        {
+            continue;
+        }
+        let names = regs.iter().map(|r| r.name.as_str()).collect::<Vec<_>>();
+        writeln!(
+            out,
+            "{bpath}: registers {} can be collected with `_array: {spec}` (dimIncrement {stride:#x})",
+            names.join(", "),
+        )
+        .unwrap();
+    }
+}
+
+/// Is value of field described by its readable enumeratedValues.
+/// Fields without such enumeratedValues or with derived ones are skipped
+fn reset_in_enums(f: &FieldInfo, value: u64) -> bool {
+    let mut checked = false;
+    for evs in &f.enumerated_values {
+        if evs.derived_from.is_some() || evs.usage() == Some(Usage::Write) {
+            continue;
+        }
+        checked = true;
+        if evs
+            .values
+            .iter()
+            .any(|ev| ev.is_default() || ev.value == Some(value))
+        {
+            return true;
+        }
+    }
+    !checked
+}
+
+/// Collects paths of fields named `*IE` or `*EN` with name stems before suffix
+fn walk_enable_fields(
+    children: &[RegisterCluster],
+    bpath: &BlockPath,
+    enables: &mut Vec<(FieldPath, String)>,
+) {
+    for rc in children {
+        match rc {
+            RegisterCluster::Register(r) => {
+                let rpath = bpath.new_register(&r.name);
+                for f in r.fields() {
+                    if let Some(stem) = f
+                        .name
+                        .strip_suffix("IE")
+                        .or_else(|| f.name.strip_suffix("EN"))
+                    {
+                        enables.push((rpath.new_field(&f.name), stem.trim_end_matches('_').into()));
+                    }
+                }
+            }
+            RegisterCluster::Cluster(c) => {
+                walk_enable_fields(&c.children, &bpath.new_cluster(&c.name), enables)
+            }
+        }
+    }
+}
+
+/// Heuristic: one of interrupt name parts (except peripheral name) starts with
+/// enable field stem or vice versa. Interrupt named after peripheral matches any enable
+fn interrupt_matches(iname: &str, pname: &str, stem: &str) -> bool {
+    let parts = iname
+        .split('_')
+        .filter(|part| !part.is_empty() && *part != pname)
+        .collect::<Vec<_>>();
+    if parts.is_empty() {
+        return true;
+    }
+    !stem.is_empty()
+        && parts
+            .iter()
+            .any(|part| part.starts_with(stem) || stem.starts_with(part))
+}
+
+/// Calls `f` for each register in device, including registers inside clusters
+fn for_each_register(device: &Device, mut f: impl FnMut(&RegisterPath, &Register)) {
+    fn walk(
+        children: &[RegisterCluster],
+        bpath: &BlockPath,
+        f: &mut impl FnMut(&RegisterPath, &Register),
+    ) {
+        for rc in children {
+            match rc {
+                RegisterCluster::Register(r) => f(&bpath.new_register(&r.name), r),
+                RegisterCluster::Cluster(c) => walk(&c.children, &bpath.new_cluster(&c.name), f),
+            }
+        }
+    }
+    for p in &device.peripherals {
+        if let Some(regs) = p.registers.as_ref() {
+            walk(regs, &BlockPath::new(&p.name), &mut f);
         }
     }
 }
+
+/// Size in bytes from offset 0 to the end of the last register
+fn footprint(children: &[RegisterCluster]) -> u64 {
+    let mut regs = Vec::new();
+    collect_registers(children, 0, "", &mut regs);
+    regs.iter()
+        .map(|(offset, size, _)| offset + *size as u64 / 8)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Calls `f` for each cluster in device, including nested clusters
+fn for_each_cluster(device: &Device, mut f: impl FnMut(&BlockPath, &Cluster)) {
+    fn walk(
+        children: &[RegisterCluster],
+        bpath: &BlockPath,
+        f: &mut impl FnMut(&BlockPath, &Cluster),
+    ) {
+        for rc in children {
+            if let RegisterCluster::Cluster(c) = rc {
+                let cpath = bpath.new_cluster(&c.name);
+                f(&cpath, c);
+                walk(&c.children, &cpath, f);
+            }
+        }
+    }
+    for p in &device.peripherals {
+        if let Some(regs) = p.registers.as_ref() {
+            walk(regs, &BlockPath::new(&p.name), &mut f);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SVD: &str = r"
+<device>
+    <name>dev</name>
+    <peripherals>
+        <peripheral>
+            <name>PeriphA</name>
+            <baseAddress>0x10000000</baseAddress>
+            <registers>
+                <register>
+                    <name>REG1</name>
+                    <addressOffset>0x0</addressOffset>
+                    <size>32</size>
+                    <fields>
+                        <field>
+                            <name>F1</name>
+                            <bitOffset>0</bitOffset>
+                            <bitWidth>2</bitWidth>
+                            <writeConstraint>
+                                <range>
+                                    <minimum>0</minimum>
+                                    <maximum>1</maximum>
+                                </range>
+                            </writeConstraint>
+                            <enumeratedValues>
+                                <enumeratedValue>
+                                    <name>A</name>
+                                    <value>1</value>
+                                </enumeratedValue>
+                                <enumeratedValue>
+                                    <name>B</name>
+                                    <value>3</value>
+                                </enumeratedValue>
+                            </enumeratedValues>
+                        </field>
+                    </fields>
+                </register>
+            </registers>
+        </peripheral>
+    </peripherals>
+</device>";
+
+    #[test]
+    fn check_enum_constraints() {
+        let device = svd_parser::parse(SVD).unwrap();
+        let response = Request::CheckEnumConstraints.process(&device).unwrap();
+        assert_eq!(
+            response,
+            "PeriphA.REG1.F1: enumeratedValue B (0x3) is out of writeConstraint range\n"
+        );
+    }
+
+    #[test]
+    fn check_duplicate_fields() {
+        let mut device = svd_parser::parse(SVD).unwrap();
+        let RegisterCluster::Register(r) =
+            &mut device.peripherals[0].registers.as_mut().unwrap()[0]
+        else {
+            unreachable!()
+        };
+        let fields = r.fields.as_mut().unwrap();
+        fields.push(fields[0].clone());
+        let response = Request::CheckDuplicateFields.process(&device).unwrap();
+        assert_eq!(response, "PeriphA.REG1: field F1 is duplicated\n");
+    }
+
+    #[test]
+    fn check_reset_in_enum() {
+        let svd = SVD.replace(
+            "<size>32</size>",
+            "<size>32</size><resetValue>0x2</resetValue>",
+        );
+        let device = svd_parser::parse(&svd).unwrap();
+        let response = Request::CheckResetInEnum.process(&device).unwrap();
+        assert_eq!(
+            response,
+            "PeriphA.REG1.F1: reset value 0x2 is not in enumeratedValues\n"
+        );
+        let svd = SVD.replace(
+            "<size>32</size>",
+            "<size>32</size><resetValue>0x3</resetValue>",
+        );
+        let device = svd_parser::parse(&svd).unwrap();
+        let response = Request::CheckResetInEnum.process(&device).unwrap();
+        assert_eq!(response, "");
+    }
+
+    #[test]
+    fn check_enum_descriptions() {
+        let svd = SVD.replace(
+            "<name>B</name>",
+            "<name>B</name><description>Value B</description>",
+        );
+        let device = svd_parser::parse(&svd).unwrap();
+        let response = Request::CheckEnumDescriptions.process(&device).unwrap();
+        assert_eq!(
+            response,
+            "PeriphA.REG1.F1: enumeratedValue A has no description\n"
+        );
+    }
+
+    #[test]
+    fn check_access_consistency() {
+        let check = |field: &str| {
+            let svd = SVD.replace(
+                "<bitWidth>2</bitWidth>",
+                &format!("<bitWidth>2</bitWidth>{field}"),
+            );
+            let device = svd_parser::parse(&svd).unwrap();
+            Request::CheckAccessConsistency.process(&device).unwrap()
+        };
+        assert_eq!(
+            check("<access>read-only</access>"),
+            "PeriphA.REG1.F1: read-only field has writeConstraint\n"
+        );
+        assert_eq!(
+            check("<access>write-only</access><readAction>clear</readAction>"),
+            "PeriphA.REG1.F1: write-only field has readAction\n"
+        );
+        assert_eq!(
+            check(
+                "<access>read-only</access><modifiedWriteValues>oneToClear</modifiedWriteValues>"
+            ),
+            "PeriphA.REG1.F1: read-only field has modifiedWriteValues\n\
+             PeriphA.REG1.F1: read-only field has writeConstraint\n"
+        );
+        assert_eq!(
+            check("<access>read-write</access><readAction>clear</readAction>"),
+            ""
+        );
+    }
+
+    #[test]
+    fn interrupt_enables() {
+        let svd = r#"
+<device>
+    <name>dev</name>
+    <peripherals>
+        <peripheral>
+            <name>TIM1</name>
+            <baseAddress>0x10000000</baseAddress>
+            <interrupt><name>TIM1_UP</name><value>1</value></interrupt>
+            <interrupt><name>TIM1_BRK</name><value>2</value></interrupt>
+            <registers>
+                <register>
+                    <name>DIER</name>
+                    <addressOffset>0xc</addressOffset>
+                    <fields>
+                        <field><name>UIE</name><bitOffset>0</bitOffset><bitWidth>1</bitWidth></field>
+                        <field><name>CC1IE</name><bitOffset>1</bitOffset><bitWidth>1</bitWidth></field>
+                        <field><name>BIE</name><bitOffset>7</bitOffset><bitWidth>1</bitWidth></field>
+                    </fields>
+                </register>
+            </registers>
+        </peripheral>
+        <peripheral derivedFrom="TIM1">
+            <name>TIM2</name>
+            <baseAddress>0x10001000</baseAddress>
+            <interrupt><name>TIM2</name><value>3</value></interrupt>
+        </peripheral>
+    </peripherals>
+</device>"#;
+        let device = svd_parser::parse(svd).unwrap();
+        let response = Request::InterruptEnables.process(&device).unwrap();
+        assert_eq!(
+            response,
+            "TIM1_UP (1): TIM1.DIER.UIE
+TIM1_BRK (2): TIM1.DIER.BIE
+TIM2 (3): TIM2.DIER.UIE
+TIM2 (3): TIM2.DIER.CC1IE
+TIM2 (3): TIM2.DIER.BIE
+"
+        );
+    }
+
+    static INT_SVD: &str = r"
+<device>
+    <name>dev</name>
+    <cpu>
+        <name>CM4</name>
+        <revision>r0p1</revision>
+        <endian>little</endian>
+        <mpuPresent>true</mpuPresent>
+        <fpuPresent>true</fpuPresent>
+        <nvicPrioBits>4</nvicPrioBits>
+        <vendorSystickConfig>false</vendorSystickConfig>
+        <deviceNumInterrupts>32</deviceNumInterrupts>
+    </cpu>
+    <peripherals>
+        <peripheral>
+            <name>PeriphA</name>
+            <baseAddress>0x10000000</baseAddress>
+            <interrupt>
+                <name>INT_A</name>
+                <value>31</value>
+            </interrupt>
+            <interrupt>
+                <name>INT_B</name>
+                <value>32</value>
+            </interrupt>
+        </peripheral>
+    </peripherals>
+</device>";
+
+    #[test]
+    fn check_interrupt_bounds() {
+        let device = svd_parser::parse(INT_SVD).unwrap();
+        let response = Request::CheckInterruptBounds.process(&device).unwrap();
+        assert_eq!(
+            response,
+            "PeriphA.INT_B: interrupt value 32 is out of deviceNumInterrupts (32)\n"
+        );
+    }
+
+    #[test]
+    fn device_info() {
+        let svd = INT_SVD
+            .replace("<name>dev</name>", "<name>dev</name><version>1.2</version>")
+            .replace(
+                "</peripherals>",
+                "<peripheral><dim>2</dim><dimIncrement>0x100</dimIncrement><name>PeriphB%s</name>\
+                <baseAddress>0x10001000</baseAddress>\
+                <interrupt><name>INT_A</name><value>31</value></interrupt>\
+                <interrupt><name>INT_C</name><value>0</value></interrupt>\
+                </peripheral></peripherals>",
+            );
+        let device = svd_parser::parse(&svd).unwrap();
+        let response = Request::DeviceVersion.process(&device).unwrap();
+        assert_eq!(response, "1.2");
+        let response = Request::PeripheralCount.process(&device).unwrap();
+        assert_eq!(response, "3");
+        let response = Request::InterruptTable.process(&device).unwrap();
+        assert_eq!(
+            response,
+            "0\tINT_C\tPeriphB%s\n31\tINT_A\tPeriphA,PeriphB%s\n32\tINT_B\tPeriphA\n"
+        );
+
+        let svd = svd.replace(
+            "<name>INT_C</name><value>0</value>",
+            "<name>INT_D</name><value>32</value>",
+        );
+        let device = svd_parser::parse(&svd).unwrap();
+        let err = Request::InterruptTable.process(&device).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Interrupt 32 has conflicting names INT_B and INT_D"
+        );
+    }
+
+    #[test]
+    fn check_header_prefix() {
+        let svd = INT_SVD
+            .replace(
+                "<name>dev</name>",
+                "<name>dev</name><headerDefinitionsPrefix>Dev_</headerDefinitionsPrefix>",
+            )
+            .replace("</peripherals>", "<peripheral><name>Dev_B</name><baseAddress>0x10001000</baseAddress></peripheral></peripherals>");
+        let device = svd_parser::parse(&svd).unwrap();
+        let response = Request::CheckHeaderPrefix.process(&device).unwrap();
+        assert_eq!(
+            response,
+            "PeriphA: name does not start with headerDefinitionsPrefix `Dev_`\n"
+        );
+    }
+
+    static SIZE_SVD: &str = r"
+<device>
+    <name>dev</name>
+    <peripherals>
+        <peripheral>
+            <name>PeriphA</name>
+            <baseAddress>0x10000000</baseAddress>
+            <registers>
+                <register>
+                    <name>CR</name>
+                    <addressOffset>0x0</addressOffset>
+                    <size>32</size>
+                </register>
+                <cluster>
+                    <dim>4</dim>
+                    <dimIncrement>0x10</dimIncrement>
+                    <name>CH%s</name>
+                    <addressOffset>0x20</addressOffset>
+                    <register>
+                        <dim>2</dim>
+                        <dimIncrement>0x4</dimIncrement>
+                        <name>DR%s</name>
+                        <addressOffset>0x4</addressOffset>
+                        <size>16</size>
+                    </register>
+                </cluster>
+            </registers>
+        </peripheral>
+    </peripherals>
+</device>";
+
+    #[test]
+    fn peripheral_size() {
+        let device = svd_parser::parse(SIZE_SVD).unwrap();
+        let response = Request::from_str("size:PeriphA")
+            .unwrap()
+            .process(&device)
+            .unwrap();
+        // CH3 at 0x50, DR1 at 0x8 inside it, 2 bytes wide
+        assert_eq!(response, "90");
+    }
+
+    #[test]
+    fn check_cluster_size() {
+        let svd = SIZE_SVD.replace(
+            "<dimIncrement>0x10</dimIncrement>",
+            "<dimIncrement>0x8</dimIncrement>",
+        );
+        let device = svd_parser::parse(&svd).unwrap();
+        let response = Request::CheckClusterSize.process(&device).unwrap();
+        assert_eq!(
+            response,
+            "PeriphA.CH%s: children occupy 0xa bytes, more than dimIncrement 0x8\n"
+        );
+        let device = svd_parser::parse(SIZE_SVD).unwrap();
+        let response = Request::CheckClusterSize.process(&device).unwrap();
+        assert_eq!(response, "");
+    }
+
+    #[test]
+    fn suggest_arrays() {
+        let reg = |name: &str, offset: u32| {
+            format!(
+                "<register><name>{name}</name><addressOffset>{offset:#x}</addressOffset><size>32</size>\
+                <fields><field><name>EN</name><bitOffset>0</bitOffset><bitWidth>1</bitWidth></field></fields></register>"
+            )
+        };
+        let regs = [
+            reg("CH0", 0x10),
+            reg("CH1", 0x14),
+            reg("CH2", 0x18),
+            reg("OUT1", 0x0),
+            reg("OUT2", 0x8),
+            reg("OUT3", 0xc),
+        ];
+        let svd = SIZE_SVD.replace(
+            "<register>\n                    <name>CR</name>",
+            &format!(
+                "{}<register>\n                    <name>CR</name>",
+                regs.concat()
+            ),
+        );
+        let device = svd_parser::parse(&svd).unwrap();
+        let response = Request::SuggestArrays.process(&device).unwrap();
+        assert_eq!(
+            response,
+            "PeriphA: registers CH0, CH1, CH2 can be collected with `_array: CH*` (dimIncrement 0x4)\n"
+        );
+    }
+
+    #[test]
+    fn suggest_aliases() {
+        let reg = |name: &str, offset: u32| {
+            format!(
+                "<register><name>{name}</name><addressOffset>{offset:#x}</addressOffset><size>32</size><resetValue>0x1</resetValue>\
+                <fields><field><name>EN</name><bitOffset>0</bitOffset><bitWidth>1</bitWidth></field></fields></register>"
+            )
+        };
+        let svd = SIZE_SVD.replace(
+            "<register>\n                    <name>CR</name>",
+            &format!(
+                "{}<register>\n                    <name>CR</name>",
+                [reg("KEY", 0x40), reg("KEY_MIRROR", 0x80)].concat()
+            ),
+        );
+        let device = svd_parser::parse(&svd).unwrap();
+        let response = Request::from_str("suggest-aliases")
+            .unwrap()
+            .process(&device)
+            .unwrap();
+        assert_eq!(
+            response,
+            "PeriphA: register KEY_MIRROR duplicates KEY and can be marked with `_alias: {KEY: KEY_MIRROR}`\n"
+        );
+    }
+
+    #[test]
+    fn suggest_splits() {
+        let field = |name: &str, values: &[(&str, u32)]| {
+            let values = values
+                .iter()
+                .map(|(n, v)| {
+                    format!("<enumeratedValue><name>{n}</name><value>{v}</value></enumeratedValue>")
+                })
+                .collect::<String>();
+            format!(
+                "<field><name>{name}</name><bitOffset>0</bitOffset><bitWidth>3</bitWidth>\
+                <enumeratedValues>{values}</enumeratedValues></field>"
+            )
+        };
+        let fields = [
+            field("IRQ", &[("None", 0), ("Tx", 1), ("Rx", 2), ("Err", 4)]),
+            field("MODE", &[("Off", 0), ("Slow", 1), ("Fast", 2), ("Auto", 3)]),
+        ];
+        let svd = SIZE_SVD.replace(
+            "<size>32</size>\n                </register>",
+            &format!(
+                "<size>32</size><fields>{}</fields></register>",
+                fields.concat()
+            ),
+        );
+        let device = svd_parser::parse(&svd).unwrap();
+        let response = Request::from_str("suggest-splits")
+            .unwrap()
+            .process(&device)
+            .unwrap();
+        assert_eq!(
+            response,
+            "PeriphA.CR.IRQ: enumeratedValues look like bit flags (Tx, Rx, Err), consider `_split`\n"
+        );
+        let response = Request::from_str("suggest-splits:Other*")
+            .unwrap()
+            .process(&device)
+            .unwrap();
+        assert_eq!(response, "");
+    }
+
+    #[test]
+    fn write_mask() {
+        let fields = "<fields>\
+            <field><name>MODE%s</name><dim>2</dim><dimIncrement>2</dimIncrement>\
+            <bitOffset>0</bitOffset><bitWidth>2</bitWidth></field>\
+            <field><name>BUSY</name><bitOffset>4</bitOffset><bitWidth>1</bitWidth><access>read-only</access></field>\
+            <field><name>CLR</name><bitOffset>8</bitOffset><bitWidth>1</bitWidth><access>write-only</access></field>\
+            </fields>";
+        let svd = SIZE_SVD.replace(
+            "<size>32</size>\n                </register>",
+            &format!("<size>32</size><access>read-write</access>{fields}</register>"),
+        );
+        let device = svd_parser::parse(&svd).unwrap();
+        let response = Request::from_str("write-mask:PeriphA.CR")
+            .unwrap()
+            .process(&device)
+            .unwrap();
+        assert_eq!(response, "0x0000010f");
+        let response = Request::from_str("write-mask:PeriphA.CH%s.DR%s")
+            .unwrap()
+            .process(&device)
+            .unwrap();
+        assert_eq!(response, "0xffff");
+        assert!(Request::from_str("write-mask:PeriphA.SR")
+            .unwrap()
+            .process(&device)
+            .is_err());
+    }
+
+    #[test]
+    fn check_derived_registers() {
+        let derived = r#"<peripheral derivedFrom="PeriphA">
+            <name>PeriphB</name>
+            <baseAddress>0x10001000</baseAddress>
+            <registers>
+                <register>
+                    <name>SR</name>
+                    <addressOffset>0x4</addressOffset>
+                </register>
+            </registers>
+        </peripheral>
+        <peripheral derivedFrom="PeriphA">
+            <name>PeriphC</name>
+            <baseAddress>0x10002000</baseAddress>
+        </peripheral>
+    </peripherals>"#;
+        let svd = SIZE_SVD.replace("</peripherals>", derived);
+        let device = svd_parser::parse(&svd).unwrap();
+        let response = Request::from_str("check-derived-registers")
+            .unwrap()
+            .process(&device)
+            .unwrap();
+        assert_eq!(
+            response,
+            "PeriphB: derived from PeriphA, but has own registers or clusters: SR\n"
+        );
+    }
+
+    #[test]
+    fn check_offset_alignment() {
+        let svd = SIZE_SVD.replace(
+            "<addressOffset>0x0</addressOffset>",
+            "<addressOffset>0x2</addressOffset>",
+        );
+        let device = svd_parser::parse(&svd).unwrap();
+        let response = Request::from_str("check-offset-alignment")
+            .unwrap()
+            .process(&device)
+            .unwrap();
+        assert_eq!(
+            response,
+            "PeriphA.CR: addressOffset 0x2 is not aligned to 32-bit size\n"
+        );
+    }
+
+    #[test]
+    fn check_peripheral_arrays() {
+        let peripherals = r#"<peripheral>
+            <dim>2</dim>
+            <dimIncrement>0x400</dimIncrement>
+            <dimIndex>1,2</dimIndex>
+            <name>TIM%s</name>
+            <baseAddress>0x40000000</baseAddress>
+        </peripheral>
+        <peripheral derivedFrom="TIM%s">
+            <name>TIM1</name>
+            <baseAddress>0x40000000</baseAddress>
+        </peripheral>
+        <peripheral derivedFrom="TIM%s">
+            <name>TIM2</name>
+            <baseAddress>0x40001000</baseAddress>
+        </peripheral>
+        <peripheral derivedFrom="TIM%s">
+            <name>TIM3</name>
+            <baseAddress>0x40000800</baseAddress>
+        </peripheral>
+    </peripherals>"#;
+        let svd = SIZE_SVD.replace("</peripherals>", peripherals);
+        let device = svd_parser::parse(&svd).unwrap();
+        let response = Request::from_str("check-peripheral-arrays")
+            .unwrap()
+            .process(&device)
+            .unwrap();
+        assert_eq!(
+            response,
+            "TIM2: baseAddress 0x40001000 doesn't match element address 0x40000400 of TIM%s (dimIncrement 0x400)\n\
+             TIM3: index 3 is not in dimIndex of TIM%s\n"
+        );
+    }
+
+    #[test]
+    fn suggest_field_arrays() {
+        let field = |name: &str, offset: u32| {
+            format!(
+                "<field><name>{name}</name><bitOffset>{offset}</bitOffset><bitWidth>1</bitWidth></field>"
+            )
+        };
+        let fields = [
+            field("CH0EN", 0),
+            field("CH1EN", 4),
+            field("CH2EN", 8),
+            field("CH3EN", 12),
+            field("OUT1", 16),
+            field("OUT2", 17),
+            field("OUT3", 19),
+        ];
+        let svd = SIZE_SVD.replace(
+            "<size>32</size>\n                </register>",
+            &format!(
+                "<size>32</size><fields>{}</fields></register>",
+                fields.concat()
+            ),
+        );
+        let device = svd_parser::parse(&svd).unwrap();
+        let response = Request::from_str("suggest-field-arrays")
+            .unwrap()
+            .process(&device)
+            .unwrap();
+        assert_eq!(
+            response,
+            "PeriphA.CR: fields CH0EN, CH1EN, CH2EN, CH3EN can be collected with `_array: CH*EN` (dimIncrement 4)\n"
+        );
+    }
+
+    #[test]
+    fn check_enum_usage() {
+        let field = |name: &str, access: &str, usage: &str| {
+            format!(
+                "<field><name>{name}</name><bitOffset>0</bitOffset><bitWidth>1</bitWidth>\
+                <access>{access}</access><enumeratedValues><usage>{usage}</usage>\
+                <enumeratedValue><name>A</name><value>0</value></enumeratedValue>\
+                </enumeratedValues></field>"
+            )
+        };
+        let fields = [
+            field("RO", "read-only", "write"),
+            field("WO", "write-only", "write"),
+            field("RW", "read-write", "read"),
+        ];
+        let svd = SIZE_SVD.replace(
+            "<size>32</size>\n                </register>",
+            &format!(
+                "<size>32</size><fields>{}</fields></register>",
+                fields.concat()
+            ),
+        );
+        let device = svd_parser::parse(&svd).unwrap();
+        let response = Request::CheckEnumUsage.process(&device).unwrap();
+        assert_eq!(
+            response,
+            "PeriphA.CR.RO: EnumeratedValues usage Some(Write) is incompatible with access ReadOnly\n"
+        );
+    }
+}
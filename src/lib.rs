@@ -4,18 +4,24 @@ use std::{fs::File, path::Path, str::FromStr};
 
 pub mod common;
 pub mod convert;
+pub mod cstruct;
+pub mod enums;
 pub mod html;
 pub mod info;
 pub mod interrupts;
 pub mod makedeps;
+pub mod merge;
 pub mod mmap;
 pub mod patch;
+pub mod symbols;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum ConfigFormat {
     Yaml,
     Json,
+    #[cfg(feature = "toml")]
+    Toml,
 }
 
 impl FromStr for ConfigFormat {
@@ -24,6 +30,8 @@ impl FromStr for ConfigFormat {
         match s {
             "yml" | "yaml" | "YAML" => Ok(Self::Yaml),
             "json" | "JSON" => Ok(Self::Json),
+            #[cfg(feature = "toml")]
+            "toml" | "TOML" => Ok(Self::Toml),
             _ => Err(anyhow!("Unknown config file format")),
         }
     }
@@ -43,6 +51,8 @@ pub(crate) fn get_encoder_config(
         let config_map: std::collections::HashMap<String, String> = match config_format {
             ConfigFormat::Yaml => serde_yaml::from_str(&config)?,
             ConfigFormat::Json => serde_json::from_str(&config)?,
+            #[cfg(feature = "toml")]
+            ConfigFormat::Toml => toml::from_str(&config)?,
         };
 
         let mut config = svd_encoder::Config::default();
@@ -58,3 +68,21 @@ pub(crate) fn get_encoder_config(
 
 #[cfg(test)]
 mod test_utils;
+
+#[cfg(all(test, feature = "toml"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toml_encoder_config() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("format.toml");
+        std::fs::write(&path, "register_address_offset = \"Dec\"\n")?;
+        let config = get_encoder_config(Some(&path))?;
+        assert_eq!(
+            config.register_address_offset,
+            svd_encoder::NumberFormat::Dec
+        );
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use crate::patch::yaml_includes;
+use crate::patch::yaml_includes_with_base_dir;
 use anyhow::{anyhow, Result};
 use std::io::{Read, Write};
 use std::{
@@ -30,7 +30,8 @@ fn write_file(file_name: &Path, deps: Vec<PathBuf>) -> Result<()> {
     Ok(())
 }
 
-pub fn makedeps(yaml_file: &Path, deps_file: &Path) -> Result<()> {
+/// Write dependencies of `yaml_file`. Relative paths are resolved against `base_dir` if it is set
+pub fn makedeps(yaml_file: &Path, deps_file: &Path, base_dir: Option<&Path>) -> Result<()> {
     let f = File::open(yaml_file)?;
     let mut contents = String::new();
     (&f).read_to_string(&mut contents)?;
@@ -42,7 +43,7 @@ pub fn makedeps(yaml_file: &Path, deps_file: &Path) -> Result<()> {
                 Yaml::String(yaml_file.to_str().unwrap().into()),
             );
 
-            let deps = yaml_includes(root)?;
+            let deps = yaml_includes_with_base_dir(root, base_dir)?;
 
             write_file(deps_file, deps)?;
             Ok(())
@@ -67,9 +68,9 @@ mod tests {
         let test_dir = test_utils::res_dir().join(Path::new("makedeps"));
         let yaml_file = test_dir.join(Path::new("test.yaml"));
 
-        makedeps(&yaml_file, &deps_file)?;
+        makedeps(&yaml_file, &deps_file, None)?;
 
-        let deps: String = fs::read_to_string(deps_file)?.parse()?;
+        let deps: String = fs::read_to_string(&deps_file)?.parse()?;
         let exp_string = format!(
             "{}/test.d: {} {}\n",
             out_dir.path().display(),
@@ -79,6 +80,12 @@ mod tests {
 
         assert_eq!(deps, exp_string);
 
+        // Includes are resolved against base directory instead of YAML directory
+        let moved_yaml = out_dir.path().join("test.yaml");
+        fs::write(&moved_yaml, "_include:\n  - inc1.yaml\n")?;
+        makedeps(&moved_yaml, &deps_file, Some(&test_dir.join("sub-tests")))?;
+        assert_eq!(fs::read_to_string(&deps_file)?, exp_string);
+
         Ok(())
     }
 }
@@ -0,0 +1,112 @@
+use crate::common::svd_reader;
+use crate::get_encoder_config;
+use anyhow::{anyhow, Context, Result};
+use std::path::Path;
+use svd_parser::svd::{Device, Peripheral};
+
+/// Import all peripherals of `extra` SVD file into `base` and write result to `out_path`
+pub fn merge(
+    base: &Path,
+    extra: &Path,
+    out_path: &Path,
+    format_config: Option<&Path>,
+) -> Result<()> {
+    let mut device = svd_reader::device(base)?;
+    let extra_device = svd_reader::device(extra)?;
+    merge_devices(&mut device, &extra_device)
+        .with_context(|| format!("Merging {} into {}", extra.display(), base.display()))?;
+    let config = get_encoder_config(format_config)?;
+    let svd = svd_encoder::encode_with_config(&device, &config)?;
+    std::fs::write(out_path, svd).with_context(|| format!("Writing {}", out_path.display()))
+}
+
+/// Add peripherals of `extra` to `base`.
+/// Peripherals with same name or overlapping address ranges are reported as errors
+pub fn merge_devices(base: &mut Device, extra: &Device) -> Result<()> {
+    for p in &extra.peripherals {
+        for bp in &base.peripherals {
+            if bp.name == p.name {
+                return Err(anyhow!("Peripheral {} is present in both devices", p.name));
+            }
+            let (start, end) = address_range(p);
+            let (bstart, bend) = address_range(bp);
+            if start < bend && bstart < end {
+                return Err(anyhow!(
+                    "Peripheral {} ({start:#x}..{end:#x}) overlaps {} ({bstart:#x}..{bend:#x})",
+                    p.name,
+                    bp.name,
+                ));
+            }
+        }
+    }
+    base.peripherals.extend(extra.peripherals.iter().cloned());
+    Ok(())
+}
+
+/// Range of addresses covered by address blocks of peripheral.
+/// Peripheral without address blocks occupies only its base address
+fn address_range(p: &Peripheral) -> (u64, u64) {
+    let blocks = p.address_block.as_deref().unwrap_or_default();
+    let start = blocks
+        .iter()
+        .map(|ab| p.base_address + ab.offset as u64)
+        .min()
+        .unwrap_or(p.base_address);
+    let end = blocks
+        .iter()
+        .map(|ab| p.base_address + ab.offset as u64 + ab.size as u64)
+        .max()
+        .unwrap_or(p.base_address + 1);
+    (start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(peripherals: &[(&str, u64)]) -> Device {
+        let peripherals = peripherals
+            .iter()
+            .map(|(name, address)| {
+                format!(
+                    "<peripheral><name>{name}</name><baseAddress>{address:#x}</baseAddress>\
+                    <addressBlock><offset>0x0</offset><size>0x400</size><usage>registers</usage></addressBlock>\
+                    </peripheral>"
+                )
+            })
+            .collect::<String>();
+        svd_parser::parse(&format!(
+            "<device><name>dev</name><peripherals>{peripherals}</peripherals></device>"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn merge() {
+        let mut base = device(&[("UART0", 0x4000_0000), ("UART1", 0x4000_0400)]);
+        let extra = device(&[("SPI0", 0x4000_0800)]);
+        merge_devices(&mut base, &extra).unwrap();
+        let names = base
+            .peripherals
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["UART0", "UART1", "SPI0"]);
+    }
+
+    #[test]
+    fn collisions() {
+        let mut base = device(&[("UART0", 0x4000_0000)]);
+        let err = merge_devices(&mut base, &device(&[("UART0", 0x5000_0000)])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Peripheral UART0 is present in both devices"
+        );
+        let err = merge_devices(&mut base, &device(&[("SPI0", 0x4000_0200)])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Peripheral SPI0 (0x40000200..0x40000600) overlaps UART0 (0x40000000..0x40000400)"
+        );
+        assert_eq!(base.peripherals.len(), 1);
+    }
+}
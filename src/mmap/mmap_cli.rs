@@ -1,15 +1,62 @@
 use crate::common::svd_reader;
 use crate::common::{str_utils, svd_utils};
-use anyhow::{Context, Result};
-use std::{fs::File, io::Read, path::Path};
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::{fs::File, io::Read, path::Path, str::FromStr};
 use svd::PeripheralInfo;
-use svd_parser::svd::{self, Cluster, Field, Peripheral, Register, RegisterCluster, RegisterInfo};
+use svd_parser::svd::{
+    self, Access, Cluster, Field, FieldInfo, Peripheral, Register, RegisterCluster, RegisterInfo,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MmapFormat {
+    /// Sorted lines for diffing
+    #[default]
+    Text,
+    /// Tree of peripherals, registers and fields
+    Json,
+    /// Row for every field followed by interrupts
+    Csv,
+}
+
+impl FromStr for MmapFormat {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" | "TEXT" => Ok(Self::Text),
+            "json" | "JSON" => Ok(Self::Json),
+            "csv" | "CSV" => Ok(Self::Csv),
+            _ => Err(anyhow!("Unknown mmap format")),
+        }
+    }
+}
+
+/// Counters of documented fields, same as in `coverage` command
+#[derive(Clone, Copy, Debug, Default)]
+struct CoveredFields {
+    documented: usize,
+    total: usize,
+}
+
+impl CoveredFields {
+    fn add(&mut self, other: Self) {
+        self.documented += other.documented;
+        self.total += other.total;
+    }
+
+    fn to_json(self) -> Value {
+        json!({ "documented": self.documented, "total": self.total })
+    }
+}
 
 /// Output sorted text of every peripheral, register, field, and interrupt
 /// in the device, such that automated diffing is possible.
-pub fn parse_device(svd_file: &Path) -> Result<()> {
+pub fn parse_device(svd_file: &Path, format: MmapFormat) -> Result<()> {
     let mut file = File::open(svd_file).expect("svd file doesn't exist");
-    match get_text(&mut file) {
+    match get_text(&mut file, format) {
         Err(e) => {
             let path_str = svd_file.display();
             Err(e).with_context(|| format!("Parsing {path_str}"))
@@ -21,9 +68,13 @@ pub fn parse_device(svd_file: &Path) -> Result<()> {
     }
 }
 
-fn get_text<R: Read>(svd: &mut R) -> Result<String> {
+fn get_text<R: Read>(svd: &mut R, format: MmapFormat) -> Result<String> {
     let peripherals = svd_reader::peripherals(svd)?;
-    Ok(to_text(&peripherals))
+    Ok(match format {
+        MmapFormat::Text => to_text(&peripherals),
+        MmapFormat::Json => serde_json::to_string_pretty(&to_json(&peripherals))?,
+        MmapFormat::Csv => to_csv(&peripherals),
+    })
 }
 
 fn to_text(peripherals: &[Peripheral]) -> String {
@@ -52,7 +103,268 @@ fn to_text(peripherals: &[Peripheral]) -> String {
     mmap.join("\n")
 }
 
-fn get_periph_registers<'a>(
+fn to_json(peripherals: &[Peripheral]) -> Value {
+    let mut out = Vec::new();
+    for p in peripherals {
+        match p {
+            Peripheral::Single(p) => out.push(peripheral_json(p, peripherals)),
+            Peripheral::Array(p, d) => {
+                for pi in svd::peripheral::expand(p, d) {
+                    out.push(peripheral_json(&pi, peripherals));
+                }
+            }
+        }
+    }
+    Value::Array(out)
+}
+
+fn peripheral_json(peripheral: &PeripheralInfo, peripherals: &[Peripheral]) -> Value {
+    let interrupts = peripheral
+        .interrupt
+        .iter()
+        .map(|i| json!({ "name": i.name, "value": i.value, "description": i.description }))
+        .collect::<Vec<_>>();
+    let mut coverage = CoveredFields::default();
+    let (registers, clusters) = registers_json(
+        peripheral.base_address,
+        get_periph_registers(peripheral, peripherals).as_deref(),
+        &mut coverage,
+    );
+    json!({
+        "name": peripheral.name,
+        "baseAddress": peripheral.base_address,
+        "interrupts": interrupts,
+        "registers": registers,
+        "clusters": clusters,
+        "coverage": coverage.to_json(),
+    })
+}
+
+fn registers_json(
+    base_address: u64,
+    registers: Option<&[RegisterCluster]>,
+    coverage: &mut CoveredFields,
+) -> (Vec<Value>, Vec<Value>) {
+    let mut regs = Vec::new();
+    let mut clusters = Vec::new();
+    for rc in registers.unwrap_or_default() {
+        match rc {
+            RegisterCluster::Register(Register::Single(r)) => {
+                regs.push(register_json(base_address, r, coverage));
+            }
+            RegisterCluster::Register(Register::Array(r, d)) => {
+                for ri in svd::register::expand(r, d) {
+                    regs.push(register_json(base_address, &ri, coverage));
+                }
+            }
+            RegisterCluster::Cluster(Cluster::Single(c)) => {
+                clusters.push(cluster_json(base_address, c, &c.children, coverage));
+            }
+            RegisterCluster::Cluster(Cluster::Array(c, d)) => {
+                for ci in svd::cluster::expand(c, d) {
+                    clusters.push(cluster_json(base_address, &ci, &c.children, coverage));
+                }
+            }
+        }
+    }
+    (regs, clusters)
+}
+
+fn cluster_json(
+    base_address: u64,
+    c: &svd::ClusterInfo,
+    children: &[RegisterCluster],
+    coverage: &mut CoveredFields,
+) -> Value {
+    let address = base_address + c.address_offset as u64;
+    let mut ccoverage = CoveredFields::default();
+    let (registers, clusters) = registers_json(address, Some(children), &mut ccoverage);
+    coverage.add(ccoverage);
+    json!({
+        "name": c.name,
+        "addressOffset": c.address_offset,
+        "address": address,
+        "registers": registers,
+        "clusters": clusters,
+        "coverage": ccoverage.to_json(),
+    })
+}
+
+fn register_json(base_address: u64, r: &RegisterInfo, coverage: &mut CoveredFields) -> Value {
+    let mut rcoverage = CoveredFields::default();
+    let mut fields = Vec::new();
+    for f in r.fields() {
+        let fields_info = match f {
+            Field::Single(f) => vec![f.clone()],
+            Field::Array(f, d) => svd::field::expand(f, d).collect(),
+        };
+        for f in fields_info {
+            let documented = is_documented(&f, r.properties.access);
+            rcoverage.total += 1;
+            if documented {
+                rcoverage.documented += 1;
+            }
+            fields.push(json!({
+                "name": f.name,
+                "bitOffset": f.bit_offset(),
+                "bitWidth": f.bit_width(),
+                "access": f.access.map(Access::as_str),
+                "coverage": documented,
+            }));
+        }
+    }
+    coverage.add(rcoverage);
+    json!({
+        "name": r.name,
+        "addressOffset": r.address_offset,
+        "address": base_address + r.address_offset as u64,
+        "access": r.properties.access.map(Access::as_str),
+        "fields": fields,
+        "coverage": rcoverage.to_json(),
+    })
+}
+
+/// Flat table of fields with columns
+/// `peripheral,register,address,field,bitOffset,bitWidth,access,covered,description`.
+/// Interrupts are listed in separate table after empty line
+fn to_csv(peripherals: &[Peripheral]) -> String {
+    let mut out = String::from(
+        "peripheral,register,address,field,bitOffset,bitWidth,access,covered,description\n",
+    );
+    let mut interrupts = String::new();
+    for p in peripherals {
+        match p {
+            Peripheral::Single(p) => peripheral_csv(p, peripherals, &mut out, &mut interrupts),
+            Peripheral::Array(p, d) => {
+                for pi in svd::peripheral::expand(p, d) {
+                    peripheral_csv(&pi, peripherals, &mut out, &mut interrupts);
+                }
+            }
+        }
+    }
+    if !interrupts.is_empty() {
+        out.push_str("\ninterrupt,value,peripheral,description\n");
+        out.push_str(&interrupts);
+    }
+    out
+}
+
+fn peripheral_csv(
+    peripheral: &PeripheralInfo,
+    peripherals: &[Peripheral],
+    out: &mut String,
+    interrupts: &mut String,
+) {
+    for i in &peripheral.interrupt {
+        csv_row(
+            interrupts,
+            &[
+                &i.name,
+                &i.value.to_string(),
+                &peripheral.name,
+                &str_utils::get_description(&i.description),
+            ],
+        );
+    }
+    registers_csv(
+        &peripheral.name,
+        peripheral.base_address,
+        "",
+        get_periph_registers(peripheral, peripherals).as_deref(),
+        out,
+    );
+}
+
+/// Registers inside clusters are named by their path from peripheral
+fn registers_csv(
+    pname: &str,
+    base_address: u64,
+    prefix: &str,
+    registers: Option<&[RegisterCluster]>,
+    out: &mut String,
+) {
+    for rc in registers.unwrap_or_default() {
+        match rc {
+            RegisterCluster::Register(Register::Single(r)) => {
+                register_csv(pname, base_address, prefix, r, out);
+            }
+            RegisterCluster::Register(Register::Array(r, d)) => {
+                for ri in svd::register::expand(r, d) {
+                    register_csv(pname, base_address, prefix, &ri, out);
+                }
+            }
+            RegisterCluster::Cluster(Cluster::Single(c)) => registers_csv(
+                pname,
+                base_address + c.address_offset as u64,
+                &format!("{prefix}{}.", c.name),
+                Some(&c.children),
+                out,
+            ),
+            RegisterCluster::Cluster(Cluster::Array(c, d)) => {
+                for ci in svd::cluster::expand(c, d) {
+                    registers_csv(
+                        pname,
+                        base_address + ci.address_offset as u64,
+                        &format!("{prefix}{}.", ci.name),
+                        Some(&c.children),
+                        out,
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn register_csv(pname: &str, base_address: u64, prefix: &str, r: &RegisterInfo, out: &mut String) {
+    let rname = format!("{prefix}{}", r.name);
+    let addr = str_utils::format_address(base_address + r.address_offset as u64);
+    for f in r.fields() {
+        let fields_info = match f {
+            Field::Single(f) => vec![f.clone()],
+            Field::Array(f, d) => svd::field::expand(f, d).collect(),
+        };
+        for f in fields_info {
+            let access = f.access.or(r.properties.access);
+            csv_row(
+                out,
+                &[
+                    pname,
+                    &rname,
+                    &addr,
+                    &f.name,
+                    &f.bit_offset().to_string(),
+                    &f.bit_width().to_string(),
+                    access.map(Access::as_str).unwrap_or_default(),
+                    &is_documented(&f, r.properties.access).to_string(),
+                    &str_utils::get_description(&f.description),
+                ],
+            );
+        }
+    }
+}
+
+fn csv_row(out: &mut String, cells: &[&str]) {
+    let cells = cells.iter().map(|c| csv_escape(c)).collect::<Vec<_>>();
+    writeln!(out, "{}", cells.join(",")).unwrap();
+}
+
+/// Quote cell containing commas, quotes or line breaks as in RFC 4180
+fn csv_escape(cell: &str) -> Cow<'_, str> {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\"")).into()
+    } else {
+        cell.into()
+    }
+}
+
+/// Field is documented if it has enumeratedValues, writeConstraint or is read-only
+fn is_documented(f: &FieldInfo, raccess: Option<Access>) -> bool {
+    !f.enumerated_values.is_empty()
+        || f.write_constraint.is_some()
+        || f.access.or(raccess) == Some(Access::ReadOnly)
+}
+
+pub(crate) fn get_periph_registers<'a>(
     peripheral: &'a PeripheralInfo,
     peripheral_list: &'a [Peripheral],
 ) -> &'a Option<Vec<RegisterCluster>> {
@@ -280,7 +592,58 @@ INTERRUPT 002: INT_B2 (PeriphB): Interrupt B2";
     #[test]
     fn mmap() {
         let mut svd = SVD.as_bytes();
-        let actual_mmap = get_text(&mut svd).unwrap();
+        let actual_mmap = get_text(&mut svd, MmapFormat::Text).unwrap();
         assert_eq!(EXPECTED_MMAP, actual_mmap);
     }
+
+    static EXPECTED_CSV: &str = r#"peripheral,register,address,field,bitOffset,bitWidth,access,covered,description
+PeriphA,REG1,0x10000010,F1,5,2,,false,Field 1
+PeriphA,REG1,0x10000010,F2,10,1,read-only,true,"Field ""2"", flag"
+
+interrupt,value,peripheral,description
+INT_A1,1,PeriphA,Interrupt A1
+INT_B2,2,PeriphB,Interrupt B2
+"#;
+
+    #[test]
+    fn mmap_csv() {
+        let svd = SVD
+            .replace(
+                "<bitWidth>1</bitWidth>",
+                "<bitWidth>1</bitWidth><access>read-only</access>",
+            )
+            .replace("Field 2", "Field \"2\", flag");
+        let actual = get_text(&mut svd.as_bytes(), MmapFormat::Csv).unwrap();
+        assert_eq!(EXPECTED_CSV, actual);
+    }
+
+    #[test]
+    fn mmap_json() {
+        let svd = SVD.replace(
+            "<bitWidth>1</bitWidth>",
+            "<bitWidth>1</bitWidth><access>read-only</access>",
+        );
+        let actual: Value =
+            serde_json::from_str(&get_text(&mut svd.as_bytes(), MmapFormat::Json).unwrap())
+                .unwrap();
+        assert_eq!(actual.as_array().unwrap().len(), 2);
+        let periph_a = &actual[0];
+        assert_eq!(periph_a["name"], "PeriphA");
+        assert_eq!(periph_a["baseAddress"], 0x1000_0000);
+        assert_eq!(periph_a["interrupts"][0]["name"], "INT_A1");
+        assert_eq!(periph_a["coverage"], json!({ "documented": 1, "total": 2 }));
+        let reg1 = &periph_a["registers"][0];
+        assert_eq!(reg1["addressOffset"], 0x10);
+        assert_eq!(reg1["address"], 0x1000_0010);
+        assert_eq!(
+            reg1["fields"][1],
+            json!({
+                "name": "F2",
+                "bitOffset": 10,
+                "bitWidth": 1,
+                "access": "read-only",
+                "coverage": true,
+            })
+        );
+    }
 }
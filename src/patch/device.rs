@@ -1,6 +1,7 @@
-use anyhow::{anyhow, Context};
+use anyhow::{anyhow, Context, Result};
 use itertools::Itertools;
-use svd_parser::svd::{Device, Peripheral, PeripheralInfo};
+use svd_parser::expand::{derive_enumerated_values, derive_peripheral, FieldPath, Index};
+use svd_parser::svd::{Access, Device, Peripheral, PeripheralInfo, RegisterCluster, Usage};
 use yaml_rust::{yaml::Hash, Yaml};
 
 use std::collections::HashSet;
@@ -8,10 +9,29 @@ use std::{fs::File, io::Read, path::Path};
 
 use super::iterators::{MatchIter, Matched};
 use super::peripheral::{PeripheralExt, RegisterBlockExt};
-use super::yaml_ext::{AsType, GetVal};
-use super::{abspath, matchname, Config, PatchResult, Spec, VAL_LVL};
-use super::{make_address_block, make_address_blocks, make_cpu, make_interrupt, make_peripheral};
-use super::{make_dim_element, modify_dim_element, modify_register_properties};
+use super::register::{check_usage, RegisterInfoExt};
+use super::yaml_ext::{parse_i64, AsType, GetVal};
+use super::{abspath, delete_absent, matchname, Config, PatchResult, Spec, VAL_LVL};
+use super::{
+    clear_cpu, make_address_block, make_address_blocks, make_cpu, make_interrupt, make_peripheral,
+};
+use super::{make_dim_element, modify_dim_element, modify_register_properties, MatchCaseGuard};
+
+/// Known vendors as (shortcut, vendor, vendorID)
+const VENDORS: &[(&str, &str, &str)] = &[
+    ("stmicro", "STMicroelectronics", "ST"),
+    ("nordic", "Nordic Semiconductor", "Nordic"),
+    ("nxp", "NXP Semiconductors", "NXP"),
+    ("microchip", "Microchip Technology", "Microchip"),
+    ("atmel", "Atmel", "Atmel"),
+    ("espressif", "Espressif Systems", "Espressif"),
+    ("gigadevice", "GigaDevice Semiconductor", "GD"),
+    ("infineon", "Infineon Technologies", "Infineon"),
+    ("renesas", "Renesas Electronics", "Renesas"),
+    ("silabs", "Silicon Laboratories", "SiliconLabs"),
+    ("ti", "Texas Instruments", "TI"),
+    ("raspberrypi", "Raspberry Pi", "RaspberryPi"),
+];
 
 pub type PerMatchIterMut<'a, 'b> = MatchIter<'b, std::slice::IterMut<'a, Peripheral>>;
 
@@ -28,6 +48,18 @@ pub trait DeviceExt {
         "_add",
         "_derive",
         "_rebase",
+        "_base_addresses_from",
+        "_delete_derived",
+        "_resolve_derives",
+        "_require",
+        "_normalize_access",
+        "_fix_enum_usage",
+        "_canonicalize",
+        "_strip_reserved",
+        "_strip_redundant_display_names",
+        "_set_version",
+        "_vendor",
+        "_rename_interrupts",
     ];
 
     /// Iterates over all peripherals that match pspec
@@ -37,13 +69,26 @@ pub trait DeviceExt {
     fn present_peripherals(&self) -> String;
 
     /// Work through a device, handling all peripherals
-    fn process(&mut self, device: &Hash, config: &Config) -> PatchResult;
+    /// and return names of peripherals touched by any command
+    fn process(&mut self, device: &Hash, config: &Config) -> Result<HashSet<String>>;
+
+    /// Returns names of present peripherals which are not in `touched`
+    fn untouched_peripherals(&self, touched: &HashSet<String>) -> Vec<String>;
+
+    /// Add names of peripherals matched by pspec to `touched`
+    fn touch_peripherals(&self, pspec: &str, touched: &mut HashSet<String>);
 
-    /// Delete registers matched by rspec inside ptag
-    fn delete_peripheral(&mut self, pspec: &str) -> PatchResult;
+    /// Delete peripherals matched by pspec
+    fn delete_peripheral(&mut self, pspec: &str, config: &Config) -> PatchResult;
 
     /// Create copy of peripheral
-    fn copy_peripheral(&mut self, pname: &str, pmod: &Hash, path: &Path) -> PatchResult;
+    fn copy_peripheral(
+        &mut self,
+        pname: &str,
+        pmod: &Hash,
+        path: &Path,
+        base_dir: Option<&Path>,
+    ) -> PatchResult;
 
     /// Modify the `cpu` node inside `device` according to `mod`
     fn modify_cpu(&mut self, cmod: &Hash) -> PatchResult;
@@ -62,9 +107,57 @@ pub trait DeviceExt {
     /// Update all derivedFrom referencing pold
     fn rebase_peripheral(&mut self, pnew: &str, pold: &str) -> PatchResult;
 
+    /// Set base addresses of peripherals from CSV file with `name,address` lines
+    fn base_addresses_from(&mut self, path: &Path, touched: &mut HashSet<String>) -> PatchResult;
+
+    /// Delete all peripherals derived from other ones.
+    /// Peripherals which are bases of remaining ones are deleted on next iterations
+    fn delete_derived(&mut self) -> PatchResult;
+
+    /// Set vendor and vendorID from table of known vendors.
+    /// Unknown vendor name is set as is and vendorID is cleared
+    fn set_vendor(&mut self, vendor: &str);
+
+    /// Set version of peripherals matched by pspec
+    fn set_version(&mut self, version: &str, pspec: &str) -> PatchResult;
+
+    /// Copy registers from base peripheral into derived peripherals matched by pspec
+    /// and remove their derivedFrom
+    fn resolve_derives(&mut self, pspec: &str) -> PatchResult;
+
+    /// Remove access equal to inherited one from registers and fields
+    /// or set it explicitly everywhere if `materialize`
+    fn normalize_access(&mut self, materialize: bool) -> PatchResult;
+
+    /// Set usage of untagged enumeratedValues according to field access
+    fn fix_enum_usage(&mut self) -> PatchResult;
+
+    /// Sort peripherals by address, registers by offset, fields by offset
+    /// and enumeratedValues by value. Collapse whitespaces in descriptions
+    fn canonicalize(&mut self);
+
+    /// Check that register given by `PERIPHERAL.REGISTER` path satisfies requirements
+    fn require_register(&self, rpath: &str, req: &Hash) -> PatchResult;
+
+    /// Remove fields matched by any of `patterns` which have no enumeratedValues
+    /// and writeConstraint. Returns names of changed peripherals
+    fn strip_reserved(&mut self, patterns: &[&str]) -> Vec<String>;
+
+    /// Remove displayName of registers which is same as name.
+    /// Returns names of changed peripherals
+    fn strip_redundant_display_names(&mut self) -> Vec<String>;
+
+    /// Rename interrupts of all peripherals which fully match regex pattern.
+    /// Returns names of changed peripherals
+    fn rename_interrupts(&mut self, pattern: &str, replacement: &str) -> Result<Vec<String>>;
+
     /// Clear contents of all fields inside peripherals matched by pspec
     fn clear_fields(&mut self, fspec: &str) -> PatchResult;
 
+    /// Check that enumeratedValues derived by dotted path in `derives` can be found
+    /// in device and have compatible usage
+    fn check_enum_derives(&self, derives: &[(FieldPath, String)]) -> PatchResult;
+
     /// Work through a peripheral, handling all registers
     fn process_peripheral(
         &mut self,
@@ -83,24 +176,53 @@ impl DeviceExt for Device {
         self.peripherals.iter().map(|p| p.name.as_str()).join(", ")
     }
 
-    fn process(&mut self, device: &Hash, config: &Config) -> PatchResult {
+    fn untouched_peripherals(&self, touched: &HashSet<String>) -> Vec<String> {
+        self.peripherals
+            .iter()
+            .filter(|p| !touched.contains(&p.name))
+            .map(|p| p.name.clone())
+            .collect()
+    }
+
+    fn touch_peripherals(&self, pspec: &str, touched: &mut HashSet<String>) {
+        let (pspec, _) = pspec.spec();
+        for p in &self.peripherals {
+            if matchname(&p.name, pspec) {
+                touched.insert(p.name.clone());
+            }
+        }
+    }
+
+    fn process(&mut self, device: &Hash, config: &Config) -> Result<HashSet<String>> {
+        let _case = MatchCaseGuard::new(config.case_insensitive_match);
+        let mut touched = HashSet::new();
+        let mut config = config.clone();
+        config.enum_derives = Default::default();
+        let config = &config;
+
         // Handle any deletions
         for pspec in device.str_vec_iter("_delete")? {
-            self.delete_peripheral(pspec)
+            self.delete_peripheral(pspec, config)
                 .with_context(|| format!("Deleting peripheral matched to `{pspec}`"))?;
         }
 
         // Handle any copied peripherals
         for (pname, val) in device.hash_iter("_copy") {
             let pname = pname.str()?;
+            touched.insert(pname.to_string());
             self.copy_peripheral(
                 pname,
                 val.hash()?,
                 Path::new(device.get_str("_path")?.unwrap_or(".")),
+                config.base_dir.as_deref(),
             )
             .with_context(|| format!("Copying peripheral `{pname}`"))?;
         }
 
+        if let Some(vendor) = device.get_str("_vendor")? {
+            self.set_vendor(vendor);
+        }
+
         // Handle any modifications
         for (key, val) in device.hash_iter("_modify") {
             let key = key.str()?;
@@ -111,6 +233,10 @@ impl DeviceExt for Device {
                 "_peripherals" => {
                     for (pspec, pmod) in val.hash()? {
                         let pspec = pspec.str()?;
+                        self.touch_peripherals(pspec, &mut touched);
+                        if let Some(name) = pmod.hash()?.get_str("name")? {
+                            touched.insert(name.to_string());
+                        }
                         self.modify_peripheral(pspec, pmod.hash()?)
                             .with_context(|| {
                                 format!("Modifying peripherals matched to `{pspec}`")
@@ -134,14 +260,20 @@ impl DeviceExt for Device {
                     modify_register_properties(&mut self.default_register_properties, key, val)?;
                 }
 
-                _ => self
-                    .modify_peripheral(key, val.hash()?)
-                    .with_context(|| format!("Modifying peripherals matched to `{key}`"))?,
+                _ => {
+                    self.touch_peripherals(key, &mut touched);
+                    if let Some(name) = val.hash()?.get_str("name")? {
+                        touched.insert(name.to_string());
+                    }
+                    self.modify_peripheral(key, val.hash()?)
+                        .with_context(|| format!("Modifying peripherals matched to `{key}`"))?
+                }
             }
         }
 
         // Handle field clearing
         for pspec in device.str_vec_iter("_clear_fields")? {
+            self.touch_peripherals(pspec, &mut touched);
             self.clear_fields(pspec).with_context(|| {
                 format!("Clearing contents of fields in peripherals matched to `{pspec}` ")
             })?;
@@ -150,6 +282,7 @@ impl DeviceExt for Device {
         // Handle any new peripherals (!)
         for (pname, padd) in device.hash_iter("_add") {
             let pname = pname.str()?;
+            touched.insert(pname.to_string());
             self.add_peripheral(pname, padd.hash()?)
                 .with_context(|| format!("Adding peripheral `{pname}`"))?;
         }
@@ -157,6 +290,7 @@ impl DeviceExt for Device {
         // Handle any derived peripherals
         for (pname, pderive) in device.hash_iter("_derive") {
             let pname = pname.str()?;
+            touched.insert(pname.to_string());
             self.derive_peripheral(pname, pderive)
                 .with_context(|| format!("Deriving peripheral `{pname}` from `{pderive:?}`"))?;
         }
@@ -165,29 +299,154 @@ impl DeviceExt for Device {
         for (pname, pold) in device.hash_iter("_rebase") {
             let pname = pname.str()?;
             let pold = pold.str()?;
+            touched.insert(pname.to_string());
+            touched.insert(pold.to_string());
             self.rebase_peripheral(pname, pold)
                 .with_context(|| format!("Rebasing peripheral from `{pold}` to `{pname}`"))?;
         }
 
+        // Handle base addresses from address map
+        if let Some(map) = device.get_str("_base_addresses_from")? {
+            let path = abspath(
+                Path::new(device.get_str("_path")?.unwrap_or(".")),
+                Path::new(map),
+                config.base_dir.as_deref(),
+            )
+            .with_context(|| format!("Opening address map `{map}`"))?;
+            self.base_addresses_from(&path, &mut touched)
+                .with_context(|| format!("Setting base addresses from `{map}`"))?;
+        }
+
+        // Handle deletion of derived peripherals
+        if device.get_bool("_delete_derived")? == Some(true) {
+            self.delete_derived()?;
+        }
+
+        // Handle peripheral versions
+        for (version, pspecs) in device.hash_iter("_set_version") {
+            let version = match version {
+                Yaml::Real(v) | Yaml::String(v) => v.as_str(),
+                _ => version.str()?,
+            };
+            let pspecs = match pspecs {
+                Yaml::String(pspec) => vec![pspec.as_str()],
+                Yaml::Array(a) => a.iter().map(|p| p.str()).collect::<Result<_, _>>()?,
+                _ => {
+                    return Err(anyhow!(
+                        "`_set_version` requires string value or array of strings"
+                    ))
+                }
+            };
+            for pspec in pspecs {
+                self.touch_peripherals(pspec, &mut touched);
+                self.set_version(version, pspec).with_context(|| {
+                    format!("Setting version `{version}` of peripherals matched to `{pspec}`")
+                })?;
+            }
+        }
+
+        // Handle any peripherals which should not be derived anymore
+        for pspec in device.str_vec_iter("_resolve_derives")? {
+            self.touch_peripherals(pspec, &mut touched);
+            self.resolve_derives(pspec).with_context(|| {
+                format!("Resolving derives of peripherals matched to `{pspec}`")
+            })?;
+        }
+
         // Now process all peripherals
         for (periphspec, val) in device {
             let periphspec = periphspec.str()?;
             if !Self::KEYWORDS.contains(&periphspec) {
                 //val["_path"] = device["_path"]; // TODO: check
+                self.touch_peripherals(periphspec, &mut touched);
                 self.process_peripheral(periphspec, val.hash()?, config)
                     .with_context(|| format!("According to `{periphspec}`"))?;
             }
         }
 
-        Ok(())
+        // Resolve enumeratedValues derived from other registers and peripherals
+        self.check_enum_derives(&config.enum_derives.take())?;
+
+        // Handle interrupt renames
+        for (pattern, replacement) in device.hash_iter("_rename_interrupts") {
+            let pattern = pattern.str()?;
+            let replacement = replacement.str()?;
+            touched.extend(
+                self.rename_interrupts(pattern, replacement)
+                    .with_context(|| {
+                        format!("Renaming interrupts matched to `{pattern}` with `{replacement}`")
+                    })?,
+            );
+        }
+
+        // Remove reserved fields
+        match device.get_yaml("_strip_reserved") {
+            Some(Yaml::Boolean(false)) | None => {}
+            Some(Yaml::Boolean(true)) => {
+                touched.extend(self.strip_reserved(&["RES*", "Reserved*"]))
+            }
+            Some(_) => {
+                let patterns = device.str_vec_iter("_strip_reserved")?.collect::<Vec<_>>();
+                touched.extend(self.strip_reserved(&patterns));
+            }
+        }
+
+        // Remove display names which repeat names
+        if device.get_bool("_strip_redundant_display_names")? == Some(true) {
+            touched.extend(self.strip_redundant_display_names());
+        }
+
+        // Handle access normalization
+        if let Some(mode) = device.get_str("_normalize_access")? {
+            let materialize = match mode {
+                "remove" => false,
+                "materialize" => true,
+                _ => {
+                    return Err(anyhow!(
+                        "`_normalize_access` must be `remove` or `materialize`, not `{mode}`"
+                    ))
+                }
+            };
+            self.normalize_access(materialize)?;
+        }
+
+        // Handle usage of untagged enums
+        if device.get_bool("_fix_enum_usage")? == Some(true) {
+            self.fix_enum_usage()?;
+        }
+
+        // Make output independent of order of commands and source SVD
+        if device.get_bool("_canonicalize")? == Some(true) {
+            self.canonicalize();
+        }
+
+        // Check requirements after all changes are applied
+        for (rpath, req) in device.hash_iter("_require") {
+            let rpath = rpath.str()?;
+            self.require_register(rpath, req.hash()?)
+                .with_context(|| format!("Checking requirements of `{rpath}`"))?;
+        }
+
+        Ok(touched)
     }
 
-    fn delete_peripheral(&mut self, pspec: &str) -> PatchResult {
+    fn delete_peripheral(&mut self, pspec: &str, config: &Config) -> PatchResult {
+        let (pspec, ignore) = pspec.spec();
+        let len = self.peripherals.len();
         self.peripherals.retain(|p| !(matchname(&p.name, pspec)));
+        if self.peripherals.len() == len && !ignore {
+            return delete_absent("peripheral", pspec, config);
+        }
         Ok(())
     }
 
-    fn copy_peripheral(&mut self, pname: &str, pmod: &Hash, path: &Path) -> PatchResult {
+    fn copy_peripheral(
+        &mut self,
+        pname: &str,
+        pmod: &Hash,
+        path: &Path,
+        base_dir: Option<&Path>,
+    ) -> PatchResult {
         let pcopysrc = pmod
             .get_str("from")?
             .unwrap()
@@ -195,7 +454,7 @@ impl DeviceExt for Device {
             .collect::<Vec<_>>();
         let mut new = match pcopysrc.as_slice() {
             [ppath, pcopyname] => {
-                let f = File::open(abspath(path, Path::new(ppath))?)?;
+                let f = File::open(abspath(path, Path::new(ppath), base_dir)?)?;
                 let mut contents = String::new();
                 (&f).read_to_string(&mut contents).unwrap();
                 let filedev = svd_parser::parse(&contents)
@@ -241,6 +500,9 @@ impl DeviceExt for Device {
         } else {
             self.cpu = Some(cpu.build(VAL_LVL)?);
         }
+        if let Some(c) = self.cpu.as_mut() {
+            clear_cpu(c, cmod);
+        }
         Ok(())
     }
 
@@ -258,7 +520,7 @@ impl DeviceExt for Device {
                 if let Some(ints) = pmod.get_hash("interrupts")? {
                     for (iname, val) in ints {
                         let iname = iname.str()?;
-                        let int = make_interrupt(val.hash()?)?;
+                        let int = make_interrupt(val.hash()?, iname)?;
                         for i in &mut ptag.interrupt {
                             if i.name == iname {
                                 i.modify_from(int, VAL_LVL)?;
@@ -429,6 +691,275 @@ impl DeviceExt for Device {
         Ok(())
     }
 
+    fn base_addresses_from(&mut self, path: &Path, touched: &mut HashSet<String>) -> PatchResult {
+        let contents = std::fs::read_to_string(path)?;
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((pspec, address)) = line.split_once(',') else {
+                return Err(anyhow!("Line {}: expected `name,address`", i + 1));
+            };
+            let (pspec, address) = (pspec.trim(), address.trim());
+            let Some(address) = parse_i64(&Yaml::String(address.into())) else {
+                // Skip header
+                if i == 0 {
+                    continue;
+                }
+                return Err(anyhow!("Line {}: incorrect address `{address}`", i + 1));
+            };
+            let mut found = false;
+            for ptag in self.iter_peripherals(pspec) {
+                ptag.base_address = address as u64;
+                touched.insert(ptag.name.clone());
+                found = true;
+            }
+            if !found {
+                let present = self.present_peripherals();
+                return Err(anyhow!(
+                    "Could not find `{pspec}. Present peripherals: {present}.`"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn delete_derived(&mut self) -> PatchResult {
+        loop {
+            let bases = self
+                .peripherals
+                .iter()
+                .filter_map(|p| p.derived_from.clone())
+                .collect::<HashSet<_>>();
+            let len = self.peripherals.len();
+            // Remove only leaves so no `derivedFrom` points to deleted peripheral
+            self.peripherals
+                .retain(|p| p.derived_from.is_none() || bases.contains(&p.name));
+            if self.peripherals.len() == len {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_vendor(&mut self, vendor: &str) {
+        if let Some((_, name, id)) = VENDORS
+            .iter()
+            .find(|(key, _, _)| key.eq_ignore_ascii_case(vendor))
+        {
+            self.vendor = Some(name.to_string());
+            self.vendor_id = Some(id.to_string());
+        } else {
+            self.vendor = Some(vendor.into());
+            self.vendor_id = None;
+        }
+    }
+
+    fn set_version(&mut self, version: &str, pspec: &str) -> PatchResult {
+        let (pspec, ignore) = pspec.spec();
+        let ptags = self.iter_peripherals(pspec).collect::<Vec<_>>();
+        if ptags.is_empty() && !ignore {
+            let present = self.present_peripherals();
+            return Err(anyhow!(
+                "Could not find `{pspec}. Present peripherals: {present}.`"
+            ));
+        }
+        for ptag in ptags {
+            ptag.version = Some(version.into());
+        }
+        Ok(())
+    }
+
+    fn resolve_derives(&mut self, pspec: &str) -> PatchResult {
+        let (pspec, ignore) = pspec.spec();
+        let index = Index::create(self);
+        let mut resolved = Vec::new();
+        for (i, ptag) in self.peripherals.iter().enumerate() {
+            if !matchname(&ptag.name, pspec) {
+                continue;
+            }
+            if let Some(dpath) = ptag.derived_from.as_deref() {
+                let mut p = ptag.clone();
+                derive_peripheral(&mut p, dpath, &index)
+                    .with_context(|| format!("Resolving peripheral `{}`", ptag.name))?;
+                p.derived_from = None;
+                resolved.push((i, p));
+            }
+        }
+        if resolved.is_empty() && !ignore {
+            let present = self.present_peripherals();
+            return Err(anyhow!(
+                "Could not find derived `{pspec}. Present peripherals: {present}.`"
+            ));
+        }
+        for (i, p) in resolved {
+            self.peripherals[i] = p;
+        }
+        Ok(())
+    }
+
+    fn normalize_access(&mut self, materialize: bool) -> PatchResult {
+        let default = self.default_register_properties.access;
+        for ptag in &mut self.peripherals {
+            let default = ptag.default_register_properties.access.or(default);
+            if let Some(regs) = ptag.registers.as_mut() {
+                normalize_access(regs, default, materialize);
+            }
+        }
+        Ok(())
+    }
+
+    fn fix_enum_usage(&mut self) -> PatchResult {
+        let default = self.default_register_properties.access;
+        for ptag in &mut self.peripherals {
+            let default = ptag.default_register_properties.access.or(default);
+            for rtag in ptag.all_regs_mut() {
+                let raccess = rtag.properties.access.or(default);
+                for ftag in rtag.fields_mut() {
+                    let Some(access) = ftag.access.or(raccess) else {
+                        continue;
+                    };
+                    for evs in &mut ftag.enumerated_values {
+                        if evs.derived_from.is_none() && evs.usage.is_none() {
+                            let usage = check_usage(access, None)?;
+                            if usage != Usage::ReadWrite {
+                                evs.usage = Some(usage);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn canonicalize(&mut self) {
+        fn normalize(description: &mut Option<String>) {
+            if let Some(d) = description.as_mut() {
+                *d = d.split_whitespace().join(" ");
+            }
+        }
+        fn walk(children: &mut [RegisterCluster]) {
+            children.sort_by(|a, b| {
+                let key = |rc: &RegisterCluster| match rc {
+                    RegisterCluster::Register(r) => (r.address_offset, r.name.clone()),
+                    RegisterCluster::Cluster(c) => (c.address_offset, c.name.clone()),
+                };
+                key(a).cmp(&key(b))
+            });
+            for rc in children {
+                match rc {
+                    RegisterCluster::Register(r) => {
+                        normalize(&mut r.description);
+                        let Some(fields) = r.fields.as_mut() else {
+                            continue;
+                        };
+                        fields.sort_by(|a, b| {
+                            (a.bit_range.offset, &a.name).cmp(&(b.bit_range.offset, &b.name))
+                        });
+                        for f in fields {
+                            normalize(&mut f.description);
+                            for evs in &mut f.enumerated_values {
+                                // Default has no value and goes last
+                                evs.values.sort_by_key(|ev| (ev.value.is_none(), ev.value));
+                                for ev in &mut evs.values {
+                                    normalize(&mut ev.description);
+                                }
+                            }
+                        }
+                    }
+                    RegisterCluster::Cluster(c) => {
+                        normalize(&mut c.description);
+                        walk(&mut c.children);
+                    }
+                }
+            }
+        }
+
+        self.description = self.description.split_whitespace().join(" ");
+        self.peripherals
+            .sort_by(|a, b| (a.base_address, &a.name).cmp(&(b.base_address, &b.name)));
+        for p in &mut self.peripherals {
+            normalize(&mut p.description);
+            if let Some(regs) = p.registers.as_mut() {
+                walk(regs);
+            }
+        }
+    }
+
+    fn require_register(&self, rpath: &str, req: &Hash) -> PatchResult {
+        let mut names = rpath.split('.');
+        let pname = names.next().unwrap();
+        let rname = names
+            .next_back()
+            .ok_or_else(|| anyhow!("Path `{rpath}` must be in `PERIPHERAL.REGISTER` form"))?;
+        let ptag = self.get_peripheral(pname).ok_or_else(|| {
+            let present = self.present_peripherals();
+            anyhow!("peripheral {pname} not found. Present peripherals: {present}.")
+        })?;
+        let mut ctag = None;
+        for cname in names {
+            ctag = match ctag {
+                None => ptag.get_cluster(cname),
+                Some(c) => c.get_cluster(cname),
+            };
+            if ctag.is_none() {
+                return Err(anyhow!("cluster {cname} not found"));
+            }
+        }
+        let rtag = match ctag {
+            None => ptag.get_register(rname),
+            Some(c) => c.get_register(rname),
+        }
+        .ok_or_else(|| anyhow!("register {rname} not found"))?;
+        if req.get_yaml("fields").is_some() {
+            let fnames = req.str_vec_iter("fields")?.collect::<Vec<_>>();
+            rtag.require_fields(&fnames)?;
+        }
+        Ok(())
+    }
+
+    fn strip_reserved(&mut self, patterns: &[&str]) -> Vec<String> {
+        let mut changed = Vec::new();
+        for ptag in &mut self.peripherals {
+            let mut removed = false;
+            for rtag in ptag.all_regs_mut() {
+                let Some(fields) = rtag.fields.as_mut() else {
+                    continue;
+                };
+                let len = fields.len();
+                fields.retain(|f| {
+                    !(f.enumerated_values.is_empty()
+                        && f.write_constraint.is_none()
+                        && patterns.iter().any(|p| matchname(&f.name, p)))
+                });
+                removed |= fields.len() != len;
+            }
+            if removed {
+                changed.push(ptag.name.clone());
+            }
+        }
+        changed
+    }
+
+    fn strip_redundant_display_names(&mut self) -> Vec<String> {
+        let mut changed = Vec::new();
+        for ptag in &mut self.peripherals {
+            let mut removed = false;
+            for rtag in ptag.all_regs_mut() {
+                if rtag.display_name.as_ref() == Some(&rtag.name) {
+                    rtag.display_name = None;
+                    removed = true;
+                }
+            }
+            if removed {
+                changed.push(ptag.name.clone());
+            }
+        }
+        changed
+    }
+
     fn clear_fields(&mut self, pspec: &str) -> PatchResult {
         for ptag in self.iter_peripherals(pspec) {
             if ptag.derived_from.is_some() {
@@ -439,12 +970,72 @@ impl DeviceExt for Device {
         Ok(())
     }
 
+    fn check_enum_derives(&self, derives: &[(FieldPath, String)]) -> PatchResult {
+        if derives.is_empty() {
+            return Ok(());
+        }
+        let index = Index::create(self);
+        for (fpath, d) in derives {
+            // Field could be deleted or its enumeratedValues replaced later in patch
+            let Some(ftag) = index.fields.get(fpath) else {
+                continue;
+            };
+            for evs in &ftag.enumerated_values {
+                if evs.derived_from.as_ref() != Some(d) {
+                    continue;
+                }
+                let epath = derive_enumerated_values(&mut evs.clone(), d, fpath, &index)
+                    .with_context(|| {
+                        format!("{fpath}: enumeratedValues {d} can't be found in device (absolute lookup)")
+                    })?;
+                let orig_usage = index.evs[&epath].usage().unwrap_or_default();
+                let access = ftag
+                    .access
+                    .or_else(|| {
+                        index
+                            .registers
+                            .get(&fpath.register)
+                            .and_then(|r| r.properties.access)
+                    })
+                    .unwrap_or_default();
+                let usage =
+                    check_usage(access, evs.usage).with_context(|| format!("In field {fpath}"))?;
+                if usage != orig_usage {
+                    return Err(anyhow!(
+                        "{fpath}: enumeratedValues with different usage was found: {usage:?} != {orig_usage:?}"
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn rename_interrupts(&mut self, pattern: &str, replacement: &str) -> Result<Vec<String>> {
+        let re = regex::Regex::new(&format!("^(?:{pattern})$"))?;
+        let mut changed = Vec::new();
+        for ptag in &mut self.peripherals {
+            let mut renamed = false;
+            for i in &mut ptag.interrupt {
+                if re.is_match(&i.name) {
+                    i.name = re.replace(&i.name, replacement).into_owned();
+                    renamed = true;
+                }
+            }
+            if renamed {
+                changed.push(ptag.name.clone());
+            }
+        }
+        Ok(changed)
+    }
+
     fn process_peripheral(
         &mut self,
         pspec: &str,
         peripheral: &Hash,
         config: &Config,
     ) -> PatchResult {
+        let mut config = config.clone();
+        config.register_size = self.default_register_properties.size;
         let (pspec, ignore) = pspec.spec();
         let ptags = self.iter_peripherals(pspec).collect::<Vec<_>>();
         if ptags.is_empty() && !ignore {
@@ -454,13 +1045,39 @@ impl DeviceExt for Device {
             ));
         }
         for ptag in ptags {
-            ptag.process(peripheral, config)
+            ptag.process(peripheral, &config)
                 .with_context(|| format!("Processing peripheral `{}`", ptag.name))?;
         }
         Ok(())
     }
 }
 
+fn normalize_access(children: &mut [RegisterCluster], default: Option<Access>, materialize: bool) {
+    for rc in children {
+        match rc {
+            RegisterCluster::Cluster(ctag) => {
+                let default = ctag.default_register_properties.access.or(default);
+                normalize_access(&mut ctag.children, default, materialize);
+            }
+            RegisterCluster::Register(rtag) => {
+                let raccess = rtag.properties.access.or(default);
+                if materialize {
+                    rtag.properties.access = raccess;
+                } else if rtag.properties.access == default {
+                    rtag.properties.access = None;
+                }
+                for ftag in rtag.fields_mut() {
+                    if materialize {
+                        ftag.access = ftag.access.or(raccess);
+                    } else if ftag.access == raccess {
+                        ftag.access = None;
+                    }
+                }
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -505,6 +1122,111 @@ mod tests {
         assert_eq!(dac1.registers, dac2.registers);
     }
 
+    #[test]
+    fn resolve_derives() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("resolve_derives")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let dac2 = device.get_peripheral("DAC2").unwrap();
+        assert_eq!(dac2.derived_from, None);
+        assert!(dac2.registers.is_some());
+        assert_eq!(dac1.registers, dac2.registers);
+        assert_eq!(dac2.base_address, 0x40007402);
+    }
+
+    #[test]
+    fn require_fields() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("require")).unwrap();
+        let err = device.process(&yaml, &Default::default()).unwrap_err();
+        assert_eq!(
+            format!("{:#}", err.root_cause()),
+            "Register `CR` fields mismatch. Missing: [EN2]. Unexpected: []."
+        );
+    }
+
+    #[test]
+    fn normalize_access_remove() {
+        let (mut device, yaml) =
+            test_utils::get_patcher(Path::new("normalize_access_remove")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let cr = dac1.get_register("CR").unwrap();
+        assert_eq!(cr.properties.access, None);
+        assert_eq!(cr.get_field("EN1").unwrap().access, None);
+        assert_eq!(cr.get_field("EN2").unwrap().access, None);
+        let sr = dac1.get_register("SR").unwrap();
+        assert_eq!(sr.properties.access, Some(Access::ReadOnly));
+        assert_eq!(sr.get_field("BWST1").unwrap().access, None);
+    }
+
+    #[test]
+    fn modify_default_properties() {
+        use svd_parser::svd::Protection;
+        use yaml_rust::YamlLoader;
+
+        let (mut device, _) = test_utils::get_patcher(Path::new("add")).unwrap();
+        let mut modify = |patch: &str| {
+            let patch = YamlLoader::load_from_str(patch).unwrap();
+            device
+                .process(patch[0].as_hash().unwrap(), &Default::default())
+                .unwrap();
+            device.default_register_properties
+        };
+        let props = modify("_modify: {protection: s, resetValue: 0x10, size: 32}");
+        assert_eq!(props.protection, Some(Protection::Secure));
+        assert_eq!(props.reset_value, Some(0x10));
+        assert_eq!(props.size, Some(32));
+        let props = modify("_modify: {protection: '', resetValue: ''}");
+        assert_eq!(props.protection, None);
+        assert_eq!(props.reset_value, None);
+        assert_eq!(props.size, Some(32));
+    }
+
+    #[test]
+    fn normalize_access_materialize() {
+        let (mut device, yaml) =
+            test_utils::get_patcher(Path::new("normalize_access_materialize")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let cr = dac1.get_register("CR").unwrap();
+        assert_eq!(cr.properties.access, Some(Access::ReadWrite));
+        assert_eq!(cr.get_field("EN2").unwrap().access, Some(Access::ReadWrite));
+        let sr = dac1.get_register("SR").unwrap();
+        assert_eq!(
+            sr.get_field("BWST1").unwrap().access,
+            Some(Access::ReadOnly)
+        );
+    }
+
+    #[test]
+    fn fix_enum_usage() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("fix_enum_usage")).unwrap();
+        let en1 = |device: &Device| {
+            device
+                .get_peripheral("DAC1")
+                .unwrap()
+                .get_register("CR")
+                .unwrap()
+                .get_field("EN1")
+                .unwrap()
+                .enumerated_values[0]
+                .usage
+        };
+        assert_eq!(en1(&device), None);
+        device.process(&yaml, &Default::default()).unwrap();
+        assert_eq!(en1(&device), Some(Usage::Write));
+    }
+
+    #[test]
+    fn set_version() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("set_version")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+        for (pname, version) in [("DAC1", Some("1.2")), ("DAC2", Some("1.2")), ("DAC3", None)] {
+            let p = device.get_peripheral(pname).unwrap();
+            assert_eq!(p.version.as_deref(), version);
+        }
+    }
+
     #[test]
     fn modify_device() {
         let (mut device, yaml) = test_utils::get_patcher(Path::new("modify")).unwrap();
@@ -541,4 +1263,171 @@ mod tests {
             Some("Digital-to-analog converter".to_string())
         );
     }
+
+    #[test]
+    fn delete_derived() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("delete_derived")).unwrap();
+        assert_eq!(device.peripherals.len(), 3);
+        device.process(&yaml, &Default::default()).unwrap();
+        assert_eq!(device.peripherals.len(), 1);
+        assert_eq!(device.peripherals[0].name, "DAC1");
+    }
+
+    #[test]
+    fn modify_cpu() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("modify_cpu")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+        let cpu = device.cpu.as_ref().unwrap();
+        assert_eq!(cpu.revision, "");
+        assert_eq!(cpu.fpu_double_precision, Some(true));
+        assert_eq!(cpu.device_num_interrupts, Some(82));
+        let cmod = yaml_rust::YamlLoader::load_from_str("deviceNumInterrupts: null").unwrap();
+        device.modify_cpu(cmod[0].hash().unwrap()).unwrap();
+        assert_eq!(device.cpu.as_ref().unwrap().device_num_interrupts, None);
+        let svd = svd_encoder::encode(&device).unwrap();
+        assert!(svd.contains("<fpuDP>true</fpuDP>"));
+        assert!(!svd.contains("<deviceNumInterrupts>"));
+    }
+
+    #[test]
+    fn untouched_peripherals() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("modify")).unwrap();
+        let touched = device.process(&yaml, &Default::default()).unwrap();
+        assert!(touched.contains("DAC11"));
+        assert_eq!(device.untouched_peripherals(&touched), ["DAC3"]);
+    }
+
+    #[test]
+    fn base_addresses_from() {
+        let (mut device, mut yaml) = test_utils::get_patcher(Path::new("base_addresses")).unwrap();
+        let path = test_utils::res_dir().join("base_addresses/patch.yaml");
+        yaml.insert(
+            Yaml::String("_path".into()),
+            Yaml::String(path.to_str().unwrap().into()),
+        );
+        device.process(&yaml, &Default::default()).unwrap();
+        let bases = device
+            .peripherals
+            .iter()
+            .map(|p| (p.name.as_str(), p.base_address))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            bases,
+            [
+                ("DAC1", 0x5000_0000),
+                ("DAC2", 0x40007402),
+                ("DAC3", 0x5000_1000)
+            ]
+        );
+    }
+
+    #[test]
+    fn set_vendor() {
+        let (mut device, mut yaml) = test_utils::get_patcher(Path::new("add")).unwrap();
+        yaml.insert(
+            Yaml::String("_vendor".into()),
+            Yaml::String("stmicro".into()),
+        );
+        device.process(&yaml, &Default::default()).unwrap();
+        assert_eq!(device.vendor.as_deref(), Some("STMicroelectronics"));
+        assert_eq!(device.vendor_id.as_deref(), Some("ST"));
+
+        device.set_vendor("Acme");
+        assert_eq!(device.vendor.as_deref(), Some("Acme"));
+        assert_eq!(device.vendor_id, None);
+    }
+
+    #[test]
+    fn canonicalize() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("canonicalize")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+
+        let (mut reordered, _) = test_utils::get_patcher(Path::new("canonicalize")).unwrap();
+        let patch = yaml_rust::YamlLoader::load_from_str(
+            r"
+_add:
+  DAC2:
+    description: Second   DAC
+    baseAddress: 0x40007800
+  ADC:
+    baseAddress: 0x40007000
+    registers:
+      ISR:
+        addressOffset: 0x4
+      CR:
+        description: |
+          Control
+          register
+        addressOffset: 0x0
+        fields:
+          MODE:
+            bitOffset: 1
+            bitWidth: 2
+          EN:
+            bitOffset: 0
+            bitWidth: 1
+_canonicalize: true
+ADC:
+  CR:
+    MODE:
+      THIRD: [3, Third]
+      _default: [-1, Others]
+      FIRST: [1, First]
+",
+        )
+        .unwrap();
+        reordered
+            .process(patch[0].as_hash().unwrap(), &Default::default())
+            .unwrap();
+        assert_eq!(
+            svd_encoder::encode(&device).unwrap(),
+            svd_encoder::encode(&reordered).unwrap()
+        );
+        let names = device
+            .peripherals
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["ADC", "DAC1", "DAC2"]);
+    }
+
+    #[test]
+    fn rename_interrupts() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("rename_interrupts")).unwrap();
+        let touched = device.process(&yaml, &Default::default()).unwrap();
+        assert!(touched.contains("DAC3"));
+        let interrupts = device
+            .peripherals
+            .iter()
+            .flat_map(|p| p.interrupt.iter().map(|i| (i.name.as_str(), i.value)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            interrupts,
+            [("DAC1", 54), ("TIM6_DAC", 55), ("DAC1", 54), ("DAC2", 56)]
+        );
+    }
+
+    #[test]
+    fn strip_redundant_display_names() {
+        let (mut device, yaml) =
+            test_utils::get_patcher(Path::new("strip_redundant_display_names")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let display_name = |r| dac1.get_register(r).unwrap().display_name.as_deref();
+        assert_eq!(display_name("CR"), None);
+        assert_eq!(display_name("SR"), Some("Status"));
+    }
+
+    #[test]
+    fn strip_reserved() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("strip_reserved")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+        let cr = device
+            .get_peripheral("DAC1")
+            .unwrap()
+            .get_register("CR")
+            .unwrap();
+        let fields = cr.fields().map(|f| f.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(fields, ["EN1", "RES3"]);
+    }
 }
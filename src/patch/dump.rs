@@ -0,0 +1,227 @@
+use anyhow::{anyhow, Result};
+use svd_parser::svd::{
+    DimElement, Field, FieldInfo, Peripheral, Register, RegisterCluster, RegisterProperties, Usage,
+};
+use yaml_rust::{yaml::Hash, Yaml, YamlEmitter};
+
+use super::yaml_ext::ToYaml;
+
+/// Generate patch fragment which adds peripheral `pname` of `device`
+/// and sets enumeratedValues of its fields
+pub fn dump_peripheral(device: &svd_parser::svd::Device, pname: &str) -> Result<String> {
+    let p = device
+        .get_peripheral(pname)
+        .ok_or_else(|| anyhow!("peripheral {pname} not found"))?;
+    let mut padd = Hash::new();
+    insert_opt(&mut padd, "description", p.description.as_deref());
+    insert_opt(&mut padd, "groupName", p.group_name.as_deref());
+    insert_opt(&mut padd, "version", p.version.as_deref());
+    insert_opt(&mut padd, "displayName", p.display_name.as_deref());
+    padd.insert("baseAddress".to_yaml(), int(p.base_address));
+    if let Peripheral::Array(_, d) = p {
+        insert_dim(&mut padd, d);
+    }
+    if let Some(derived) = p.derived_from.as_deref() {
+        padd.insert("derivedFrom".to_yaml(), derived.to_yaml());
+    }
+    insert_properties(&mut padd, &p.default_register_properties);
+    if let Some(ab) = p.address_block.as_ref() {
+        let blocks = ab
+            .iter()
+            .map(|ab| {
+                let mut h = Hash::new();
+                h.insert("offset".to_yaml(), int(ab.offset));
+                h.insert("size".to_yaml(), int(ab.size));
+                h.insert("usage".to_yaml(), ab.usage.as_str().to_yaml());
+                Yaml::Hash(h)
+            })
+            .collect();
+        padd.insert("addressBlocks".to_yaml(), Yaml::Array(blocks));
+    }
+    if !p.interrupt.is_empty() {
+        let mut ints = Hash::new();
+        for i in &p.interrupt {
+            let mut h = Hash::new();
+            insert_opt(&mut h, "description", i.description.as_deref());
+            h.insert("value".to_yaml(), int(i.value));
+            ints.insert(i.name.as_str().to_yaml(), Yaml::Hash(h));
+        }
+        padd.insert("interrupts".to_yaml(), Yaml::Hash(ints));
+    }
+
+    let mut regs = Hash::new();
+    let mut enums = Hash::new();
+    for rc in p.registers.iter().flatten() {
+        match rc {
+            RegisterCluster::Register(r) => {
+                let (radd, renums) = dump_register(r);
+                regs.insert(r.name.as_str().to_yaml(), Yaml::Hash(radd));
+                if !renums.is_empty() {
+                    enums.insert(r.name.as_str().to_yaml(), Yaml::Hash(renums));
+                }
+            }
+            RegisterCluster::Cluster(c) => {
+                log::warn!("Cluster {} can't be added by patch and is skipped", c.name);
+            }
+        }
+    }
+    if !regs.is_empty() {
+        padd.insert("registers".to_yaml(), Yaml::Hash(regs));
+    }
+
+    let mut add = Hash::new();
+    add.insert(pname.to_yaml(), Yaml::Hash(padd));
+    let mut root = Hash::new();
+    root.insert("_add".to_yaml(), Yaml::Hash(add));
+    if !enums.is_empty() {
+        root.insert(pname.to_yaml(), Yaml::Hash(enums));
+    }
+
+    let mut out = String::new();
+    YamlEmitter::new(&mut out).dump(&Yaml::Hash(root))?;
+    Ok(out)
+}
+
+/// Returns `_add` description of register and enumeratedValues of its fields
+fn dump_register(r: &Register) -> (Hash, Hash) {
+    let mut radd = Hash::new();
+    insert_opt(&mut radd, "description", r.description.as_deref());
+    insert_opt(&mut radd, "displayName", r.display_name.as_deref());
+    insert_opt(&mut radd, "derivedFrom", r.derived_from.as_deref());
+    radd.insert("addressOffset".to_yaml(), int(r.address_offset));
+    if let Register::Array(_, d) = r {
+        insert_dim(&mut radd, d);
+    }
+    insert_properties(&mut radd, &r.properties);
+
+    let mut fields = Hash::new();
+    let mut enums = Hash::new();
+    for f in r.fields() {
+        fields.insert(f.name.as_str().to_yaml(), Yaml::Hash(dump_field(f)));
+        let mut fenums = Hash::new();
+        for evs in &f.enumerated_values {
+            let mut h = Hash::new();
+            if let Some(derived) = evs.derived_from.as_deref() {
+                h.insert("_derivedFrom".to_yaml(), derived.to_yaml());
+            } else {
+                insert_opt(&mut h, "_name", evs.name.as_deref());
+                for ev in &evs.values {
+                    let value = if ev.is_default() {
+                        Yaml::Integer(-1)
+                    } else {
+                        int(ev.value.unwrap_or_default())
+                    };
+                    let description = ev.description.as_deref().unwrap_or_default();
+                    h.insert(
+                        ev.name.as_str().to_yaml(),
+                        Yaml::Array(vec![value, description.to_yaml()]),
+                    );
+                }
+            }
+            match evs.usage {
+                Some(Usage::Read) => {
+                    fenums.insert("_read".to_yaml(), Yaml::Hash(h));
+                }
+                Some(Usage::Write) => {
+                    fenums.insert("_write".to_yaml(), Yaml::Hash(h));
+                }
+                _ => fenums.extend(h),
+            }
+        }
+        if !fenums.is_empty() {
+            enums.insert(f.name.as_str().to_yaml(), Yaml::Hash(fenums));
+        }
+    }
+    if !fields.is_empty() {
+        radd.insert("fields".to_yaml(), Yaml::Hash(fields));
+    }
+    (radd, enums)
+}
+
+fn dump_field(f: &Field) -> Hash {
+    let FieldInfo {
+        description,
+        derived_from,
+        bit_range,
+        access,
+        modified_write_values,
+        read_action,
+        ..
+    } = &**f;
+    let mut fadd = Hash::new();
+    insert_opt(&mut fadd, "description", description.as_deref());
+    insert_opt(&mut fadd, "derivedFrom", derived_from.as_deref());
+    fadd.insert("bitOffset".to_yaml(), int(bit_range.offset));
+    fadd.insert("bitWidth".to_yaml(), int(bit_range.width));
+    insert_opt(&mut fadd, "access", access.map(|a| a.as_str()));
+    insert_opt(
+        &mut fadd,
+        "modifiedWriteValues",
+        modified_write_values.map(|m| m.as_str()),
+    );
+    insert_opt(&mut fadd, "readAction", read_action.map(|r| r.as_str()));
+    if let Field::Array(_, d) = f {
+        insert_dim(&mut fadd, d);
+    }
+    fadd
+}
+
+fn insert_properties(h: &mut Hash, properties: &RegisterProperties) {
+    if let Some(size) = properties.size {
+        h.insert("size".to_yaml(), int(size));
+    }
+    insert_opt(h, "access", properties.access.map(|a| a.as_str()));
+    if let Some(reset_value) = properties.reset_value {
+        h.insert("resetValue".to_yaml(), int(reset_value));
+    }
+    if let Some(reset_mask) = properties.reset_mask {
+        h.insert("resetMask".to_yaml(), int(reset_mask));
+    }
+}
+
+fn insert_dim(h: &mut Hash, d: &DimElement) {
+    h.insert("dim".to_yaml(), int(d.dim));
+    h.insert("dimIncrement".to_yaml(), int(d.dim_increment));
+    if let Some(dim_index) = d.dim_index.as_ref() {
+        let dim_index = dim_index.iter().map(|i| i.as_str().to_yaml()).collect();
+        h.insert("dimIndex".to_yaml(), Yaml::Array(dim_index));
+    }
+    insert_opt(h, "dimName", d.dim_name.as_deref());
+}
+
+fn insert_opt(h: &mut Hash, k: &str, v: Option<&str>) {
+    if let Some(v) = v {
+        h.insert(k.to_yaml(), v.to_yaml());
+    }
+}
+
+fn int(v: impl Into<u64>) -> Yaml {
+    Yaml::Integer(v.into() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::device::DeviceExt;
+    use crate::test_utils;
+    use std::path::Path;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn dump_reapply() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("derive_field_enums")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+        let fragment = dump_peripheral(&device, "DAC1").unwrap();
+
+        let (mut restored, _) = test_utils::get_patcher(Path::new("add")).unwrap();
+        restored.peripherals.retain(|p| p.name != "DAC1");
+        let patch = YamlLoader::load_from_str(&fragment).unwrap();
+        restored
+            .process(patch[0].as_hash().unwrap(), &Default::default())
+            .unwrap();
+        assert_eq!(
+            restored.get_peripheral("DAC1"),
+            device.get_peripheral("DAC1")
+        );
+    }
+}
@@ -3,17 +3,20 @@ pub mod patch_cli;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
 use std::fs::File;
 use std::io::{Cursor, Read};
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use svd_parser::expand::{BlockPath, FieldPath, RegisterPath};
 use svd_parser::svd::{
     addressblock::AddressBlockBuilder, interrupt::InterruptBuilder, Access, AddressBlock,
-    AddressBlockUsage, ClusterInfo, ClusterInfoBuilder, Cpu, CpuBuilder, Endian, EnumeratedValue,
-    EnumeratedValues, EnumeratedValuesBuilder, FieldInfo, FieldInfoBuilder, Interrupt,
-    ModifiedWriteValues, PeripheralInfo, PeripheralInfoBuilder, ReadAction, RegisterCluster,
-    RegisterInfo, RegisterInfoBuilder, RegisterProperties, Usage, ValidateLevel, WriteConstraint,
-    WriteConstraintRange,
+    AddressBlockUsage, ClusterInfo, ClusterInfoBuilder, Cpu, CpuBuilder, Device, Endian,
+    EnumeratedValue, EnumeratedValues, EnumeratedValuesBuilder, FieldInfo, FieldInfoBuilder,
+    Interrupt, ModifiedWriteValues, PeripheralInfo, PeripheralInfoBuilder, Protection, ReadAction,
+    RegisterCluster, RegisterInfo, RegisterInfoBuilder, RegisterProperties, Usage, ValidateLevel,
+    WriteConstraint, WriteConstraintRange,
 };
 use svd_parser::SVDError::DimIndexParse;
 use svd_rs::{BitRange, DimArrayIndex, DimElement, DimElementBuilder, MaybeArray};
@@ -27,10 +30,14 @@ use anyhow::{anyhow, Context, Result};
 pub type PatchResult = anyhow::Result<()>;
 
 pub(crate) mod device;
+mod dump;
 use device::DeviceExt;
 mod iterators;
-mod peripheral;
-mod register;
+pub(crate) mod peripheral;
+pub(crate) mod register;
+mod skeleton;
+mod summary;
+pub use summary::{Counts, Summary};
 mod yaml_ext;
 use yaml_ext::{AsType, GetVal, ToYaml};
 
@@ -45,6 +52,21 @@ pub struct Config {
     pub show_patch_on_error: bool,
     pub enum_derive: EnumAutoDerive,
     pub update_fields: bool,
+    pub check_duplicate_fields: bool,
+    pub check_overlaps: bool,
+    pub report_untouched: bool,
+    pub partial_output: bool,
+    pub case_insensitive_match: bool,
+    pub strict: bool,
+    pub summary: bool,
+    /// Directory relative paths of patch are resolved against
+    /// instead of directory of file they are written in
+    pub base_dir: Option<PathBuf>,
+    /// Register size inherited from enclosing device, peripheral and clusters
+    pub(crate) register_size: Option<u32>,
+    /// Fields which got enumeratedValues derived by dotted path in current patch.
+    /// They can be resolved only when all peripherals are processed
+    pub(crate) enum_derives: Rc<RefCell<Vec<(FieldPath, String)>>>,
 }
 
 /// Derive level when several identical enumerationValues added in a field
@@ -68,22 +90,58 @@ impl Default for Config {
             show_patch_on_error: false,
             enum_derive: Default::default(),
             update_fields: true,
+            check_duplicate_fields: false,
+            check_overlaps: false,
+            report_untouched: false,
+            partial_output: false,
+            case_insensitive_match: false,
+            strict: false,
+            summary: false,
+            base_dir: None,
+            register_size: None,
+            enum_derives: Default::default(),
         }
     }
 }
 
+/// Report deletion which matched nothing.
+/// Error in strict mode, otherwise only logged
+fn delete_absent(kind: &str, spec: &str, config: &Config) -> PatchResult {
+    if config.strict {
+        Err(anyhow!("Trying to delete absent {kind} `{spec}`"))
+    } else {
+        log::info!("Trying to delete absent {kind} `{spec}`");
+        Ok(())
+    }
+}
+
 pub fn load_patch(yaml_file: &Path) -> Result<Yaml> {
+    load_patch_with_base_dir(yaml_file, None)
+}
+
+/// Load patch file. Relative paths in it are resolved against `base_dir` if it is set
+pub fn load_patch_with_base_dir(yaml_file: &Path, base_dir: Option<&Path>) -> Result<Yaml> {
     // Load the specified YAML root file
     let f = File::open(yaml_file)?;
     let mut contents = String::new();
     (&f).read_to_string(&mut contents)?;
-    let docs = YamlLoader::load_from_str(&contents)?;
+    parse_patch(&contents, yaml_file, base_dir)
+}
+
+/// Load patch from string. Relative paths in it are resolved against `base_dir`
+pub fn load_patch_from_str(contents: &str, base_dir: &Path) -> Result<Yaml> {
+    // `_path` is path of patch file itself, so use some file name in `base_dir`
+    parse_patch(contents, &base_dir.join("patch.yaml"), None)
+}
+
+fn parse_patch(contents: &str, yaml_file: &Path, base_dir: Option<&Path>) -> Result<Yaml> {
+    let docs = YamlLoader::load_from_str(contents)?;
     let mut doc = docs.into_iter().next().unwrap(); // select the first document
     let root = doc.hash_mut()?;
     root.insert("_path".to_yaml(), yaml_file.to_str().unwrap().to_yaml());
 
     // Load all included YAML files
-    yaml_includes(root)?;
+    yaml_includes_with_base_dir(root, base_dir)?;
     Ok(doc)
 }
 
@@ -93,17 +151,22 @@ pub fn process_file(
     format_config: Option<&Path>,
     config: &Config,
 ) -> Result<()> {
-    let doc = load_patch(yaml_file)?;
+    let base_dir = config.base_dir.as_deref();
+    let doc = load_patch_with_base_dir(yaml_file, base_dir)?;
 
     // Load the specified SVD file
-    let svdpath = abspath(
-        yaml_file,
-        Path::new(
-            doc.hash()?
-                .get_str("_svd")?
-                .ok_or_else(|| anyhow!("You must have an svd key in the root YAML file"))?,
-        ),
-    )?;
+    let svd = doc
+        .hash()?
+        .get_str("_svd")?
+        .ok_or_else(|| anyhow!("You must have an svd key in the root YAML file"))?;
+    let (svd_in, svdpath): (Box<dyn Read>, _) =
+        if svd.starts_with("http://") || svd.starts_with("https://") {
+            // Downloaded SVD is not saved, its name is used only for output file
+            (Box::new(fetch_svd(svd)?), url_svd_path(yaml_file, svd))
+        } else {
+            let svdpath = abspath(yaml_file, Path::new(svd), base_dir)?;
+            (Box::new(File::open(&svdpath)?), svdpath)
+        };
     let svdpath_out = if let Some(out_path) = out_path {
         out_path.to_owned()
     } else {
@@ -114,12 +177,43 @@ pub fn process_file(
 
     let encoder_config = get_encoder_config(format_config)?;
 
-    let mut svd_out = process_reader(File::open(svdpath)?, &doc, &encoder_config, config)?;
+    let mut svd_out = process_reader(svd_in, &doc, &encoder_config, config)?;
     std::io::copy(&mut svd_out, &mut File::create(svdpath_out)?)?;
 
     Ok(())
 }
 
+/// Path near the YAML file named as file in `url` without query and fragment.
+/// YAML file stem is used if `url` has no file name
+fn url_svd_path(yaml_file: &Path, url: &str) -> PathBuf {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let name = path.rsplit('/').next().unwrap_or_default();
+    if name.is_empty() {
+        yaml_file.with_extension("svd")
+    } else {
+        yaml_file.with_file_name(name)
+    }
+}
+
+/// Download SVD file from `url`
+#[cfg(feature = "network")]
+fn fetch_svd(url: &str) -> Result<impl Read> {
+    let mut contents = Vec::new();
+    ureq::get(url)
+        .call()
+        .with_context(|| format!("Downloading SVD from `{url}`"))?
+        .into_reader()
+        .read_to_end(&mut contents)?;
+    Ok(Cursor::new(contents))
+}
+
+#[cfg(not(feature = "network"))]
+fn fetch_svd(url: &str) -> Result<std::io::Empty> {
+    Err(anyhow!(
+        "Downloading SVD from `{url}` requires svdtools built with `network` feature"
+    ))
+}
+
 pub fn process_reader<R: Read>(
     mut svd: R,
     patch: &Yaml,
@@ -131,9 +225,10 @@ pub fn process_reader<R: Read>(
     let mut parser_config = svd_parser::Config::default();
     parser_config.validate_level = ValidateLevel::Disabled;
     let mut dev = svd_parser::parse_with_config(&contents, &parser_config)?;
+    let orig = config.summary.then(|| dev.clone());
 
     // Process device
-    dev.process(patch.hash()?, config).with_context(|| {
+    let touched = dev.process(patch.hash()?, config).with_context(|| {
         let name = &dev.name;
         let mut out_str = String::new();
         let mut emitter = yaml_rust::YamlEmitter::new(&mut out_str);
@@ -145,6 +240,20 @@ pub fn process_reader<R: Read>(
         }
     })?;
 
+    if let Some(orig) = orig.as_ref() {
+        eprint!("{}", Summary::new(orig, &dev));
+    }
+
+    if config.report_untouched {
+        for pname in dev.untouched_peripherals(&touched) {
+            log::warn!("Peripheral `{pname}` is not touched by patch");
+        }
+    }
+
+    if config.partial_output {
+        retain_touched(&mut dev, touched);
+    }
+
     dev.validate_all(config.post_validate)?;
 
     Ok(Cursor::new(
@@ -152,20 +261,55 @@ pub fn process_reader<R: Read>(
     ))
 }
 
+/// Leave only touched peripherals and peripherals they are derived from
+fn retain_touched(dev: &mut Device, mut touched: HashSet<String>) {
+    loop {
+        let bases = dev
+            .peripherals
+            .iter()
+            .filter(|p| touched.contains(&p.name))
+            .filter_map(|p| p.derived_from.clone())
+            .filter(|base| !touched.contains(base))
+            .collect::<Vec<_>>();
+        if bases.is_empty() {
+            break;
+        }
+        touched.extend(bases);
+    }
+    dev.peripherals.retain(|p| touched.contains(&p.name));
+}
+
 /// Gets the absolute path of relpath from the point of view of frompath.
-fn abspath(frompath: &Path, relpath: &Path) -> Result<PathBuf, std::io::Error> {
-    normpath::BasePath::new(frompath)
-        .unwrap()
-        .parent()
-        .unwrap()
-        .unwrap()
-        .join(relpath)
-        .canonicalize()
-        .map(|b| b.as_path().into())
+/// Resolve `relpath` against `base_dir` if it is given or directory of `frompath` otherwise
+fn abspath(
+    frompath: &Path,
+    relpath: &Path,
+    base_dir: Option<&Path>,
+) -> Result<PathBuf, std::io::Error> {
+    let path = if let Some(base_dir) = base_dir {
+        normpath::BasePath::new(base_dir)?.join(relpath)
+    } else {
+        normpath::BasePath::new(frompath)
+            .unwrap()
+            .parent()
+            .unwrap()
+            .unwrap()
+            .join(relpath)
+    };
+    path.canonicalize().map(|b| b.as_path().into())
 }
 
 /// Recursively loads any included YAML files.
 pub fn yaml_includes(parent: &mut Hash) -> Result<Vec<PathBuf>> {
+    yaml_includes_with_base_dir(parent, None)
+}
+
+/// Recursively loads any included YAML files.
+/// Relative paths are resolved against `base_dir` if it is set
+pub fn yaml_includes_with_base_dir(
+    parent: &mut Hash,
+    base_dir: Option<&Path>,
+) -> Result<Vec<PathBuf>> {
     let y_path = "_path".to_yaml();
     let mut included = vec![];
     let self_path = PathBuf::from(parent.get(&y_path).unwrap().str()?);
@@ -177,7 +321,7 @@ pub fn yaml_includes(parent: &mut Hash) -> Result<Vec<PathBuf>> {
                 Yaml::Hash(val) if val.contains_key(&"_include".to_yaml()) => {
                     let ypath = self_path.to_str().unwrap().to_yaml();
                     val.insert(y_path.clone(), ypath.clone());
-                    included.extend(yaml_includes(val)?);
+                    included.extend(yaml_includes_with_base_dir(val, base_dir)?);
                 }
                 _ => {}
             }
@@ -190,7 +334,7 @@ pub fn yaml_includes(parent: &mut Hash) -> Result<Vec<PathBuf>> {
         .collect::<Vec<_>>();
     for relpath in inc {
         let relpath = relpath.as_str();
-        let path = abspath(&self_path, Path::new(relpath))
+        let path = abspath(&self_path, Path::new(relpath), base_dir)
             .with_context(|| anyhow!("Opening file \"{relpath}\" from file {self_path:?}"))?;
         if included.contains(&path) {
             continue;
@@ -208,7 +352,7 @@ pub fn yaml_includes(parent: &mut Hash) -> Result<Vec<PathBuf>> {
         included.push(path.clone());
 
         // Process any top-level includes in child
-        included.extend(yaml_includes(child)?);
+        included.extend(yaml_includes_with_base_dir(child, base_dir)?);
         update_dict(parent, child)?;
     }
     parent.remove(&"_include".to_yaml());
@@ -270,14 +414,35 @@ fn update_dict(parent: &mut Hash, child: &Hash) -> Result<()> {
     Ok(())
 }
 
+thread_local! {
+    /// Whether specs match names ignoring case. Set from [`Config::case_insensitive_match`]
+    static CASE_INSENSITIVE: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Switches case of spec matching until dropped
+pub(crate) struct MatchCaseGuard(bool);
+
+impl MatchCaseGuard {
+    pub(crate) fn new(case_insensitive: bool) -> Self {
+        Self(CASE_INSENSITIVE.with(|c| c.replace(case_insensitive)))
+    }
+}
+
+impl Drop for MatchCaseGuard {
+    fn drop(&mut self) {
+        CASE_INSENSITIVE.with(|c| c.set(self.0));
+    }
+}
+
 /// Check if name matches against a specification
-fn matchname(name: &str, spec: &str) -> bool {
+pub(crate) fn matchname(name: &str, spec: &str) -> bool {
     matchsubspec(name, spec).is_some()
 }
 
 fn newglob(spec: &str) -> globset::GlobMatcher {
     globset::GlobBuilder::new(spec)
         .backslash_escape(true)
+        .case_insensitive(CASE_INSENSITIVE.with(Cell::get))
         .build()
         .unwrap()
         .compile_matcher()
@@ -302,21 +467,106 @@ fn matchsubspec<'a>(name: &str, spec: &'a str) -> Option<&'a str> {
 }
 
 fn modify_register_properties(p: &mut RegisterProperties, f: &str, val: &Yaml) -> PatchResult {
+    // Empty string resets property
+    if val.as_str() == Some("") {
+        match f {
+            "size" => p.size = None,
+            "access" => p.access = None,
+            "resetValue" => p.reset_value = None,
+            "resetMask" => p.reset_mask = None,
+            "protection" => p.protection = None,
+            _ => {}
+        }
+        return Ok(());
+    }
     match f {
         "size" => p.size = Some(val.i64()? as u32),
         "access" => p.access = Access::parse_str(val.str()?),
         "resetValue" => p.reset_value = Some(val.i64()? as u64),
         "resetMask" => p.reset_mask = Some(val.i64()? as u64),
-        "protection" => {}
+        "protection" => p.protection = Some(parse_protection(val.str()?)?),
         _ => {}
     }
     Ok(())
 }
 
+/// Regex rename from `_rename`-like hash
+pub struct Rename<'a> {
+    pattern: &'a str,
+    re: Regex,
+    replacement: &'a str,
+    /// Pattern had `?~` prefix, so it may match nothing
+    ignore: bool,
+}
+
+/// Compile patterns of `_rename`-like hash at `key`. Patterns must fully match names
+fn get_renames<'a>(h: &'a Hash, key: &str) -> Result<Vec<Rename<'a>>> {
+    h.hash_iter(key)
+        .map(|(pattern, replacement)| {
+            let (pattern, ignore) = pattern.str()?.spec();
+            let re = Regex::new(&format!("^(?:{pattern})$"))
+                .with_context(|| format!("Invalid `{key}` pattern `{pattern}`"))?;
+            Ok(Rename {
+                pattern,
+                re,
+                replacement: replacement.str()?,
+                ignore,
+            })
+        })
+        .collect()
+}
+
+/// Replace `name` using first of `renames` which matches it.
+/// Returns index of used rename
+fn apply_renames(renames: &[Rename], name: &mut String) -> Option<usize> {
+    let i = renames.iter().position(|r| r.re.is_match(name))?;
+    let r = &renames[i];
+    *name = r.re.replace(name, r.replacement).into_owned();
+    Some(i)
+}
+
+/// Check that every rename without `?~` was `used`
+/// and that `renamed` names are unique among `names`
+fn check_renames(
+    renames: &[Rename],
+    used: &[bool],
+    renamed: &[String],
+    names: &[&str],
+    kind: &str,
+) -> PatchResult {
+    for (r, used) in renames.iter().zip(used) {
+        if !used && !r.ignore {
+            return Err(anyhow!("Could not find {kind} matching `{}`", r.pattern));
+        }
+    }
+    for name in renamed {
+        if names.iter().filter(|n| **n == name).count() > 1 {
+            return Err(anyhow!("Several {kind} are named `{name}` after renaming"));
+        }
+    }
+    Ok(())
+}
+
+/// Parse access. Empty string means no access
+fn parse_access(s: &str) -> Result<Option<Access>> {
+    if s.is_empty() {
+        return Ok(None);
+    }
+    Access::parse_str(s)
+        .map(Some)
+        .ok_or_else(|| anyhow!("Unknown access `{s}`"))
+}
+
+fn parse_protection(s: &str) -> Result<Protection> {
+    Protection::parse_str(s)
+        .ok_or_else(|| anyhow!("Unknown protection `{s}`, expected `s`, `n` or `p`"))
+}
+
 fn get_register_properties(h: &Hash) -> Result<RegisterProperties> {
     Ok(RegisterProperties::new()
         .size(h.get_u32("size")?)
         .access(h.get_str("access")?.and_then(Access::parse_str))
+        .protection(h.get_str("protection")?.map(parse_protection).transpose()?)
         .reset_value(h.get_u64("resetValue")?)
         .reset_mask(h.get_u64("resetMask")?))
 }
@@ -337,7 +587,9 @@ fn make_ev_name(name: &str, usage: Option<Usage>) -> Result<String> {
 }
 
 fn make_ev_array(values: &Hash) -> Result<EnumeratedValuesBuilder> {
-    let mut h = std::collections::BTreeMap::new();
+    let mut h: std::collections::BTreeMap<u64, EnumeratedValue> = std::collections::BTreeMap::new();
+    // Default is kept apart from concrete values so it can't take a value slot
+    let mut default: Option<EnumeratedValue> = None;
     for (n, vd) in values {
         let vname = n.str()?;
         if !vname.starts_with('_') {
@@ -360,16 +612,24 @@ fn make_ev_array(values: &Hash) -> Result<EnumeratedValuesBuilder> {
             let ev = EnumeratedValue::builder()
                 .name(vname.into())
                 .description(Some(description.into()));
-            let ev = (if def {
-                ev.is_default(Some(true))
-            } else {
-                ev.value(Some(value))
-            })
-            .build(VAL_LVL)?;
+            if def {
+                if let Some(d) = default.as_ref() {
+                    return Err(anyhow!(
+                        "enumeratedValues can't have several defaults: {} and {vname}",
+                        d.name
+                    ));
+                }
+                default = Some(ev.is_default(Some(true)).build(VAL_LVL)?);
+                continue;
+            }
+            let ev = ev.value(Some(value)).build(VAL_LVL)?;
             use std::collections::btree_map::Entry;
             match h.entry(value) {
-                Entry::Occupied(_) => {
-                    return Err(anyhow!("enumeratedValue can't have duplicate values"));
+                Entry::Occupied(e) => {
+                    return Err(anyhow!(
+                        "enumeratedValue can't have duplicate values: {} and {vname} are {value}",
+                        e.get().name
+                    ));
                 }
                 Entry::Vacant(e) => {
                     e.insert(ev);
@@ -377,7 +637,7 @@ fn make_ev_array(values: &Hash) -> Result<EnumeratedValuesBuilder> {
             }
         }
     }
-    Ok(EnumeratedValues::builder().values(h.into_values().collect()))
+    Ok(EnumeratedValues::builder().values(h.into_values().chain(default).collect()))
 }
 
 /// Returns an enumeratedValues Element which is derivedFrom name
@@ -564,29 +824,48 @@ fn get_write_constraint(h: &Hash) -> Result<Option<WriteConstraint>> {
         .get_yaml("_write_constraint")
         .or_else(|| h.get_yaml("writeConstraint"))
     {
-        match write_constraint {
-            Yaml::String(s) if s == "none" => {
-                // Completely remove the existing writeConstraint
-                Ok(None)
-            }
-            Yaml::String(s) if s == "enum" => {
-                // Only allow enumerated values
-                Ok(Some(WriteConstraint::UseEnumeratedValues(true)))
-            }
-            Yaml::Array(a) => {
-                // Allow a certain range
-                Ok(Some(WriteConstraint::Range(WriteConstraintRange {
-                    min: a[0].i64()? as u64,
-                    max: a[1].i64()? as u64,
-                })))
-            }
-            _ => Err(anyhow!("Unknown writeConstraint type {write_constraint:?}")),
-        }
+        parse_write_constraint(write_constraint)
     } else {
         Ok(None)
     }
 }
 
+/// Parse `writeConstraint` value. `none` gives `None`
+fn parse_write_constraint(write_constraint: &Yaml) -> Result<Option<WriteConstraint>> {
+    match write_constraint {
+        Yaml::String(s) if s == "none" => {
+            // Completely remove the existing writeConstraint
+            Ok(None)
+        }
+        Yaml::String(s) if s == "enum" => {
+            // Only allow enumerated values
+            Ok(Some(WriteConstraint::UseEnumeratedValues(true)))
+        }
+        Yaml::String(s) if s == "writeAsRead" => {
+            // Only allow writing the value which was read
+            Ok(Some(WriteConstraint::WriteAsRead(true)))
+        }
+        Yaml::Array(a) => {
+            // Allow a certain range
+            Ok(Some(WriteConstraint::Range(WriteConstraintRange {
+                min: a[0].i64()? as u64,
+                max: a[1].i64()? as u64,
+            })))
+        }
+        Yaml::Hash(h) if h.len() == 1 => {
+            // Explicit form as in SVD, e.g. `useEnumeratedValues: false`
+            if let Some(b) = h.get_bool("useEnumeratedValues")? {
+                Ok(Some(WriteConstraint::UseEnumeratedValues(b)))
+            } else if let Some(b) = h.get_bool("writeAsRead")? {
+                Ok(Some(WriteConstraint::WriteAsRead(b)))
+            } else {
+                Err(anyhow!("Unknown writeConstraint type {write_constraint:?}"))
+            }
+        }
+        _ => Err(anyhow!("Unknown writeConstraint type {write_constraint:?}")),
+    }
+}
+
 fn make_cluster(cadd: &Hash, path: Option<&BlockPath>) -> Result<ClusterInfoBuilder> {
     let mut cnew = ClusterInfo::builder()
         .description(opt_interpolate(&path, cadd.get_str("description")?))
@@ -620,10 +899,15 @@ fn make_cluster(cadd: &Hash, path: Option<&BlockPath>) -> Result<ClusterInfoBuil
     Ok(cnew)
 }
 
-fn make_interrupt(iadd: &Hash) -> Result<InterruptBuilder> {
-    let mut int = Interrupt::builder().description(iadd.get_string("description")?);
-    if let Some(name) = iadd.get_string("name")? {
-        int = int.name(name)
+/// `%s` in description is replaced with interrupt name
+fn make_interrupt(iadd: &Hash, iname: &str) -> Result<InterruptBuilder> {
+    let name = iadd.get_str("name")?;
+    let mut int = Interrupt::builder().description(
+        iadd.get_str("description")?
+            .map(|d| d.replace("%s", name.unwrap_or(iname))),
+    );
+    if let Some(name) = name {
+        int = int.name(name.into())
     }
     if let Some(value) = iadd.get_i64("value")? {
         int = int.value(value as u32)
@@ -643,9 +927,10 @@ fn make_peripheral(padd: &Hash, modify: bool) -> Result<PeripheralInfoBuilder> {
                 Some(h) => {
                     let mut interupts = Vec::new();
                     for (iname, val) in h {
+                        let iname = iname.str()?;
                         interupts.push(
-                            make_interrupt(val.hash()?)?
-                                .name(iname.str()?.into())
+                            make_interrupt(val.hash()?, iname)?
+                                .name(iname.into())
                                 .build(VAL_LVL)?,
                         );
                     }
@@ -702,25 +987,59 @@ fn make_peripheral(padd: &Hash, modify: bool) -> Result<PeripheralInfoBuilder> {
     }
 }
 
+/// Keys of optional `cpu` elements which are removed when set to `null`
+const CPU_OPTIONAL: &[&str] = &[
+    "fpuDP",
+    "dspPresent",
+    "icachePresent",
+    "dcachePresent",
+    "itcmPresent",
+    "dtcmPresent",
+    "vtorPresent",
+    "deviceNumInterrupts",
+    "sauNumRegions",
+];
+
 fn make_cpu(cmod: &Hash) -> Result<CpuBuilder> {
+    let is_null = |k| matches!(cmod.get_yaml(k), Some(Yaml::Null));
+    let get_bool = |k| {
+        if is_null(k) {
+            Ok(None)
+        } else {
+            cmod.get_bool(k)
+        }
+    };
+    let get_u32 = |k| {
+        if is_null(k) {
+            Ok(None)
+        } else {
+            cmod.get_u32(k)
+        }
+    };
     let mut cpu = Cpu::builder()
-        .fpu_double_precision(cmod.get_bool("fpuDP")?)
-        .dsp_present(cmod.get_bool("dspPresent")?)
-        .icache_present(cmod.get_bool("icachePresent")?)
-        .dcache_present(cmod.get_bool("dcachePresent")?)
-        .itcm_present(cmod.get_bool("itcmPresent")?)
-        .dtcm_present(cmod.get_bool("dtcmPresent")?)
-        .vtor_present(cmod.get_bool("vtorPresent")?)
-        .device_num_interrupts(cmod.get_u32("deviceNumInterrupts")?)
-        .sau_num_regions(cmod.get_u32("sauNumRegions")?);
-    if let Some(name) = cmod.get_string("name")? {
+        .fpu_double_precision(get_bool("fpuDP")?)
+        .dsp_present(get_bool("dspPresent")?)
+        .icache_present(get_bool("icachePresent")?)
+        .dcache_present(get_bool("dcachePresent")?)
+        .itcm_present(get_bool("itcmPresent")?)
+        .dtcm_present(get_bool("dtcmPresent")?)
+        .vtor_present(get_bool("vtorPresent")?)
+        .device_num_interrupts(get_u32("deviceNumInterrupts")?)
+        .sau_num_regions(get_u32("sauNumRegions")?);
+    // `name` and `revision` are required, so `null` clears them to empty string
+    if is_null("name") {
+        cpu = cpu.name(String::new());
+    } else if let Some(name) = cmod.get_string("name")? {
         cpu = cpu.name(name);
     }
-    if let Some(revision) = cmod.get_string("revision")? {
+    if is_null("revision") {
+        cpu = cpu.revision(String::new());
+    } else if let Some(revision) = cmod.get_string("revision")? {
         cpu = cpu.revision(revision);
     }
-    if let Some(endian) = cmod.get_str("endian")?.and_then(Endian::parse_str) {
-        cpu = cpu.endian(endian);
+    if let Some(endian) = cmod.get_str("endian")? {
+        cpu = cpu
+            .endian(Endian::parse_str(endian).ok_or_else(|| anyhow!("Unknown endian `{endian}`"))?);
     }
     if let Some(mpu_present) = cmod.get_bool("mpuPresent")? {
         cpu = cpu.mpu_present(mpu_present);
@@ -737,6 +1056,26 @@ fn make_cpu(cmod: &Hash) -> Result<CpuBuilder> {
     Ok(cpu)
 }
 
+/// Remove optional `cpu` elements set to `null` in `cmod`
+fn clear_cpu(cpu: &mut Cpu, cmod: &Hash) {
+    for &k in CPU_OPTIONAL {
+        if matches!(cmod.get_yaml(k), Some(Yaml::Null)) {
+            match k {
+                "fpuDP" => cpu.fpu_double_precision = None,
+                "dspPresent" => cpu.dsp_present = None,
+                "icachePresent" => cpu.icache_present = None,
+                "dcachePresent" => cpu.dcache_present = None,
+                "itcmPresent" => cpu.itcm_present = None,
+                "dtcmPresent" => cpu.dtcm_present = None,
+                "vtorPresent" => cpu.vtor_present = None,
+                "deviceNumInterrupts" => cpu.device_num_interrupts = None,
+                "sauNumRegions" => cpu.sau_num_regions = None,
+                _ => unreachable!(),
+            }
+        }
+    }
+}
+
 /// Find left and right indices of enumeration token in specification string
 ///
 /// # Examples
@@ -780,6 +1119,20 @@ fn check_offsets(offsets: &[u32], dim_increment: u32) -> bool {
     true
 }
 
+/// Drops `dimIndex` of collected array if it is default `0, 1, ..., dim - 1`.
+/// Other ascending ranges are written compactly by encoder
+fn explicit_dim_index(dim_index: Vec<String>) -> Option<Vec<String>> {
+    if dim_index
+        .iter()
+        .enumerate()
+        .all(|(i, idx)| *idx == i.to_string())
+    {
+        None
+    } else {
+        Some(dim_index)
+    }
+}
+
 /// Tries to get common description (or displayNames) for register/field array with "%s" in index position.
 /// Returns `None` if incoming descriptions have more then 1 difference
 fn common_description(descs: &[Option<&str>], dim_index: &[String]) -> Option<Option<String>> {
@@ -834,6 +1187,93 @@ impl Spec for str {
     }
 }
 
+/// Collects violations of CMSIS-SVD constraints which are not checked by parser:
+/// `dimIndex` count differing from `dim`, enumerated values which do not fit
+/// in field and register offsets or `dimIncrement` unaligned to register size
+pub fn cmsis_lints(dev: &Device) -> Vec<String> {
+    let mut out = Vec::new();
+    for p in &dev.peripherals {
+        if let MaybeArray::Array(_, d) = p {
+            lint_dim_index(&p.name, d, &mut out);
+        }
+        let size = p
+            .default_register_properties
+            .size
+            .or(dev.default_register_properties.size)
+            .unwrap_or(32);
+        lint_children(
+            &BlockPath::new(&p.name),
+            p.registers.iter().flatten(),
+            size,
+            &mut out,
+        );
+    }
+    out
+}
+
+fn lint_children<'a>(
+    bpath: &BlockPath,
+    children: impl Iterator<Item = &'a RegisterCluster>,
+    size: u32,
+    out: &mut Vec<String>,
+) {
+    for rc in children {
+        match rc {
+            RegisterCluster::Register(r) => {
+                let rpath = bpath.new_register(&r.name);
+                if let MaybeArray::Array(_, d) = r {
+                    lint_dim_index(&rpath, d, out);
+                }
+                let size = r.properties.size.unwrap_or(size);
+                for problem in register::misaligned_offsets(r, size) {
+                    out.push(format!("{rpath}: {problem}"));
+                }
+                for f in r.fields() {
+                    let fpath = rpath.new_field(&f.name);
+                    if let MaybeArray::Array(_, d) = f {
+                        lint_dim_index(&fpath, d, out);
+                    }
+                    for ev in register::enums_out_of_width(f) {
+                        out.push(format!(
+                            "{fpath}: enumeratedValue {} ({:#x}) does not fit in {} bits",
+                            ev.name,
+                            ev.value.unwrap_or_default(),
+                            f.bit_range.width
+                        ));
+                    }
+                    for ev in register::enums_out_of_range(f) {
+                        out.push(format!(
+                            "{fpath}: enumeratedValue {} ({:#x}) is out of writeConstraint range",
+                            ev.name,
+                            ev.value.unwrap_or_default()
+                        ));
+                    }
+                }
+            }
+            RegisterCluster::Cluster(c) => {
+                let cpath = bpath.new_cluster(&c.name);
+                if let MaybeArray::Array(_, d) = c {
+                    lint_dim_index(&cpath, d, out);
+                }
+                let size = c.default_register_properties.size.unwrap_or(size);
+                lint_children(&cpath, c.children.iter(), size, out);
+            }
+        }
+    }
+}
+
+fn lint_dim_index(path: &impl std::fmt::Display, d: &DimElement, out: &mut Vec<String>) {
+    if let Some(problem) = dim_index_mismatch(d) {
+        out.push(format!("{path}: {problem}"));
+    }
+}
+
+/// Describes `dimIndex` which count differs from `dim`
+pub(crate) fn dim_index_mismatch(d: &DimElement) -> Option<String> {
+    let len = d.dim_index.as_ref().map_or(d.dim as usize, Vec::len);
+    (len != d.dim as usize).then(|| format!("dimIndex has {len} elements, but dim is {}", d.dim))
+}
+
 pub(crate) fn check_dimable_name(name: &str) -> Result<()> {
     static PATTERN: Lazy<Regex> = Lazy::new(|| {
         Regex::new("^(((%s)|(%s)[_A-Za-z]{1}[_A-Za-z0-9]*)|([_A-Za-z]{1}[_A-Za-z0-9]*(\\[%s\\])?)|([_A-Za-z]{1}[_A-Za-z0-9]*(%s)?[_A-Za-z0-9]*))$").unwrap()
@@ -905,6 +1345,154 @@ mod tests {
     use crate::test_utils;
     use std::path::Path;
 
+    #[test]
+    fn case_insensitive_match() {
+        let patch = YamlLoader::load_from_str(
+            r"
+dac1:
+  _add:
+    Ch1_Data:
+      addressOffset: 0x10
+    Ch2_Data:
+      addressOffset: 0x14
+  _modify:
+    cr:
+      description: Control
+  _array:
+    ch?_data: {}
+",
+        )
+        .unwrap();
+        let patch = patch[0].as_hash().unwrap();
+
+        let (mut device, _) = test_utils::get_patcher(Path::new("add")).unwrap();
+        assert!(device.process(patch, &Default::default()).is_err());
+
+        let (mut device, _) = test_utils::get_patcher(Path::new("add")).unwrap();
+        let config = Config {
+            case_insensitive_match: true,
+            ..Default::default()
+        };
+        device.process(patch, &config).unwrap();
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let cr = dac1.get_register("CR").unwrap();
+        assert_eq!(cr.description.as_deref(), Some("Control"));
+        let Some(MaybeArray::Array(data, dim)) = dac1.get_register("Ch%s_Data") else {
+            panic!("Ch%s_Data array is not collected");
+        };
+        assert_eq!(data.address_offset, 0x10);
+        assert_eq!(
+            dim.dim_index.as_deref(),
+            Some(&["1".to_string(), "2".to_string()][..])
+        );
+
+        // matching is case sensitive again after patching
+        assert!(!matchname("DAC1", "dac1"));
+    }
+
+    #[test]
+    fn modify_protection() {
+        let (mut device, _) = test_utils::get_patcher(Path::new("add")).unwrap();
+        let patch = YamlLoader::load_from_str(
+            r"
+_modify:
+  protection: n
+DAC1:
+  _modify:
+    CR:
+      protection: s
+",
+        )
+        .unwrap();
+        device
+            .process(patch[0].as_hash().unwrap(), &Default::default())
+            .unwrap();
+        assert_eq!(
+            device.default_register_properties.protection,
+            Some(Protection::NonSecure)
+        );
+        let cr = device
+            .get_peripheral("DAC1")
+            .unwrap()
+            .get_register("CR")
+            .unwrap();
+        assert_eq!(cr.properties.protection, Some(Protection::Secure));
+
+        let xml = svd_encoder::encode(&device).unwrap();
+        let parsed = svd_parser::parse(&xml).unwrap();
+        assert_eq!(
+            parsed.get_peripheral("DAC1").unwrap().get_register("CR"),
+            Some(cr)
+        );
+
+        let patch =
+            YamlLoader::load_from_str("DAC1:\n  _modify:\n    CR:\n      protection: x").unwrap();
+        let err = device
+            .process(patch[0].as_hash().unwrap(), &Default::default())
+            .unwrap_err();
+        assert_eq!(
+            err.root_cause().to_string(),
+            "Unknown protection `x`, expected `s`, `n` or `p`"
+        );
+    }
+
+    #[test]
+    fn cmsis_lints() {
+        let svd = r"<device>
+  <name>dev</name>
+  <peripherals>
+    <peripheral>
+      <dim>2</dim>
+      <dimIncrement>0x100</dimIncrement>
+      <name>PER_%s</name>
+      <baseAddress>0x40000000</baseAddress>
+      <registers>
+        <register>
+          <name>CR</name>
+          <addressOffset>0x2</addressOffset>
+          <size>32</size>
+          <fields>
+            <field>
+              <name>MODE</name>
+              <bitOffset>0</bitOffset>
+              <bitWidth>2</bitWidth>
+              <writeConstraint><range><minimum>0</minimum><maximum>1</maximum></range></writeConstraint>
+              <enumeratedValues>
+                <enumeratedValue><name>A</name><value>0</value></enumeratedValue>
+                <enumeratedValue><name>B</name><value>2</value></enumeratedValue>
+                <enumeratedValue><name>C</name><value>4</value></enumeratedValue>
+              </enumeratedValues>
+            </field>
+          </fields>
+        </register>
+        <register>
+          <name>SR</name>
+          <addressOffset>0x4</addressOffset>
+          <size>32</size>
+        </register>
+      </registers>
+    </peripheral>
+  </peripherals>
+</device>";
+        let mut parser_config = svd_parser::Config::default();
+        parser_config.validate_level = ValidateLevel::Disabled;
+        let mut device = svd_parser::parse_with_config(svd, &parser_config).unwrap();
+        // parser rejects such `dimIndex`, but patches can produce it
+        if let MaybeArray::Array(_, d) = &mut device.peripherals[0] {
+            d.dim_index = Some(vec!["A".into(), "B".into(), "C".into()]);
+        }
+        assert_eq!(
+            super::cmsis_lints(&device),
+            [
+                "PER_%s: dimIndex has 3 elements, but dim is 2",
+                "PER_%s.CR: addressOffset 0x2 is not aligned to 32-bit size",
+                "PER_%s.CR.MODE: enumeratedValue C (0x4) does not fit in 2 bits",
+                "PER_%s.CR.MODE: enumeratedValue B (0x2) is out of writeConstraint range",
+                "PER_%s.CR.MODE: enumeratedValue C (0x4) is out of writeConstraint range",
+            ]
+        );
+    }
+
     #[test]
     fn add_register() -> Result<()> {
         let (mut device, yaml) = test_utils::get_patcher(Path::new("add_register")).unwrap();
@@ -949,4 +1537,235 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn add_interrupt_description_template() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("add_interrupt")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let int = dac1.get_interrupt("DAC").unwrap();
+        assert_eq!(int.value, 54);
+        assert_eq!(int.description.as_deref(), Some("DAC global interrupt"));
+    }
+
+    fn ev_hash(yaml: &str) -> Hash {
+        YamlLoader::load_from_str(yaml).unwrap()[0]
+            .as_hash()
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn default_dim_index() {
+        let idx = |s: &[&str]| s.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(explicit_dim_index(idx(&["0", "1", "2"])), None);
+        for explicit in [&["00", "01"][..], &["3", "2", "1", "0"], &["1", "3", "5"]] {
+            assert_eq!(explicit_dim_index(idx(explicit)), Some(idx(explicit)));
+        }
+    }
+
+    #[test]
+    fn ev_array_several_defaults() {
+        let values = ev_hash("A: [0, a]\nB: [-1, b]\nC: [-1, c]");
+        let err = make_ev_array(&values).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "enumeratedValues can't have several defaults: B and C"
+        );
+    }
+
+    #[test]
+    fn ev_array_value_collision() {
+        let values = ev_hash("A: [1, a]\nB: [-1, b]\nC: [1, c]");
+        let err = make_ev_array(&values).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "enumeratedValue can't have duplicate values: A and C are 1"
+        );
+        let values = ev_hash("B: [-1, b]\nA: [1, a]");
+        let evs = make_ev_array(&values).unwrap().build(VAL_LVL).unwrap();
+        assert_eq!(evs.values[0].name, "A");
+        assert_eq!(evs.values[1].is_default, Some(true));
+    }
+
+    #[test]
+    fn ev_array_radix() {
+        let values = ev_hash(
+            "HEX: [0x0A, hex]\nBIN: [\"0b1011\", bin]\nUND: [\"0x1_0\", und]\nMAX: [0xFFFFFFFF, max]",
+        );
+        let evs = make_ev_array(&values).unwrap().build(VAL_LVL).unwrap();
+        let values = evs
+            .values
+            .iter()
+            .map(|ev| (ev.name.as_str(), ev.value, ev.is_default))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            [
+                ("HEX", Some(10), None),
+                ("BIN", Some(11), None),
+                ("UND", Some(16), None),
+                ("MAX", Some(0xFFFF_FFFF), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn partial_output() {
+        let dir = test_utils::res_dir().join("modify");
+        let patch = load_patch(&dir.join("patch.yaml")).unwrap();
+        let svd = File::open(dir.join("stm32l4x2.svd")).unwrap();
+        let config = Config {
+            partial_output: true,
+            ..Default::default()
+        };
+        let mut out = String::new();
+        process_reader(svd, &patch, &Default::default(), &config)
+            .unwrap()
+            .read_to_string(&mut out)
+            .unwrap();
+        let device = svd_parser::parse(&out).unwrap();
+        let names = device
+            .peripherals
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["DAC11"]);
+    }
+
+    #[test]
+    fn url_svd_path() {
+        let yaml = Path::new("dir/patch.yaml");
+        let path = |url| super::url_svd_path(yaml, url);
+        assert_eq!(
+            path("https://host/svd/x.svd?raw=true#top"),
+            Path::new("dir/x.svd")
+        );
+        assert_eq!(path("https://host/svd/"), Path::new("dir/patch.svd"));
+    }
+
+    #[test]
+    fn load_patch_from_str() {
+        let dir = test_utils::res_dir().join("include");
+        let patch =
+            super::load_patch_from_str("_include: [tsc.yaml]\n", &dir.join("subdir")).unwrap();
+        let svd = File::open(dir.join("stm32l4x2.svd")).unwrap();
+        let mut out = String::new();
+        process_reader(svd, &patch, &Default::default(), &Default::default())
+            .unwrap()
+            .read_to_string(&mut out)
+            .unwrap();
+        let device = svd_parser::parse(&out).unwrap();
+        let en1 = device
+            .get_peripheral("DAC1")
+            .unwrap()
+            .get_register("CR")
+            .unwrap()
+            .get_field("EN1")
+            .unwrap();
+        assert_eq!(en1.bit_range.offset, 2);
+        assert_eq!(en1.bit_range.width, 4);
+    }
+
+    #[test]
+    fn reset_value_format() {
+        let dir = test_utils::res_dir().join("add");
+        let patch = super::load_patch_from_str(
+            "DAC1:\n  _add:\n    SR:\n      addressOffset: 0x4\n      resetValue: 0x5\n      resetMask: 0xFF\n",
+            &dir,
+        )
+        .unwrap();
+        let encode = |format_config: &EncoderConfig| {
+            let svd = File::open(dir.join("stm32l4x2.svd")).unwrap();
+            let mut out = String::new();
+            process_reader(svd, &patch, format_config, &Default::default())
+                .unwrap()
+                .read_to_string(&mut out)
+                .unwrap();
+            out
+        };
+        // Reset values are zero-padded by default
+        let out = encode(&EncoderConfig::default());
+        assert!(out.contains("<resetValue>0x00000005</resetValue>"));
+        assert!(out.contains("<resetMask>0x000000FF</resetMask>"));
+        assert!(out.contains("<resetValue>0x00000000</resetValue>"));
+        // unless other format is set with `--format-config`
+        let mut format_config = EncoderConfig::default();
+        format_config.update("register_reset_value", "UpperHex");
+        format_config.update("register_reset_mask", "UpperHex");
+        let out = encode(&format_config);
+        assert!(out.contains("<resetValue>0x5</resetValue>"));
+        assert!(out.contains("<resetMask>0xFF</resetMask>"));
+    }
+
+    #[test]
+    fn base_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml_file = dir.path().join("patch.yaml");
+        std::fs::write(
+            &yaml_file,
+            "_svd: add/stm32l4x2.svd\n\nDAC1:\n  _delete: [CR]\n",
+        )
+        .unwrap();
+        let out_path = dir.path().join("out.svd");
+        assert!(process_file(&yaml_file, Some(&out_path), None, &Default::default()).is_err());
+
+        let config = Config {
+            base_dir: Some(test_utils::res_dir()),
+            ..Default::default()
+        };
+        process_file(&yaml_file, Some(&out_path), None, &config).unwrap();
+        let out = std::fs::read_to_string(&out_path).unwrap();
+        let device = svd_parser::parse(&out).unwrap();
+        assert!(device
+            .get_peripheral("DAC1")
+            .unwrap()
+            .registers
+            .as_ref()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[cfg(feature = "network")]
+    #[test]
+    fn svd_by_url() {
+        use std::io::Write;
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let svd = std::fs::read(test_utils::res_dir().join("add/stm32l4x2.svd")).unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).unwrap();
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                svd.len()
+            )
+            .unwrap();
+            stream.write_all(&svd).unwrap();
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let yaml_file = dir.path().join("patch.yaml");
+        std::fs::write(
+            &yaml_file,
+            format!("_svd: http://127.0.0.1:{port}/stm32l4x2.svd\n\nDAC1:\n  _delete: [CR]\n"),
+        )
+        .unwrap();
+        process_file(&yaml_file, None, None, &Default::default()).unwrap();
+        server.join().unwrap();
+
+        let out = std::fs::read_to_string(dir.path().join("stm32l4x2.svd.patched")).unwrap();
+        let device = svd_parser::parse(&out).unwrap();
+        assert!(device
+            .get_peripheral("DAC1")
+            .unwrap()
+            .registers
+            .as_ref()
+            .unwrap()
+            .is_empty());
+    }
 }
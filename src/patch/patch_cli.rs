@@ -1,6 +1,7 @@
 use super::Config;
-use anyhow::Result;
-use std::path::Path;
+use anyhow::{anyhow, Result};
+use std::{fs::File, io::Read, path::Path};
+use yaml_rust::{Yaml, YamlEmitter};
 
 pub fn patch(
     yaml_file: &Path,
@@ -12,10 +13,55 @@ pub fn patch(
     Ok(())
 }
 
-pub fn expand_patch(yaml_file: &Path) -> Result<String> {
-    let doc = super::load_patch(yaml_file)?;
+pub fn expand_patch(yaml_file: &Path, base_dir: Option<&Path>) -> Result<String> {
+    let doc = super::load_patch_with_base_dir(yaml_file, base_dir)?;
     let mut out_str = String::new();
     let mut emitter = yaml_rust::YamlEmitter::new(&mut out_str);
     emitter.dump(&doc).unwrap();
     Ok(out_str)
 }
+
+/// Write patch fragment which adds peripheral `pname` of SVD file
+pub fn dump_peripheral(svd_file: &Path, pname: &str, out_path: &Path) -> Result<()> {
+    let mut xml = String::new();
+    File::open(svd_file)?.read_to_string(&mut xml)?;
+    let device = svd_parser::parse(&xml)?;
+    std::fs::write(out_path, super::dump::dump_peripheral(&device, pname)?)?;
+    Ok(())
+}
+
+/// Write patch skeleton with `_modify` stubs of all registers and fields of SVD file
+pub fn skeleton(svd_file: &Path, out_path: &Path) -> Result<()> {
+    let mut xml = String::new();
+    File::open(svd_file)?.read_to_string(&mut xml)?;
+    let device = svd_parser::parse(&xml)?;
+    let mut root = yaml_rust::yaml::Hash::new();
+    root.insert(
+        Yaml::String("_svd".into()),
+        Yaml::String(svd_file.to_string_lossy().into_owned()),
+    );
+    root.extend(super::skeleton::skeleton(&device));
+    let mut out = String::new();
+    YamlEmitter::new(&mut out).dump(&Yaml::Hash(root))?;
+    std::fs::write(out_path, out)?;
+    Ok(())
+}
+
+/// Validate SVD file, optionally checking CMSIS-SVD constraints not covered by parser
+pub fn validate(svd_file: &Path, cmsis: bool) -> Result<()> {
+    let mut xml = String::new();
+    File::open(svd_file)?.read_to_string(&mut xml)?;
+    let mut parser_config = svd_parser::Config::default();
+    parser_config.validate_level = svd_rs::ValidateLevel::Strict;
+    let device = svd_parser::parse_with_config(&xml, &parser_config)?;
+    if cmsis {
+        let violations = super::cmsis_lints(&device);
+        for v in &violations {
+            println!("{v}");
+        }
+        if !violations.is_empty() {
+            return Err(anyhow!("{} CMSIS-SVD violations found", violations.len()));
+        }
+    }
+    Ok(())
+}
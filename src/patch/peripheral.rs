@@ -1,10 +1,12 @@
+use std::cmp::Ordering;
+
 use anyhow::{anyhow, Context, Ok};
 use itertools::Itertools;
 use svd::Name;
 use svd_parser::expand::BlockPath;
 use svd_parser::svd::{
-    self, Cluster, ClusterInfo, DimElement, Interrupt, Peripheral, Register, RegisterCluster,
-    RegisterInfo,
+    self, Access, Cluster, ClusterInfo, DimElement, Interrupt, Peripheral, Register,
+    RegisterCluster, RegisterInfo,
 };
 use yaml_rust::{yaml::Hash, Yaml};
 
@@ -12,8 +14,9 @@ use super::iterators::{MatchIter, Matched};
 use super::register::{RegisterExt, RegisterInfoExt};
 use super::yaml_ext::{AsType, GetVal, ToYaml};
 use super::{
-    check_offsets, common_description, make_dim_element, matchname, matchsubspec,
-    modify_dim_element, spec_ind, Config, PatchResult, Spec, VAL_LVL,
+    apply_renames, check_offsets, check_renames, common_description, delete_absent,
+    explicit_dim_index, get_renames, make_dim_element, matchname, matchsubspec, modify_dim_element,
+    parse_access, spec_ind, Config, PatchResult, Rename, Spec, VAL_LVL,
 };
 use super::{make_cluster, make_interrupt, make_register};
 
@@ -37,15 +40,32 @@ pub(crate) trait PeripheralExt: InterruptExt + RegisterBlockExt {
         "_add",
         "_derive",
         "_expand_array",
+        "_split",
         "_expand_cluster",
+        "_expand_all_clusters",
+        "_uncluster",
         "_array",
         "_cluster",
         "_clusters",
         "_interrupts",
+        "_combine_halves",
+        "_alias",
+        "_alternate_group",
+        "_regex",
+        "_rename",
+        "_prefix",
+        "_display_name_template",
+        "_align_address_block",
+        "_access",
+        "_access_recursive",
+        "_sort",
     ];
 
     /// Work through a peripheral, handling all registers
     fn process(&mut self, peripheral: &Hash, config: &Config) -> PatchResult;
+
+    /// Round up sizes of address blocks to multiple of `align`
+    fn align_address_block(&mut self, align: u32) -> PatchResult;
 }
 
 /// Collecting methods for processing cluster contents
@@ -62,10 +82,16 @@ pub(crate) trait ClusterExt: RegisterBlockExt {
         "_add",
         "_derive",
         "_expand_array",
+        "_split",
         "_expand_cluster",
+        "_uncluster",
         "_array",
         "_cluster",
         "_clusters",
+        "_rename",
+        "_access",
+        "_access_recursive",
+        "_sort",
     ];
 
     /// Work through a cluster, handling all registers
@@ -97,7 +123,7 @@ pub(crate) trait InterruptExt {
     ) -> MatchIter<'b, std::slice::IterMut<'a, Interrupt>>;
 
     /// Delete interrupts matched by ispec
-    fn delete_interrupt(&mut self, ispec: &str) -> PatchResult;
+    fn delete_interrupt(&mut self, ispec: &str, config: &Config) -> PatchResult;
 
     /// Add iname given by iadd to ptag
     fn add_interrupt(&mut self, iname: &str, iadd: &Hash) -> PatchResult;
@@ -158,9 +184,14 @@ pub(crate) trait RegisterBlockExt: Name {
     fn add_child(&mut self, child: RegisterCluster);
 
     /// Delete registers and clusters matched by rspec inside ptag
-    fn delete_child(&mut self, rcspec: &str) -> PatchResult {
+    fn delete_child(&mut self, rcspec: &str, config: &Config) -> PatchResult {
+        let (rcspec, ignore) = rcspec.spec();
         if let Some(children) = self.children_mut() {
+            let len = children.len();
             children.retain(|rc| !matchname(rc.name(), rcspec));
+            if children.len() == len && !ignore {
+                return delete_absent("register or cluster", rcspec, config);
+            }
             Ok(())
         } else {
             Err(anyhow!("No registers or clusters"))
@@ -168,35 +199,34 @@ pub(crate) trait RegisterBlockExt: Name {
     }
 
     /// Delete registers matched by rspec inside ptag
-    fn delete_register(&mut self, rspec: &str) -> PatchResult {
+    fn delete_register(&mut self, rspec: &str, config: &Config) -> PatchResult {
+        let (rspec, ignore) = rspec.spec();
         if let Some(children) = self.children_mut() {
+            let len = children.len();
             children.retain(
                 |rc| !matches!(rc, RegisterCluster::Register(r) if matchname(&r.name, rspec)),
             );
+            if children.len() == len && !ignore {
+                return delete_absent("register", rspec, config);
+            }
             Ok(())
         } else {
             Err(anyhow!("No registers or clusters"))
         }
     }
 
-    fn delete_cluster(&mut self, cspec: &str) -> PatchResult {
+    /// Delete clusters matched by cspec inside ptag
+    fn delete_cluster(&mut self, cspec: &str, config: &Config) -> PatchResult {
         let (cspec, ignore) = cspec.spec();
-
         if let Some(children) = self.children_mut() {
-            let mut deleted = false;
-            children.retain(|rc| {
-                let retain =
-                    !matches!(rc, RegisterCluster::Cluster(c) if matchname(&c.name, cspec));
-                if !retain {
-                    deleted = true;
-                }
-                retain
-            });
-            if !deleted && !ignore {
-                Err(anyhow!("No matching clusters found"))
-            } else {
-                Ok(())
+            let len = children.len();
+            children.retain(
+                |rc| !matches!(rc, RegisterCluster::Cluster(c) if matchname(&c.name, cspec)),
+            );
+            if children.len() == len && !ignore {
+                return delete_absent("cluster", cspec, config);
             }
+            Ok(())
         } else {
             Err(anyhow!("No registers or clusters"))
         }
@@ -322,10 +352,69 @@ pub(crate) trait RegisterBlockExt: Name {
         Ok(())
     }
 
-    /// Remove fields from rname and mark it as derivedFrom rderive.
-    /// Update all derivedFrom referencing rname
-    fn derive_cluster(&mut self, _cspec: &str, _cderive: &Yaml, _bpath: &BlockPath) -> PatchResult {
-        todo!()
+    /// Remove children from cname and mark it as derivedFrom cderive.
+    /// Update all derivedFrom referencing cname
+    fn derive_cluster(&mut self, cspec: &str, cderive: &Yaml, bpath: &BlockPath) -> PatchResult {
+        let (cspec, ignore) = cspec.spec();
+        let (cderive, dim, info) = if let Some(cderive) = cderive.as_str() {
+            (
+                cderive,
+                None,
+                ClusterInfo::builder().derived_from(Some(cderive.into())),
+            )
+        } else if let Some(hash) = cderive.as_hash() {
+            let cderive = hash.get_str("_from")?.ok_or_else(|| {
+                anyhow!("derive: source cluster not given, please add a _from field to {cspec}")
+            })?;
+            (
+                cderive,
+                make_dim_element(hash)?,
+                make_cluster(hash, Some(bpath))?.derived_from(Some(cderive.into())),
+            )
+        } else {
+            return Err(anyhow!("derive: incorrect syntax for {cspec}"));
+        };
+
+        // Absolute names can't be verified without reference to the Device
+        if !cderive.contains('.') && !self.clstrs().any(|c| c.name == cderive) {
+            let present = self.present_clusters();
+            return Err(anyhow!(
+                "Could not find `{bpath}:{cderive}. Present clusters: {present}."
+            ));
+        }
+
+        let ctags = self.iter_clusters(cspec).collect::<Vec<_>>();
+        let mut found = Vec::new();
+        if !ctags.is_empty() {
+            for ctag in ctags {
+                found.push(ctag.name.to_string());
+                modify_dim_element(ctag, &dim)?;
+                ctag.children.clear();
+                ctag.modify_from(info.clone(), VAL_LVL)?;
+            }
+        } else if !ignore {
+            super::check_dimable_name(cspec)?;
+            let cluster = info
+                .name(cspec.into())
+                .children(Vec::new())
+                .build(VAL_LVL)?;
+            self.add_child(RegisterCluster::Cluster({
+                if let Some(dim) = dim {
+                    cluster.array(dim.build(VAL_LVL)?)
+                } else {
+                    cluster.single()
+                }
+            }));
+        }
+        for cname in found {
+            for c in self
+                .clstrs_mut()
+                .filter(|c| c.derived_from.as_deref() == Some(&cname))
+            {
+                c.derived_from = Some(cderive.into());
+            }
+        }
+        Ok(())
     }
 
     /// Add rname given by deriving from rcopy to ptag
@@ -360,8 +449,38 @@ pub(crate) trait RegisterBlockExt: Name {
     }
 
     /// Add cname given by deriving from ccopy to ptag
-    fn copy_cluster(&mut self, _rname: &str, _ccopy: &Hash, _bpath: &BlockPath) -> PatchResult {
-        todo!()
+    fn copy_cluster(&mut self, cname: &str, ccopy: &Hash, bpath: &BlockPath) -> PatchResult {
+        let srcname = ccopy.get_str("_from")?.ok_or_else(|| {
+            anyhow!("derive: source cluster not given, please add a _from field to {cname}")
+        })?;
+
+        let mut source = self
+            .clstrs()
+            .find(|c| c.name == srcname)
+            .ok_or_else(|| {
+                let present = self.present_clusters();
+                anyhow!(
+                    "{} {bpath} does not have cluster {srcname}. Present clusters: {present}.`",
+                    Self::RB_TYPE,
+                )
+            })?
+            .clone();
+        let fixes = make_cluster(ccopy, Some(bpath))?.name(cname.into());
+        source.modify_from(fixes, VAL_LVL)?;
+        if let Some(ctag) = self.clstrs_mut().find(|c| c.name == cname) {
+            source.address_offset = ctag.address_offset;
+            *ctag = source;
+        } else {
+            let children = self
+                .children_mut()
+                .ok_or_else(|| anyhow!("No registers or clusters"))?;
+            let pos = children
+                .iter()
+                .position(|rc| rc_offset(rc) > source.address_offset)
+                .unwrap_or(children.len());
+            children.insert(pos, RegisterCluster::Cluster(source));
+        }
+        Ok(())
     }
 
     fn modify_child(&mut self, rcspec: &str, rcmod: &Hash, bpath: &BlockPath) -> PatchResult {
@@ -568,6 +687,71 @@ pub(crate) trait RegisterBlockExt: Name {
         Ok(())
     }
 
+    /// Rename registers and clusters using regex renames,
+    /// updating display names and alternates consistently
+    fn rename(&mut self, renames: &[Rename]) -> PatchResult {
+        let mut used = vec![false; renames.len()];
+        let mut renamed = Vec::new();
+        for rtag in self.regs_mut() {
+            if let Some(i) = apply_renames(renames, &mut rtag.name) {
+                used[i] = true;
+                renamed.push(rtag.name.clone());
+            }
+            if let Some(dname) = rtag.display_name.as_mut() {
+                apply_renames(renames, dname);
+            }
+            if let Some(name) = rtag.alternate_register.as_mut() {
+                apply_renames(renames, name);
+            }
+        }
+        for ctag in self.clstrs_mut() {
+            if let Some(i) = apply_renames(renames, &mut ctag.name) {
+                used[i] = true;
+                renamed.push(ctag.name.clone());
+            }
+            if let Some(name) = ctag.alternate_cluster.as_mut() {
+                apply_renames(renames, name);
+            }
+        }
+        let names = self
+            .regs()
+            .map(|r| r.name.as_str())
+            .chain(self.clstrs().map(|c| c.name.as_str()))
+            .collect::<Vec<_>>();
+        check_renames(renames, &used, &renamed, &names, "registers or clusters")
+    }
+
+    /// Add prefix to names of registers matched by rspec
+    fn add_prefix(&mut self, prefix: &str, rspec: &str) -> PatchResult {
+        let (rspec, ignore) = rspec.spec();
+        let mut found = false;
+        for rtag in self.iter_registers(rspec) {
+            rtag.name.insert_str(0, prefix);
+            if let Some(dname) = rtag.display_name.as_mut() {
+                dname.insert_str(0, prefix);
+            }
+            found = true;
+        }
+        if !found && !ignore {
+            let present = self.present_registers();
+            return Err(anyhow!(
+                "Could not find `{rspec}`. Present registers: {present}."
+            ));
+        }
+        Ok(())
+    }
+
+    /// Set display names of registers from their names using template with `%s`.
+    /// Existing display names are kept unless `force`
+    fn display_name_template(&mut self, template: &str, force: bool) -> PatchResult {
+        for rtag in self.all_regs_mut() {
+            if force || rtag.display_name.is_none() {
+                rtag.display_name = Some(template.replace("%s", &rtag.name));
+            }
+        }
+        Ok(())
+    }
+
     /// Collect same registers in peripheral into register array
     fn collect_in_array(
         &mut self,
@@ -697,12 +881,84 @@ pub(crate) trait RegisterBlockExt: Name {
             return Err(anyhow!("No registers or clusters"));
         };
 
-        self.delete_cluster(cspec)
+        // presence of clusters is checked above
+        self.delete_cluster(cspec, &Config::default())
             .with_context(|| format!("Deleting clusters matched to `{cspec}`"))?;
 
         Ok(())
     }
 
+    /// Expand all clusters of block, including nested ones.
+    /// Single clusters are unclustered
+    fn expand_all_clusters(&mut self, bpath: &BlockPath) -> PatchResult {
+        loop {
+            let cnames = self
+                .clstrs()
+                .map(|c| (c.name.clone(), c.is_array()))
+                .collect::<Vec<_>>();
+            if cnames.is_empty() {
+                return Ok(());
+            }
+            // children of nested clusters become clusters of block on next iteration
+            for (cname, is_array) in cnames {
+                if is_array {
+                    self.expand_cluster(&cname, bpath, None, None, None, None)
+                } else {
+                    self.uncluster(&cname, bpath)
+                }
+                .with_context(|| format!("During expand of `{cname}` cluster"))?;
+            }
+        }
+    }
+
+    /// Move children of single clusters matched by cspec to parent block,
+    /// adding cluster offset to their offsets and cluster name to their names
+    fn uncluster(&mut self, cspec: &str, bpath: &BlockPath) -> PatchResult {
+        let (cspec, ignore) = cspec.spec();
+        let present = self.present_clusters();
+        let Some(children) = self.children_mut() else {
+            return Err(anyhow!("No registers or clusters"));
+        };
+        let mut found = false;
+        let mut i = 0;
+        while i < children.len() {
+            if !matches!(&children[i], RegisterCluster::Cluster(c) if matchname(&c.name, cspec)) {
+                i += 1;
+                continue;
+            }
+            let RegisterCluster::Cluster(c) = children.remove(i) else {
+                unreachable!()
+            };
+            let Cluster::Single(c) = c else {
+                return Err(anyhow!(
+                    "Cluster `{bpath}:{}` is an array. Use `_expand_cluster` instead",
+                    c.name
+                ));
+            };
+            found = true;
+            for mut rc in c.children {
+                match &mut rc {
+                    RegisterCluster::Register(r) => {
+                        r.address_offset += c.address_offset;
+                        r.name = format!("{}_{}", c.name, r.name);
+                    }
+                    RegisterCluster::Cluster(cl) => {
+                        cl.address_offset += c.address_offset;
+                        cl.name = format!("{}_{}", c.name, cl.name);
+                    }
+                }
+                children.insert(i, rc);
+                i += 1;
+            }
+        }
+        if !found && !ignore {
+            return Err(anyhow!(
+                "Could not find `{bpath}:{cspec}. Present clusters: {present}.`"
+            ));
+        }
+        Ok(())
+    }
+
     /// get the prefix to apply to a register name in a cluster that is being expanded
     #[allow(clippy::too_many_arguments)]
     fn expand_cluster_register_name_prefix(
@@ -809,6 +1065,69 @@ pub(crate) trait RegisterBlockExt: Name {
         Ok(prefix)
     }
 
+    /// Split register array into single registers named from `names` list
+    /// or from template with `%s` replaced by index
+    fn split_array(&mut self, rspec: &str, names: &Yaml) -> PatchResult {
+        let (rspec, ignore) = rspec.spec();
+        let Some(regs) = self.children_mut() else {
+            return Err(anyhow!("No registers or clusters"));
+        };
+        // Check names of all matched arrays before changing anything
+        let mut all_names = Vec::new();
+        for rc in regs.iter() {
+            let RegisterCluster::Register(Register::Array(r, d)) = rc else {
+                continue;
+            };
+            if !matchname(&r.name, rspec) {
+                continue;
+            }
+            let names = match names {
+                Yaml::String(template) => d
+                    .indexes()
+                    .map(|idx| template.replace("%s", &idx))
+                    .collect::<Vec<_>>(),
+                Yaml::Array(names) => names
+                    .iter()
+                    .map(|n| n.str().map(String::from))
+                    .collect::<Result<_, _>>()?,
+                _ => {
+                    return Err(anyhow!(
+                        "`_split` requires list of names or template with `%s`"
+                    ))
+                }
+            };
+            if names.len() != d.dim as usize {
+                return Err(anyhow!(
+                    "`{}` has {} elements, but {} names are given",
+                    r.name,
+                    d.dim,
+                    names.len()
+                ));
+            }
+            all_names.push(names);
+        }
+        if all_names.is_empty() {
+            if ignore {
+                return Ok(());
+            }
+            return Err(anyhow!("Register array {rspec} not found"));
+        }
+        let mut all_names = all_names.into_iter();
+        for rc in std::mem::take(regs) {
+            match rc {
+                RegisterCluster::Register(Register::Array(r, d)) if matchname(&r.name, rspec) => {
+                    let names = all_names.next().unwrap();
+                    for (mut ri, name) in svd::register::expand(&r, &d).zip(names) {
+                        ri.name = name;
+                        regs.push(RegisterCluster::Register(ri.single()))
+                    }
+                }
+                rc => regs.push(rc),
+            }
+        }
+        Ok(())
+    }
+
     /// Expand register array
     fn expand_array(&mut self, rspec: &str, _rmod: &Hash, _config: &Config) -> PatchResult {
         if let Some(regs) = self.children_mut() {
@@ -861,13 +1180,289 @@ pub(crate) trait RegisterBlockExt: Name {
         }
         Ok(())
     }
+
+    /// Mark registers matched by aspec as `alternateRegister` of `canon`.
+    /// Aliases must have same fields and reset value as `canon`
+    fn alias_registers(&mut self, canon: &str, aspec: &str) -> PatchResult {
+        let (fields, reset_value) = self
+            .regs()
+            .find(|r| r.name == canon)
+            .map(|r| (r.fields.clone(), r.properties.reset_value))
+            .ok_or_else(|| {
+                let present = self.present_registers();
+                anyhow!("Could not find `{canon}`. Present registers: {present}.")
+            })?;
+        let mut found = false;
+        for rtag in self.regs_mut().matched(aspec) {
+            if rtag.name == canon {
+                continue;
+            }
+            if rtag.fields != fields || rtag.properties.reset_value != reset_value {
+                return Err(anyhow!(
+                    "`{}` has different fields or reset value than `{canon}`",
+                    rtag.name
+                ));
+            }
+            rtag.alternate_register = Some(canon.into());
+            found = true;
+        }
+        if !found {
+            let present = self.present_registers();
+            return Err(anyhow!(
+                "Could not find `{aspec}`. Present registers: {present}."
+            ));
+        }
+        Ok(())
+    }
+
+    /// Set access of child registers or of all descendant registers if `recursive`.
+    /// Fields are not changed
+    fn set_access(&mut self, access: Option<Access>, recursive: bool) {
+        if recursive {
+            for rtag in self.all_regs_mut() {
+                rtag.properties.access = access;
+            }
+        } else {
+            for rtag in self.regs_mut() {
+                rtag.properties.access = access;
+            }
+        }
+    }
+
+    /// Set `alternateGroup` of registers matched by rspec to `group`
+    fn alternate_group(&mut self, group: &str, rspec: &str) -> PatchResult {
+        let (rspec, ignore) = rspec.spec();
+        let rtags = self.iter_registers(rspec).collect::<Vec<_>>();
+        if rtags.is_empty() && !ignore {
+            let present = self.present_registers();
+            return Err(anyhow!(
+                "Could not find `{rspec}`. Present registers: {present}."
+            ));
+        }
+        let info = RegisterInfo::builder().alternate_group(Some(group.into()));
+        for rtag in rtags {
+            rtag.modify_from(info.clone(), VAL_LVL)?;
+        }
+        Ok(())
+    }
+
+    /// Sort children according to `_sort` value, either key for all children
+    /// or hash with separate keys for `_registers` and `_clusters`
+    fn sort(&mut self, sort: &Yaml) -> PatchResult {
+        match sort {
+            Yaml::String(key) => self.sort_children(key, |_| true),
+            Yaml::Hash(h) => {
+                if let Some(key) = h.get_str("_registers")? {
+                    self.sort_children(key, |rc| matches!(rc, RegisterCluster::Register(_)))?;
+                }
+                if let Some(key) = h.get_str("_clusters")? {
+                    self.sort_children(key, |rc| matches!(rc, RegisterCluster::Cluster(_)))?;
+                }
+                Ok(())
+            }
+            _ => Err(anyhow!("`_sort` requires string value or hash")),
+        }
+    }
+
+    /// Stable sort of children passed `filter` by `address`, `name` or `name-natural`.
+    /// Other children keep their places
+    fn sort_children(
+        &mut self,
+        key: &str,
+        filter: impl Fn(&RegisterCluster) -> bool,
+    ) -> PatchResult {
+        let cmp: fn(&RegisterCluster, &RegisterCluster) -> Ordering = match key {
+            "address" => |a, b| rc_offset(a).cmp(&rc_offset(b)),
+            "name" => |a, b| rc_name(a).cmp(rc_name(b)),
+            "name-natural" => |a, b| natural_cmp(rc_name(a), rc_name(b)),
+            _ => return Err(anyhow!("Unknown sort key `{key}`")),
+        };
+        let children = self
+            .children_mut()
+            .ok_or_else(|| anyhow!("No registers or clusters"))?;
+        let slots = (0..children.len())
+            .filter(|&i| filter(&children[i]))
+            .collect::<Vec<_>>();
+        let mut sorted = slots
+            .iter()
+            .map(|&i| children[i].clone())
+            .collect::<Vec<_>>();
+        sorted.sort_by(cmp);
+        for (i, rc) in slots.into_iter().zip(sorted) {
+            children[i] = rc;
+        }
+        Ok(())
+    }
+
+    /// Merge 16-bit low registers matched by pattern with their high partners at offset+2
+    fn combine_halves(&mut self, pattern: &str, partner: &str) -> PatchResult {
+        let (pre, post) = pattern
+            .split_once("%s")
+            .ok_or_else(|| anyhow!("`pattern` must contain `%s`"))?;
+        let regs = self
+            .children_mut()
+            .ok_or_else(|| anyhow!("No registers or clusters"))?;
+        let mut pairs = Vec::new();
+        for (i, rc) in regs.iter().enumerate() {
+            let RegisterCluster::Register(Register::Single(low)) = rc else {
+                continue;
+            };
+            let Some(base) = low
+                .name
+                .strip_prefix(pre)
+                .and_then(|s| s.strip_suffix(post))
+                .filter(|s| !s.is_empty())
+            else {
+                continue;
+            };
+            let hname = partner.replace("%s", base);
+            let hoffset = low.address_offset + 2;
+            if let Some(j) = regs.iter().position(|rc| {
+                matches!(rc, RegisterCluster::Register(Register::Single(r))
+                    if r.name == hname && r.address_offset == hoffset)
+            }) {
+                pairs.push((i, j, base.to_string()));
+            }
+        }
+        if pairs.is_empty() {
+            return Err(anyhow!(
+                "Could not find `{pattern}` registers with `{partner}` partners"
+            ));
+        }
+        let mut partners = Vec::new();
+        for (i, j, base) in pairs {
+            let RegisterCluster::Register(high) = regs[j].clone() else {
+                unreachable!()
+            };
+            let RegisterCluster::Register(low) = &mut regs[i] else {
+                unreachable!()
+            };
+            low.name = base;
+            low.display_name = None;
+            low.properties.size = Some(32);
+            low.properties.reset_value = low
+                .properties
+                .reset_value
+                .zip(high.properties.reset_value)
+                .map(|(l, h)| l | (h << 16));
+            low.properties.reset_mask = low
+                .properties
+                .reset_mask
+                .zip(high.properties.reset_mask)
+                .map(|(l, h)| l | (h << 16));
+            if let Some(hfields) = high.fields.as_ref() {
+                let fields = low.fields.get_or_insert_with(Default::default);
+                for f in hfields {
+                    let mut f = f.clone();
+                    f.bit_range.offset += 16;
+                    fields.push(f);
+                }
+            }
+            partners.push(j);
+        }
+        partners.sort_unstable();
+        for j in partners.into_iter().rev() {
+            regs.remove(j);
+        }
+        Ok(())
+    }
+}
+
+/// Pairs of `(canonical, alias)` registers which have same size, fields and reset value,
+/// but different offsets. Canonical register is the one with lowest offset
+pub(crate) fn alias_candidates(children: &[RegisterCluster]) -> Vec<(&str, &str)> {
+    let mut regs = children
+        .iter()
+        .filter_map(|rc| match rc {
+            RegisterCluster::Register(Register::Single(r))
+                if r.alternate_register.is_none()
+                    && r.fields.as_ref().is_some_and(|f| !f.is_empty()) =>
+            {
+                Some(r)
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    regs.sort_by_key(|r| r.address_offset);
+    let mut candidates = Vec::new();
+    for (i, alias) in regs.iter().enumerate() {
+        if let Some(canon) = regs[..i].iter().find(|r| {
+            r.address_offset != alias.address_offset
+                && r.properties.size == alias.properties.size
+                && r.properties.reset_value == alias.properties.reset_value
+                && r.fields == alias.fields
+        }) {
+            candidates.push((canon.name.as_str(), alias.name.as_str()));
+        }
+    }
+    candidates
+}
+
+fn rc_offset(rc: &RegisterCluster) -> u32 {
+    match rc {
+        RegisterCluster::Register(r) => r.address_offset,
+        RegisterCluster::Cluster(c) => c.address_offset,
+    }
+}
+
+fn rc_name(rc: &RegisterCluster) -> &str {
+    match rc {
+        RegisterCluster::Register(r) => &r.name,
+        RegisterCluster::Cluster(c) => &c.name,
+    }
+}
+
+/// Compare names so that numbers inside them are compared by value, e.g. `CH2 < CH10`
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    fn chunks(s: &str) -> Vec<&str> {
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        for (i, c) in s.char_indices().skip(1) {
+            let prev = s[..i].chars().next_back().unwrap();
+            if prev.is_ascii_digit() != c.is_ascii_digit() {
+                chunks.push(&s[start..i]);
+                start = i;
+            }
+        }
+        chunks.push(&s[start..]);
+        chunks
+    }
+    for (ca, cb) in chunks(a).into_iter().zip(chunks(b)) {
+        let ord = if ca.starts_with(|c: char| c.is_ascii_digit())
+            && cb.starts_with(|c: char| c.is_ascii_digit())
+        {
+            let (ta, tb) = (ca.trim_start_matches('0'), cb.trim_start_matches('0'));
+            ta.len().cmp(&tb.len()).then_with(|| ta.cmp(tb))
+        } else {
+            ca.cmp(cb)
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
 }
 
 fn modify_register(rtags: Vec<&mut Register>, rmod: &Hash, bpath: &BlockPath) -> PatchResult {
     let register_builder = make_register(rmod, Some(bpath))?;
     let dim = make_dim_element(rmod)?;
+    let indexes = rmod
+        .get_yaml("_reindex")
+        .map(|_| {
+            rmod.str_vec_iter("_reindex")
+                .map(|it| it.map(String::from).collect::<Vec<_>>())
+        })
+        .transpose()?;
     for rtag in rtags {
         modify_dim_element(rtag, &dim)?;
+        if let Some(indexes) = indexes.as_ref() {
+            match rtag {
+                Register::Array(info, dim) => reindex(&mut info.name, dim, indexes)?,
+                Register::Single(info) => {
+                    return Err(anyhow!("Can't reindex `{}`. It is not an array", info.name))
+                }
+            }
+        }
         rtag.modify_from(register_builder.clone(), VAL_LVL)?;
         if let Some("") = rmod.get_str("access")? {
             rtag.properties.access = None;
@@ -879,13 +1474,43 @@ fn modify_register(rtags: Vec<&mut Register>, rmod: &Hash, bpath: &BlockPath) ->
 fn modify_cluster(ctags: Vec<&mut Cluster>, cmod: &Hash, bpath: &BlockPath) -> PatchResult {
     let cluster_builder = make_cluster(cmod, Some(bpath))?;
     let dim = make_dim_element(cmod)?;
+    let indexes = cmod
+        .get_yaml("_reindex")
+        .map(|_| {
+            cmod.str_vec_iter("_reindex")
+                .map(|it| it.map(String::from).collect::<Vec<_>>())
+        })
+        .transpose()?;
     for ctag in ctags {
         modify_dim_element(ctag, &dim)?;
+        if let Some(indexes) = indexes.as_ref() {
+            match ctag {
+                Cluster::Array(info, dim) => reindex(&mut info.name, dim, indexes)?,
+                Cluster::Single(info) => {
+                    return Err(anyhow!("Can't reindex `{}`. It is not an array", info.name))
+                }
+            }
+        }
         ctag.modify_from(cluster_builder.clone(), VAL_LVL)?;
     }
     Ok(())
 }
 
+/// Replace `dimIndex` of array. As indexes may be not numeric anymore,
+/// `[%s]` in name is changed to `%s`
+fn reindex(name: &mut String, dim: &mut DimElement, indexes: &[String]) -> PatchResult {
+    if indexes.len() != dim.dim as usize {
+        return Err(anyhow!(
+            "`{name}` has {} elements, but {} indexes were given",
+            dim.dim,
+            indexes.len()
+        ));
+    }
+    *name = name.replace("[%s]", "%s");
+    dim.dim_index = Some(indexes.to_vec());
+    Ok(())
+}
+
 impl RegisterBlockExt for Peripheral {
     const RB_TYPE: &'static str = "peripheral";
 
@@ -959,7 +1584,7 @@ impl PeripheralExt for Peripheral {
         if self.derived_from.is_some() {
             if let Some(deletions) = pmod.get_hash("_delete").ok().flatten() {
                 for ispec in deletions.str_vec_iter("_interrupts")? {
-                    self.delete_interrupt(ispec)
+                    self.delete_interrupt(ispec, config)
                         .with_context(|| format!("Deleting interrupts matched to `{ispec}`"))?;
                 }
             }
@@ -992,34 +1617,40 @@ impl PeripheralExt for Peripheral {
         }
 
         let ppath = BlockPath::new(&self.name);
+        let mut config = config.clone();
+        config.register_size = self
+            .default_register_properties
+            .size
+            .or(config.register_size);
+        let config = &config;
 
         // Handle deletions
         if let Some(deletions) = pmod.get_yaml("_delete") {
             match deletions {
                 Yaml::String(rcspec) => {
-                    self.delete_child(rcspec).with_context(|| {
+                    self.delete_child(rcspec, config).with_context(|| {
                         format!("Deleting registers and clusters matched to `{rcspec}`")
                     })?;
                 }
                 Yaml::Array(deletions) => {
                     for rcspec in deletions {
                         let rcspec = rcspec.str()?;
-                        self.delete_child(rcspec).with_context(|| {
+                        self.delete_child(rcspec, config).with_context(|| {
                             format!("Deleting registers and clusters matched to `{rcspec}`")
                         })?;
                     }
                 }
                 Yaml::Hash(deletions) => {
                     for rspec in deletions.str_vec_iter("_registers")? {
-                        self.delete_register(rspec)
+                        self.delete_register(rspec, config)
                             .with_context(|| format!("Deleting registers matched to `{rspec}`"))?;
                     }
                     for cspec in deletions.str_vec_iter("_clusters")? {
-                        self.delete_cluster(cspec)
+                        self.delete_cluster(cspec, config)
                             .with_context(|| format!("Deleting clusters matched to `{cspec}`"))?;
                     }
                     for ispec in deletions.str_vec_iter("_interrupts")? {
-                        self.delete_interrupt(ispec)
+                        self.delete_interrupt(ispec, config)
                             .with_context(|| format!("Deleting interrupts matched to `{ispec}`"))?;
                     }
                     for d in deletions.keys() {
@@ -1039,6 +1670,12 @@ impl PeripheralExt for Peripheral {
             }
         }
 
+        // Set access of all registers before child commands, so they can override it
+        if let Some(access) = pmod.get_str("_access")? {
+            let recursive = pmod.get_bool("_access_recursive")? == Some(true);
+            self.set_access(parse_access(access)?, recursive);
+        }
+
         // Handle any copied peripherals
         for (rname, rcopy) in pmod.hash_iter("_copy") {
             let rname = rname.str()?;
@@ -1056,7 +1693,7 @@ impl PeripheralExt for Peripheral {
                     for (cname, val) in rcopy.hash()? {
                         let cname = cname.str()?;
                         let ccopy = val.hash()?;
-                        self.copy_cluster(rname, ccopy, &ppath)
+                        self.copy_cluster(cname, ccopy, &ppath)
                             .with_context(|| format!("Copying cluster `{cname}` from `{val:?}`"))?;
                     }
                 }
@@ -1176,6 +1813,77 @@ impl PeripheralExt for Peripheral {
             }
         }
 
+        // Handle prefixes of matched registers
+        for (prefix, rspec) in pmod.hash_iter("_prefix") {
+            let prefix = prefix.str()?;
+            let rspec = rspec.str()?;
+            self.add_prefix(prefix, rspec).with_context(|| {
+                format!("Adding prefix `{prefix}` to registers matched to `{rspec}`")
+            })?;
+        }
+
+        // Handle display names
+        match pmod.get_yaml("_display_name_template") {
+            Some(Yaml::String(template)) => self.display_name_template(template, false)?,
+            Some(Yaml::Hash(h)) => {
+                let template = h
+                    .get_str("template")?
+                    .ok_or_else(|| anyhow!("`_display_name_template` requires `template`"))?;
+                let force = h.get_bool("_force")?.unwrap_or(false);
+                self.display_name_template(template, force)?;
+            }
+            Some(_) => {
+                return Err(anyhow!(
+                    "`_display_name_template` requires string value or hash"
+                ))
+            }
+            None => {}
+        }
+
+        // Handle regex renames of registers and clusters
+        for key in ["_regex", "_rename"] {
+            let renames = get_renames(pmod, key)?;
+            if !renames.is_empty() {
+                self.rename(&renames)
+                    .context("Renaming registers and clusters")?;
+            }
+        }
+
+        // Merge 16-bit register halves
+        if let Some(combine) = pmod.get_hash("_combine_halves")? {
+            let pattern = combine.get_str("pattern")?.unwrap_or("%sL");
+            let partner = combine.get_str("partner")?.unwrap_or("%sH");
+            self.combine_halves(pattern, partner).with_context(|| {
+                format!("Combining `{pattern}` registers with `{partner}` partners")
+            })?;
+        }
+
+        // Mark mirror registers as alternates of canonical ones
+        if let Some(aliases) = pmod.get_hash("_alias")? {
+            for canon in aliases.keys() {
+                let canon = canon.str()?;
+                for aspec in aliases.str_vec_iter(canon)? {
+                    self.alias_registers(canon, aspec).with_context(|| {
+                        format!("Aliasing registers matched to `{aspec}` to `{canon}`")
+                    })?;
+                }
+            }
+        }
+
+        // Assign alternate groups to overlapping registers
+        if let Some(groups) = pmod.get_hash("_alternate_group")? {
+            for group in groups.keys() {
+                let group = group.str()?;
+                for rspec in groups.str_vec_iter(group)? {
+                    self.alternate_group(group, rspec).with_context(|| {
+                        format!(
+                            "Setting alternateGroup `{group}` of registers matched to `{rspec}`"
+                        )
+                    })?;
+                }
+            }
+        }
+
         // Handle registers or clusters
         for (rcspec, rcmod) in pmod {
             let rcspec = rcspec.str()?;
@@ -1192,6 +1900,12 @@ impl PeripheralExt for Peripheral {
             self.expand_array(rspec, rmod.hash()?, config)
                 .with_context(|| format!("During expand of `{rspec}` array"))?;
         }
+        // Split register arrays into named registers
+        for (rspec, names) in pmod.hash_iter("_split") {
+            let rspec = rspec.str()?;
+            self.split_array(rspec, names)
+                .with_context(|| format!("Splitting `{rspec}` array"))?;
+        }
         // Collect registers in arrays
         for (rspec, rmod) in pmod.hash_iter("_array") {
             let rspec = rspec.str()?;
@@ -1264,6 +1978,54 @@ impl PeripheralExt for Peripheral {
             }
         }
 
+        if pmod.get_bool("_expand_all_clusters")? == Some(true) {
+            self.expand_all_clusters(&ppath)
+                .with_context(|| "During expand of all clusters")?;
+        }
+
+        // Move cluster contents to peripheral
+        for cspec in pmod.str_vec_iter("_uncluster")? {
+            self.uncluster(cspec, &ppath)
+                .with_context(|| format!("Unclustering clusters matched to `{cspec}`"))?;
+        }
+
+        // Sort registers and clusters
+        if let Some(sort) = pmod.get_yaml("_sort") {
+            self.sort(sort).context("Sorting registers and clusters")?;
+        }
+
+        if let Some(align) = pmod.get_u32("_align_address_block")? {
+            self.align_address_block(align)
+                .with_context(|| format!("Aligning address block to {align:#x}"))?;
+        }
+
+        Ok(())
+    }
+
+    fn align_address_block(&mut self, align: u32) -> PatchResult {
+        if align == 0 {
+            return Err(anyhow!("alignment must be positive"));
+        }
+        let Some(blocks) = self.address_block.as_mut().filter(|ab| !ab.is_empty()) else {
+            return Err(anyhow!("peripheral {} has no address block", self.name));
+        };
+        let sizes = blocks
+            .iter()
+            .map(|ab| {
+                ab.size
+                    .checked_add(align - 1)
+                    .map(|s| s / align * align)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "address block size {:#x} can't be aligned to {align:#x}",
+                            ab.size
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for (ab, size) in blocks.iter_mut().zip(sizes) {
+            ab.size = size;
+        }
         Ok(())
     }
 }
@@ -1283,49 +2045,57 @@ impl InterruptExt for Peripheral {
                 self.name
             ));
         }
-        self.interrupt
-            .push(make_interrupt(iadd)?.name(iname.into()).build(VAL_LVL)?);
+        self.interrupt.push(
+            make_interrupt(iadd, iname)?
+                .name(iname.into())
+                .build(VAL_LVL)?,
+        );
         Ok(())
     }
 
     fn modify_interrupt(&mut self, ispec: &str, imod: &Hash) -> PatchResult {
         for itag in self.iter_interrupts(ispec) {
-            itag.modify_from(make_interrupt(imod)?, VAL_LVL)?;
+            itag.modify_from(make_interrupt(imod, &itag.name)?, VAL_LVL)?;
         }
         Ok(())
     }
 
-    fn delete_interrupt(&mut self, ispec: &str) -> PatchResult {
+    fn delete_interrupt(&mut self, ispec: &str, config: &Config) -> PatchResult {
+        let (ispec, ignore) = ispec.spec();
+        let len = self.interrupt.len();
         self.interrupt.retain(|i| !(matchname(&i.name, ispec)));
+        if self.interrupt.len() == len && !ignore {
+            return delete_absent("interrupt", ispec, config);
+        }
         Ok(())
     }
 }
 
 impl ClusterExt for Cluster {
-    fn pre_process(&mut self, cmod: &Hash, parent: &BlockPath, _config: &Config) -> PatchResult {
+    fn pre_process(&mut self, cmod: &Hash, parent: &BlockPath, config: &Config) -> PatchResult {
         // Handle deletions
         if let Some(deletions) = cmod.get_yaml("_delete") {
             match deletions {
                 Yaml::String(rcspec) => {
-                    self.delete_child(rcspec).with_context(|| {
+                    self.delete_child(rcspec, config).with_context(|| {
                         format!("Deleting registers and clusters matched to `{rcspec}`")
                     })?;
                 }
                 Yaml::Array(deletions) => {
                     for rcspec in deletions {
                         let rcspec = rcspec.str()?;
-                        self.delete_child(rcspec).with_context(|| {
+                        self.delete_child(rcspec, config).with_context(|| {
                             format!("Deleting registers and clusters matched to `{rcspec}`")
                         })?;
                     }
                 }
                 Yaml::Hash(deletions) => {
                     for rspec in deletions.str_vec_iter("_registers")? {
-                        self.delete_register(rspec)
+                        self.delete_register(rspec, config)
                             .with_context(|| format!("Deleting registers matched to `{rspec}`"))?;
                     }
                     for cspec in deletions.str_vec_iter("_clusters")? {
-                        self.delete_cluster(cspec)
+                        self.delete_cluster(cspec, config)
                             .with_context(|| format!("Deleting clusters matched to `{cspec}`"))?;
                     }
                     for d in deletions.keys() {
@@ -1347,6 +2117,12 @@ impl ClusterExt for Cluster {
 
         let cpath = parent.new_cluster(&self.name);
 
+        // Set access of all registers before child commands, so they can override it
+        if let Some(access) = cmod.get_str("_access")? {
+            let recursive = cmod.get_bool("_access_recursive")? == Some(true);
+            self.set_access(parse_access(access)?, recursive);
+        }
+
         // Handle any copied peripherals
         for (rname, rcopy) in cmod.hash_iter("_copy") {
             let rname = rname.str()?;
@@ -1364,7 +2140,7 @@ impl ClusterExt for Cluster {
                     for (cname, val) in rcopy.hash()? {
                         let cname = cname.str()?;
                         let ccopy = val.hash()?;
-                        self.copy_cluster(rname, ccopy, &cpath)
+                        self.copy_cluster(cname, ccopy, &cpath)
                             .with_context(|| format!("Copying cluster `{cname}` from `{val:?}`"))?;
                     }
                 }
@@ -1441,6 +2217,13 @@ impl ClusterExt for Cluster {
             }
         }
 
+        // Handle regex renames of registers and clusters
+        let renames = get_renames(cmod, "_rename")?;
+        if !renames.is_empty() {
+            self.rename(&renames)
+                .context("Renaming registers and clusters")?;
+        }
+
         for (rspec, rderive) in cmod.hash_iter("_derive") {
             let rspec = rspec.str()?;
             match rspec {
@@ -1473,6 +2256,13 @@ impl ClusterExt for Cluster {
     }
 
     fn process(&mut self, cmod: &Hash, parent: &BlockPath, config: &Config) -> PatchResult {
+        let mut config = config.clone();
+        config.register_size = self
+            .default_register_properties
+            .size
+            .or(config.register_size);
+        let config = &config;
+
         self.pre_process(cmod, parent, config)?;
 
         let cpath = parent.new_cluster(&self.name);
@@ -1506,6 +2296,12 @@ impl ClusterExt for Cluster {
             self.expand_array(rspec, rmod.hash()?, config)
                 .with_context(|| format!("During expand of `{rspec}` array"))?;
         }
+        // Split register arrays into named registers
+        for (rspec, names) in cmod.hash_iter("_split") {
+            let rspec = rspec.str()?;
+            self.split_array(rspec, names)
+                .with_context(|| format!("Splitting `{rspec}` array"))?;
+        }
 
         // Collect registers in arrays
         for (rspec, rmod) in cmod.hash_iter("_array") {
@@ -1521,6 +2317,17 @@ impl ClusterExt for Cluster {
                 .with_context(|| format!("Collecting registers in cluster `{cname}`"))?;
         }
 
+        // Move nested cluster contents to this cluster
+        for cspec in cmod.str_vec_iter("_uncluster")? {
+            self.uncluster(cspec, &cpath)
+                .with_context(|| format!("Unclustering clusters matched to `{cspec}`"))?;
+        }
+
+        // Sort registers and clusters
+        if let Some(sort) = cmod.get_yaml("_sort") {
+            self.sort(sort).context("Sorting registers and clusters")?;
+        }
+
         Ok(())
     }
 }
@@ -1567,46 +2374,102 @@ fn collect_in_array(
             "`{rspec}` contains no tokens or contains more than one token"
         ));
     };
-    let dim = registers.len();
-    let dim_index = if rmod.contains_key(&"_start_from_zero".to_yaml()) {
-        (0..dim).map(|v| v.to_string()).collect::<Vec<_>>()
-    } else {
-        registers
-            .iter()
-            .map(|r| r.name[li..r.name.len() - ri].to_string())
-            .collect::<Vec<_>>()
-    };
+    let start_from_zero = rmod.contains_key(&"_start_from_zero".to_yaml());
     let offsets = registers
         .iter()
         .map(|r| r.address_offset)
         .collect::<Vec<_>>();
-    let dim_increment = if dim > 1 {
-        offsets[1] - offsets[0]
+    let (dim, dim_increment, dim_index, reg_index) = if rmod.get_bool("_allow_gaps")? == Some(true)
+    {
+        let dim_increment = rmod
+            .get_u32("dimIncrement")?
+            .filter(|&inc| inc != 0)
+            .ok_or_else(|| anyhow!("`_allow_gaps` requires dimIncrement"))?;
+        if let Some(r) = registers
+            .iter()
+            .find(|r| (r.address_offset - offsets[0]) % dim_increment != 0)
+        {
+            return Err(anyhow!(
+                "{path}: registers cannot be collected into {rspec} array. Offset of {} ({:#x}) is not multiple of dimIncrement {dim_increment:#x} from {} ({:#x})",
+                r.name,
+                r.address_offset,
+                registers[0].name,
+                offsets[0],
+            ));
+        }
+        let dim = ((offsets[offsets.len() - 1] - offsets[0]) / dim_increment + 1) as usize;
+        let start = if start_from_zero {
+            0
+        } else {
+            let name = &registers[0].name;
+            name[li..name.len() - ri].parse::<usize>().map_err(|_| {
+                anyhow!("`_allow_gaps` requires numeric indexes or `_start_from_zero`")
+            })?
+        };
+        let dim_index = (start..start + dim)
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>();
+        let reg_index = offsets
+            .iter()
+            .map(|o| dim_index[((o - offsets[0]) / dim_increment) as usize].clone())
+            .collect::<Vec<_>>();
+        if !start_from_zero {
+            if let Some((r, idx)) = registers
+                .iter()
+                .zip(&reg_index)
+                .find(|(r, idx)| &r.name[li..r.name.len() - ri] != idx.as_str())
+            {
+                return Err(anyhow!(
+                    "{path}: registers cannot be collected into {rspec} array. Index of {} does not match its offset, expected {idx}",
+                    r.name
+                ));
+            }
+        }
+        (dim, dim_increment, dim_index, reg_index)
     } else {
-        rmod.get_u32("dimIncrement")?
-            .or_else(|| registers[0].properties.size.map(|s| s / 8))
-            .unwrap_or_default()
+        let dim = registers.len();
+        let dim_index = if start_from_zero {
+            (0..dim).map(|v| v.to_string()).collect::<Vec<_>>()
+        } else {
+            registers
+                .iter()
+                .map(|r| r.name[li..r.name.len() - ri].to_string())
+                .collect::<Vec<_>>()
+        };
+        let dim_increment = if dim > 1 {
+            offsets[1] - offsets[0]
+        } else {
+            rmod.get_u32("dimIncrement")?
+                .or_else(|| registers[0].properties.size.map(|s| s / 8))
+                .unwrap_or_default()
+        };
+        if dim_increment == 0 {
+            return Err(anyhow!("Need to specify dimIncrement"));
+        }
+        if !check_offsets(&offsets, dim_increment) {
+            return Err(anyhow!("{path}: registers cannot be collected into {rspec} array. Different addressOffset increments"));
+        }
+        (dim, dim_increment, dim_index.clone(), dim_index)
     };
-    if dim_increment == 0 {
-        return Err(anyhow!("Need to specify dimIncrement"));
-    }
-    if !check_offsets(&offsets, dim_increment) {
-        return Err(anyhow!("{path}: registers cannot be collected into {rspec} array. Different addressOffset increments"));
-    }
     let bitmasks = registers
         .iter()
         .map(RegisterInfo::get_bitmask)
         .collect::<Vec<_>>();
-    if !bitmasks.iter().all(|&m| m == bitmasks[0]) {
+    if let Some(i) = bitmasks.iter().position(|&m| m != bitmasks[0]) {
         return Err(anyhow!(
-            "{path}: registers cannot be collected into {rspec} array. Different bit masks"
+            "{path}: registers cannot be collected into {rspec} array. Bit mask of {} ({:#x}) differs from {} ({:#x})",
+            registers[i].name,
+            bitmasks[i],
+            registers[0].name,
+            bitmasks[0],
         ));
     }
 
     registers[0].name = if let Some(name) = rmod.get_str("name")? {
         name.into()
     } else {
-        format!("{}%s{}", &rspec[..li], &rspec[rspec.len() - ri..])
+        let name = &registers[0].name;
+        format!("{}%s{}", &name[..li], &name[name.len() - ri..])
     };
 
     if let Some(desc) = rmod.get_str("description")? {
@@ -1615,7 +2478,7 @@ fn collect_in_array(
         }
     } else {
         let descs: Vec<_> = registers.iter().map(|r| r.description.as_deref()).collect();
-        registers[0].description = common_description(&descs, &dim_index).ok_or_else(||
+        registers[0].description = common_description(&descs, &reg_index).ok_or_else(||
             anyhow!("{path}: registers cannot be collected into {rspec} array. Please, specify description")
         )?;
     }
@@ -1628,7 +2491,7 @@ fn collect_in_array(
             .iter()
             .map(|r| r.display_name.as_deref())
             .collect();
-        registers[0].display_name = common_description(&names, &dim_index).ok_or_else(||
+        registers[0].display_name = common_description(&names, &reg_index).ok_or_else(||
             anyhow!("{path}: registers cannot be collected into {rspec} array. Please, specify displayName")
         )?;
     }
@@ -1637,7 +2500,7 @@ fn collect_in_array(
         DimElement::builder()
             .dim(dim as u32)
             .dim_increment(dim_increment)
-            .dim_index(Some(dim_index))
+            .dim_index(explicit_dim_index(dim_index))
             .build(VAL_LVL)?,
     );
     let mut config = config.clone();
@@ -1664,10 +2527,13 @@ fn collect_in_cluster(
     let mut place = usize::MAX;
     let mut rspecs = Vec::new();
     let single = !cname.contains("%s");
+    let keep_source = cmod.get_bool("_keep_source")?.unwrap_or(false);
 
     for (rspec, rmod) in cmod {
         let rspec = rspec.str()?;
-        if ["description", "dimIncrement"].contains(&rspec) || Cluster::KEYWORDS.contains(&rspec) {
+        if ["description", "dimIncrement", "_keep_source"].contains(&rspec)
+            || Cluster::KEYWORDS.contains(&rspec)
+        {
             continue;
         }
         let mut registers = Vec::new();
@@ -1676,9 +2542,12 @@ fn collect_in_cluster(
         while i < regs.len() {
             match &regs[i] {
                 RegisterCluster::Register(r) if matchname(&r.name, rspec) => {
-                    if let RegisterCluster::Register(r) = regs.remove(i) {
+                    place = place.min(i);
+                    if keep_source {
+                        registers.push(r.clone());
+                        i += 1;
+                    } else if let RegisterCluster::Register(r) = regs.remove(i) {
                         registers.push(r);
-                        place = place.min(i);
                     }
                 }
                 _ => i += 1,
@@ -1765,9 +2634,13 @@ fn collect_in_cluster(
                     "{path}: registers cannot be collected into {cname} cluster. Different addressOffset increments in {rspec} registers"
                 ));
             }
-            if !bitmasks.iter().all(|&m| m == bitmasks[0]) {
+            if let Some(i) = bitmasks.iter().position(|&m| m != bitmasks[0]) {
                 return Err(anyhow!(
-                    "{path}: registers cannot be collected into {cname} cluster. Different bit masks in {rspec} registers"
+                    "{path}: registers cannot be collected into {cname} cluster. Bit mask of {} ({:#x}) differs from {} ({:#x})",
+                    registers[i].name,
+                    bitmasks[i],
+                    registers[0].name,
+                    bitmasks[0],
                 ));
             }
         }
@@ -1824,7 +2697,7 @@ fn collect_in_cluster(
                         "`{rspec}` contains no tokens or contains more than one token"
                     ));
                 };
-                format!("{}{}", &rspec[..li], &rspec[rspec.len() - ri..])
+                format!("{}{}", &reg.name[..li], &reg.name[reg.name.len() - ri..])
             };
             if let Some(desc) = rmod.get_str("description")? {
                 reg.description = Some(desc.into());
@@ -1852,9 +2725,12 @@ fn collect_in_cluster(
 
 #[cfg(test)]
 mod tests {
+    use crate::patch::device::DeviceExt;
     use crate::test_utils;
     use anyhow::Result;
     use std::path::Path;
+    use svd_parser::svd::{Access, Device, Register, RegisterCluster};
+    use yaml_rust::YamlLoader;
 
     #[test]
     fn cluster() -> Result<()> {
@@ -1865,4 +2741,620 @@ mod tests {
     fn cross_cluster_derive() -> Result<()> {
         test_utils::test_expected(Path::new("cross_cluster_derive"))
     }
+
+    #[test]
+    fn copy_cluster() -> Result<()> {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("copy_cluster"))?;
+        device.process(&yaml, &Default::default())?;
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let ch1 = dac1.get_cluster("CH1").unwrap();
+        let ch0 = dac1.get_cluster("CH0").unwrap();
+        assert_eq!(ch0.address_offset, 0x8);
+        assert_eq!(ch0.description.as_deref(), Some("channel 0"));
+        assert_eq!(ch0.children, ch1.children);
+        let names = dac1
+            .registers
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|rc| match rc {
+                RegisterCluster::Register(r) => r.name.as_str(),
+                RegisterCluster::Cluster(c) => c.name.as_str(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["CR", "CH0", "CH1"]);
+        Ok(())
+    }
+
+    #[test]
+    fn derive_cluster() -> Result<()> {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("derive_cluster"))?;
+        device.process(&yaml, &Default::default())?;
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let cluster = |name| dac1.clusters().find(|c| c.name == name).unwrap();
+        let ch2 = cluster("CH2");
+        assert_eq!(ch2.derived_from.as_deref(), Some("CH1"));
+        assert!(ch2.children.is_empty());
+        assert_eq!(cluster("CH3").derived_from.as_deref(), Some("CH1"));
+        let ch4 = cluster("CH4");
+        assert_eq!(ch4.derived_from.as_deref(), Some("CH1"));
+        assert_eq!(ch4.address_offset, 0x28);
+        assert!(dac1.clusters().all(|c| c.name != "CH9"));
+        Ok(())
+    }
+
+    #[test]
+    fn combine_halves() -> Result<()> {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("combine_halves"))?;
+        device.process(&yaml, &Default::default())?;
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        assert!(dac1.get_register("CNTL").is_none());
+        assert!(dac1.get_register("CNTH").is_none());
+        let cnt = dac1.get_register("CNT").unwrap();
+        assert_eq!(cnt.address_offset, 0x10);
+        assert_eq!(cnt.properties.size, Some(32));
+        assert_eq!(cnt.properties.reset_value, Some(0x0012_0034));
+        assert_eq!(cnt.get_field("LOW").unwrap().bit_offset(), 0);
+        assert_eq!(cnt.get_field("HIGH").unwrap().bit_offset(), 16);
+        Ok(())
+    }
+
+    #[test]
+    fn align_address_block() -> Result<()> {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("align_address_block"))?;
+        device.process(&yaml, &Default::default())?;
+        let tim6 = device.get_peripheral("TIM6").unwrap();
+        assert_eq!(tim6.address_block.as_ref().unwrap()[0].size, 0x800);
+
+        let mut tim6 = tim6.clone();
+        tim6.address_block.as_mut().unwrap()[0].size = 0xFFFF_FF01;
+        let err = super::PeripheralExt::align_address_block(&mut tim6, 0x100).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "address block size 0xffffff01 can't be aligned to 0x100"
+        );
+        assert_eq!(tim6.address_block.as_ref().unwrap()[0].size, 0xFFFF_FF01);
+        Ok(())
+    }
+
+    #[test]
+    fn sort() -> Result<()> {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("sort"))?;
+        device.process(&yaml, &Default::default())?;
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let offsets = dac1
+            .registers()
+            .map(|r| r.address_offset)
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, [0x0, 0x10, 0x14, 0x28]);
+        let cr = dac1.get_register("CR").unwrap();
+        let fields = cr.fields().map(|f| f.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(fields, ["EN1", "BOFF1", "TEN1"]);
+
+        let patch = YamlLoader::load_from_str("DAC1: {_sort: {_registers: name}}")?;
+        device.process(patch[0].as_hash().unwrap(), &Default::default())?;
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let names = dac1
+            .registers()
+            .map(|r| r.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["CR", "DHR1", "DHR10", "DHR2"]);
+
+        let patch = YamlLoader::load_from_str("DAC1: {_sort: name-natural}")?;
+        device.process(patch[0].as_hash().unwrap(), &Default::default())?;
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let offsets = dac1
+            .registers()
+            .map(|r| r.address_offset)
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, [0x0, 0x10, 0x14, 0x28]);
+        Ok(())
+    }
+
+    #[test]
+    fn array_bitmask_mismatch() -> Result<()> {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("array_bitmask"))?;
+        let err = device.process(&yaml, &Default::default()).unwrap_err();
+        assert_eq!(
+            err.root_cause().to_string(),
+            "DAC1: registers cannot be collected into DHR* array. Bit mask of DHR2 (0xfff) differs from DHR1 (0xff)"
+        );
+
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("cluster_bitmask"))?;
+        let err = device.process(&yaml, &Default::default()).unwrap_err();
+        assert_eq!(
+            err.root_cause().to_string(),
+            "DAC1: registers cannot be collected into CH%s cluster. Bit mask of DHR2 (0xfff) differs from DHR1 (0xff)"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn alias() -> Result<()> {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("alias"))?;
+        device.process(&yaml, &Default::default())?;
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let mirror = dac1.get_register("CR_MIRROR").unwrap();
+        assert_eq!(mirror.address_offset, 0x100);
+        assert_eq!(mirror.alternate_register.as_deref(), Some("CR"));
+        assert_eq!(dac1.get_register("CR").unwrap().alternate_register, None);
+        Ok(())
+    }
+
+    #[test]
+    fn alias_candidates() -> Result<()> {
+        use yaml_rust::YamlLoader;
+
+        let (mut device, _) = test_utils::get_patcher(Path::new("add"))?;
+        let patch = YamlLoader::load_from_str(
+            "
+DAC1:
+  _copy:
+    CR_MIRROR:
+      _from: CR
+      addressOffset: 0x100
+",
+        )?;
+        device.process(patch[0].as_hash().unwrap(), &Default::default())?;
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        assert_eq!(
+            super::alias_candidates(dac1.registers.as_deref().unwrap_or_default()),
+            [("CR", "CR_MIRROR")]
+        );
+
+        let patch = YamlLoader::load_from_str("DAC1:\n  _alias:\n    CR: CR_MIRROR")?;
+        device.process(patch[0].as_hash().unwrap(), &Default::default())?;
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        assert!(super::alias_candidates(dac1.registers.as_deref().unwrap_or_default()).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn strict_delete() -> Result<()> {
+        use crate::patch::Config;
+        use yaml_rust::YamlLoader;
+
+        let (device, _) = test_utils::get_patcher(Path::new("add"))?;
+        let patch = YamlLoader::load_from_str(
+            "
+DAC1:
+  _delete:
+    _registers: [SR]
+",
+        )?;
+        let patch = patch[0].as_hash().unwrap();
+        device.clone().process(patch, &Default::default())?;
+        let config = Config {
+            strict: true,
+            ..Default::default()
+        };
+        let err = device.clone().process(patch, &config).unwrap_err();
+        assert_eq!(
+            err.root_cause().to_string(),
+            "Trying to delete absent register `SR`"
+        );
+
+        let patch = YamlLoader::load_from_str(
+            "
+DAC1:
+  _delete:
+    _clusters: [CH*, ?~CH*]
+",
+        )?;
+        let patch = patch[0].as_hash().unwrap();
+        device.clone().process(patch, &Default::default())?;
+        let err = device.clone().process(patch, &config).unwrap_err();
+        assert_eq!(
+            err.root_cause().to_string(),
+            "Trying to delete absent cluster `CH*`"
+        );
+
+        let patch = YamlLoader::load_from_str("_delete: [DAC3, ?~DAC4]")?;
+        let patch = patch[0].as_hash().unwrap();
+        device.clone().process(patch, &Default::default())?;
+        let err = device.clone().process(patch, &config).unwrap_err();
+        assert_eq!(
+            err.root_cause().to_string(),
+            "Trying to delete absent peripheral `DAC3`"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn block_access() -> Result<()> {
+        fn access(device: &Device) -> Vec<(&str, Option<Access>)> {
+            let mut regs = Vec::new();
+            let dac1 = device.get_peripheral("DAC1").unwrap();
+            regs.push(("CR", dac1.get_register("CR").unwrap().properties.access));
+            for c in dac1.clusters() {
+                for r in c.registers() {
+                    regs.push((c.name.as_str(), r.properties.access));
+                }
+            }
+            regs
+        }
+
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("block_access"))?;
+        device.process(&yaml, &Default::default())?;
+        assert_eq!(
+            access(&device),
+            [
+                ("CR", Some(Access::ReadOnly)),
+                ("CH1", None),
+                ("CH1", None),
+                ("CH2", Some(Access::WriteOnly)),
+                ("CH2", Some(Access::WriteOnly)),
+            ]
+        );
+
+        let patch =
+            YamlLoader::load_from_str("DAC1: {_access: read-write, _access_recursive: true}")?;
+        device.process(patch[0].as_hash().unwrap(), &Default::default())?;
+        assert!(access(&device)
+            .iter()
+            .all(|(_, a)| *a == Some(Access::ReadWrite)));
+
+        let patch = YamlLoader::load_from_str("DAC1: {_access: ''}")?;
+        device.process(patch[0].as_hash().unwrap(), &Default::default())?;
+        assert_eq!(access(&device)[0], ("CR", None));
+
+        // More specific settings win over block-wide default
+        let patch = YamlLoader::load_from_str(
+            "
+DAC1:
+  _access: read-only
+  _access_recursive: true
+  _modify:
+    CR:
+      access: write-only
+  _clusters:
+    CH2:
+      _access: read-write
+",
+        )?;
+        device.process(patch[0].as_hash().unwrap(), &Default::default())?;
+        assert_eq!(
+            access(&device),
+            [
+                ("CR", Some(Access::WriteOnly)),
+                ("CH1", Some(Access::ReadOnly)),
+                ("CH1", Some(Access::ReadOnly)),
+                ("CH2", Some(Access::ReadWrite)),
+                ("CH2", Some(Access::ReadWrite)),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn alternate_group() -> Result<()> {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("alternate_group"))?;
+        device.process(&yaml, &Default::default())?;
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let group = |r| dac1.get_register(r).unwrap().alternate_group.as_deref();
+        assert_eq!(group("DR"), Some("WORD"));
+        assert_eq!(group("DR8"), Some("BYTE"));
+        assert_eq!(group("CR"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn array_dim_index() -> Result<()> {
+        use yaml_rust::YamlLoader;
+
+        let (mut device, _) = test_utils::get_patcher(Path::new("add"))?;
+        let patch = YamlLoader::load_from_str(
+            "
+DAC1:
+  _add:
+    REG0: {addressOffset: 0x10}
+    REG1: {addressOffset: 0x14}
+    REG2: {addressOffset: 0x18}
+    REG3: {addressOffset: 0x1C}
+    OUT1: {addressOffset: 0x20}
+    OUT2: {addressOffset: 0x24}
+    OUT3: {addressOffset: 0x28}
+  _array:
+    REG?: {}
+    OUT?: {}
+",
+        )?;
+        device.process(patch[0].as_hash().unwrap(), &Default::default())?;
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let dim_index = |name| match dac1.get_register(name) {
+            Some(Register::Array(_, dim)) => dim.dim_index.clone(),
+            _ => panic!("{name} array is not collected"),
+        };
+        assert_eq!(dim_index("REG%s"), None);
+        assert_eq!(
+            dim_index("OUT%s").as_deref(),
+            Some(&["1", "2", "3"].map(String::from)[..])
+        );
+
+        let (mut device, _) = test_utils::get_patcher(Path::new("add"))?;
+        let patch = YamlLoader::load_from_str(
+            "
+DAC1:
+  _add:
+    KEYR3: {addressOffset: 0x10}
+    KEYR2: {addressOffset: 0x14}
+    KEYR1: {addressOffset: 0x18}
+    KEYR0: {addressOffset: 0x1C}
+  _array:
+    KEYR?: {}
+",
+        )?;
+        device.process(patch[0].as_hash().unwrap(), &Default::default())?;
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let dim_index = |name| match dac1.get_register(name) {
+            Some(Register::Array(_, dim)) => dim.dim_index.clone(),
+            _ => panic!("{name} array is not collected"),
+        };
+        assert_eq!(
+            dim_index("KEYR%s").as_deref(),
+            Some(&["3", "2", "1", "0"].map(String::from)[..])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn array_gaps() -> Result<()> {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("array_gaps"))?;
+        device.process(&yaml, &Default::default())?;
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let Some(Register::Array(data, dim)) = dac1.get_register("CH%s_DATA") else {
+            panic!("CH%s_DATA array is not collected");
+        };
+        assert_eq!(data.address_offset, 0x10);
+        assert_eq!(data.description.as_deref(), Some("Channel %s data"));
+        assert_eq!(dim.dim, 4);
+        assert_eq!(dim.dim_increment, 4);
+        assert_eq!(dim.dim_index, None);
+
+        let errors = [
+            (
+                "{}",
+                "DAC1: registers cannot be collected into CH?_DATA array. Different addressOffset increments",
+            ),
+            (
+                "{_allow_gaps: true, dimIncrement: 8}",
+                "DAC1: registers cannot be collected into CH?_DATA array. Offset of CH1_DATA (0x14) is not multiple of dimIncrement 0x8 from CH0_DATA (0x10)",
+            ),
+            ("{_allow_gaps: true}", "`_allow_gaps` requires dimIncrement"),
+        ];
+        for (rmod, msg) in errors {
+            let (mut device, _) = test_utils::get_patcher(Path::new("array_gaps"))?;
+            let patch = YamlLoader::load_from_str(&format!(
+                "
+DAC1:
+  _add:
+    CH0_DATA: {{addressOffset: 0x10}}
+    CH1_DATA: {{addressOffset: 0x14}}
+    CH3_DATA: {{addressOffset: 0x1C}}
+  _array:
+    CH?_DATA: {rmod}
+"
+            ))?;
+            let err = device
+                .process(patch[0].as_hash().unwrap(), &Default::default())
+                .unwrap_err();
+            assert_eq!(err.root_cause().to_string(), msg);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn split_array() -> Result<()> {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("split_array"))?;
+        device.process(&yaml, &Default::default())?;
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let regs = dac1
+            .registers()
+            .filter(|r| r.address_offset >= 0x20)
+            .map(|r| {
+                assert!(r.is_single());
+                (r.name.as_str(), r.address_offset, r.description.as_deref())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            regs,
+            [
+                ("CCR_TX", 0x20, Some("Capture/compare register 0")),
+                ("CCR_RX", 0x24, Some("Capture/compare register 1")),
+                ("CCR_ERR", 0x28, Some("Capture/compare register 2")),
+                ("CCR_IDLE", 0x2c, Some("Capture/compare register 3")),
+                ("CHA_DR", 0x40, Some("Data register A")),
+                ("CHB_DR", 0x44, Some("Data register B")),
+            ]
+        );
+
+        let (mut device, _) = test_utils::get_patcher(Path::new("split_array"))?;
+        let patch = YamlLoader::load_from_str(
+            r"
+DAC1:
+  _add:
+    CCR%s:
+      addressOffset: 0x20
+      dim: 4
+      dimIncrement: 4
+  _split:
+    CCR%s: [A, B, C]
+",
+        )?;
+        let err = device
+            .process(patch[0].as_hash().unwrap(), &Default::default())
+            .unwrap_err();
+        assert_eq!(
+            err.root_cause().to_string(),
+            "`CCR%s` has 4 elements, but 3 names are given"
+        );
+        // Registers are kept on error
+        let mut dac1 = device.get_peripheral("DAC1").unwrap().clone();
+        let regs = dac1.registers.clone();
+        let names = YamlLoader::load_from_str("[A, B, C]")?;
+        super::RegisterBlockExt::split_array(&mut dac1, "CCR%s", &names[0]).unwrap_err();
+        assert_eq!(dac1.registers, regs);
+
+        let patch = YamlLoader::load_from_str("DAC1:\n  _split:\n    ?~MISSING%s: [A]")?;
+        device.process(patch[0].as_hash().unwrap(), &Default::default())?;
+        Ok(())
+    }
+
+    #[test]
+    fn rename() -> Result<()> {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("rename"))?;
+        device.process(&yaml, &Default::default())?;
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let ccr = dac1.get_register("CCR1_TIM1").unwrap();
+        assert_eq!(ccr.display_name.as_deref(), Some("CCR1_TIM1"));
+        let alt = dac1.get_register("TIM1_CCR1_ALT").unwrap();
+        assert_eq!(alt.alternate_register.as_deref(), Some("CCR1_TIM1"));
+        let cr = dac1.get_register("CR").unwrap();
+        assert!(cr.get_field("ENABLE1").is_some());
+
+        let patch = YamlLoader::load_from_str("DAC1:\n  _rename:\n    '(CCR': X")?;
+        let err = device
+            .process(patch[0].as_hash().unwrap(), &Default::default())
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("Invalid `_rename` pattern `(CCR`"));
+        Ok(())
+    }
+
+    #[test]
+    fn regex_rename() -> Result<()> {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("regex_rename"))?;
+        device.process(&yaml, &Default::default())?;
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        assert!(dac1.get_register("CH1_DATA").is_none());
+        assert_eq!(dac1.get_register("DATA_CH1").unwrap().address_offset, 0x10);
+        assert_eq!(dac1.get_register("DATA_CH2").unwrap().address_offset, 0x14);
+        assert!(dac1.get_register("CR").is_some());
+
+        let patch = YamlLoader::load_from_str("DAC1:\n  _regex:\n    'CH(\\d+)_X': X")?;
+        let err = device
+            .process(patch[0].as_hash().unwrap(), &Default::default())
+            .unwrap_err();
+        assert_eq!(
+            err.root_cause().to_string(),
+            "Could not find registers or clusters matching `CH(\\d+)_X`"
+        );
+        let patch = YamlLoader::load_from_str("DAC1:\n  _regex:\n    '?~CH(\\d+)_X': X")?;
+        device.process(patch[0].as_hash().unwrap(), &Default::default())?;
+
+        let patch = YamlLoader::load_from_str("DAC1:\n  _regex:\n    'DATA_CH\\d': CR")?;
+        let err = device
+            .process(patch[0].as_hash().unwrap(), &Default::default())
+            .unwrap_err();
+        assert_eq!(
+            err.root_cause().to_string(),
+            "Several registers or clusters are named `CR` after renaming"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn prefix() -> Result<()> {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("prefix"))?;
+        device.process(&yaml, &Default::default())?;
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        assert!(dac1.get_register("DMA_DMACR").is_some());
+        assert!(dac1.get_register("DMA_DMASR").is_some());
+        assert!(dac1.get_register("CR").is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn display_name_template() -> Result<()> {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("display_name_template"))?;
+        device.process(&yaml, &Default::default())?;
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let cr = dac1.get_register("CR").unwrap();
+        assert_eq!(cr.display_name.as_deref(), Some("CR"));
+        let sr = dac1.get_register("SR").unwrap();
+        assert_eq!(sr.display_name.as_deref(), Some("SR register"));
+        let dr = dac1.get_register("DR").unwrap();
+        assert_eq!(dr.display_name.as_deref(), Some("DR register"));
+        Ok(())
+    }
+
+    #[test]
+    fn reindex() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("reindex")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let Some(svd_rs::Register::Array(info, dim)) = dac1.get_register("DHR%s") else {
+            panic!("DHR%s array not found");
+        };
+        assert_eq!(info.name, "DHR%s");
+        assert_eq!(
+            dim.dim_index.as_deref(),
+            Some(&["A".to_string(), "B".to_string()][..])
+        );
+    }
+
+    #[test]
+    fn uncluster() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("uncluster")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let regs = dac1
+            .registers()
+            .map(|r| (r.name.as_str(), r.address_offset))
+            .collect::<Vec<_>>();
+        assert_eq!(regs, [("CR", 0), ("CH1_DHR", 0x10), ("CH1_DOR", 0x14)]);
+        assert_eq!(dac1.clusters().count(), 0);
+    }
+
+    #[test]
+    fn cluster_keep_source() {
+        let (mut device, _) = test_utils::get_patcher(Path::new("add")).unwrap();
+        let patch = YamlLoader::load_from_str(
+            "
+DAC1:
+  _cluster:
+    CH:
+      _keep_source: true
+      CR: {}
+",
+        )
+        .unwrap();
+        device
+            .process(patch[0].as_hash().unwrap(), &Default::default())
+            .unwrap();
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let children = dac1
+            .registers
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .map(|rc| rc.name().as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(children, ["CH", "CR"]);
+        let ch = dac1.get_cluster("CH").unwrap();
+        assert!(ch.get_register("CR").is_some());
+    }
+
+    #[test]
+    fn expand_all_clusters() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("expand_all_clusters")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        let regs = dac1
+            .registers()
+            .map(|r| (r.name.as_str(), r.address_offset))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            regs,
+            [
+                ("CR", 0),
+                ("CH3_SUB_SR", 0x28),
+                ("CH_1_DHR", 0x10),
+                ("CH_1_DOR", 0x14),
+                ("CH_2_DHR", 0x18),
+                ("CH_2_DOR", 0x1c)
+            ]
+        );
+        assert_eq!(dac1.clusters().count(), 0);
+    }
 }
@@ -1,11 +1,15 @@
 use std::collections::HashSet;
 
+use once_cell::sync::Lazy;
+use regex::Regex;
+
 use anyhow::{anyhow, Context};
 use itertools::Itertools;
 use svd_parser::expand::{BlockPath, RegisterPath};
 use svd_parser::svd::{
-    Access, BitRange, DimElement, EnumeratedValues, Field, FieldInfo, ModifiedWriteValues,
-    ReadAction, Register, RegisterInfo, Usage, WriteConstraint, WriteConstraintRange,
+    Access, BitRange, DimElement, EnumeratedValue, EnumeratedValues, Field, FieldInfo,
+    ModifiedWriteValues, ReadAction, Register, RegisterInfo, Usage, WriteConstraint,
+    WriteConstraintRange,
 };
 use yaml_rust::{yaml::Hash, Yaml};
 
@@ -14,16 +18,56 @@ use crate::patch::EnumAutoDerive;
 use super::iterators::{MatchIter, Matched};
 use super::yaml_ext::{AsType, GetVal, ToYaml};
 use super::{
-    check_offsets, common_description, make_dim_element, matchname, modify_dim_element, spec_ind,
-    Config, PatchResult, Spec, VAL_LVL,
+    apply_renames, check_offsets, check_renames, common_description, delete_absent,
+    explicit_dim_index, get_renames, make_dim_element, matchname, modify_dim_element, spec_ind,
+    Config, PatchResult, Rename, Spec, VAL_LVL,
+};
+use super::{
+    make_derived_enumerated_values, make_ev_array, make_ev_name, make_field, parse_write_constraint,
 };
-use super::{make_derived_enumerated_values, make_ev_array, make_ev_name, make_field};
+
+/// Sentinel name of merged field which means longest common prefix of merged fields
+const COMMON: &str = "_common";
+
+/// Keys of field hash which describe its bits
+const BIT_SPEC: [&str; 5] = ["msb", "lsb", "bitRange", "bitOffset", "bitWidth"];
+/// Keys used by `add_field` in addition to `BIT_SPEC`
+const FIELD_SPEC: [&str; 12] = [
+    "name",
+    "description",
+    "derivedFrom",
+    "access",
+    "modifiedWriteValues",
+    "readAction",
+    "writeConstraint",
+    "_write_constraint",
+    "dim",
+    "dimIncrement",
+    "dimIndex",
+    "dimName",
+];
 
 pub type FieldMatchIterMut<'a, 'b> = MatchIter<'b, std::slice::IterMut<'a, Field>>;
 
 pub(crate) trait RegisterInfoExt {
     /// Calculate filling of register
     fn get_bitmask(&self) -> u64;
+
+    /// Set size to smallest of 8, 16, 32 or 64 bits covering all fields
+    /// if register has fields but no size
+    fn infer_size(&mut self);
+
+    /// Check that register has exactly the listed fields
+    fn require_fields(&self, fnames: &[&str]) -> PatchResult;
+
+    /// Check that resetValue has no bits set beyond register size
+    fn check_reset_width(&self) -> PatchResult;
+
+    /// Check that register has no several fields with same name
+    fn check_duplicate_fields(&self) -> PatchResult;
+
+    /// Check that no fields of register share bits
+    fn check_overlaps(&self) -> PatchResult;
 }
 
 impl RegisterInfoExt for RegisterInfo {
@@ -36,6 +80,82 @@ impl RegisterInfoExt for RegisterInfo {
         }
         mask
     }
+
+    fn infer_size(&mut self) {
+        if self.properties.size.is_some() || self.fields().next().is_none() {
+            return;
+        }
+        let bits = 64 - self.get_bitmask().leading_zeros();
+        self.properties.size = [8, 16, 32, 64].into_iter().find(|&size| size >= bits);
+    }
+
+    fn check_reset_width(&self) -> PatchResult {
+        if let (Some(size), Some(reset)) = (self.properties.size, self.properties.reset_value) {
+            if size < 64 && reset >> size != 0 {
+                return Err(anyhow!(
+                    "resetValue {reset:#x} of register `{}` does not fit in {size} bits",
+                    self.name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_duplicate_fields(&self) -> PatchResult {
+        let duplicates = duplicate_fields(self);
+        if !duplicates.is_empty() {
+            return Err(anyhow!(
+                "Register `{}` has duplicate fields: {}",
+                self.name,
+                duplicates.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_overlaps(&self) -> PatchResult {
+        let mask = |f: &Field| (!0u64 >> (64 - f.bit_range.width)) << f.bit_range.offset;
+        let mut occupied = 0;
+        for (i, ftag) in self.fields().enumerate() {
+            let fmask = mask(ftag);
+            if occupied & fmask != 0 {
+                let other = self
+                    .fields()
+                    .take(i)
+                    .find(|f| mask(f) & fmask != 0)
+                    .unwrap();
+                return Err(anyhow!(
+                    "Fields `{}` and `{}` of register `{}` overlap",
+                    other.name,
+                    ftag.name,
+                    self.name
+                ));
+            }
+            occupied |= fmask;
+        }
+        Ok(())
+    }
+
+    fn require_fields(&self, fnames: &[&str]) -> PatchResult {
+        let present = self
+            .fields()
+            .map(|f| f.name.as_str())
+            .collect::<HashSet<_>>();
+        let expected = fnames.iter().copied().collect::<HashSet<_>>();
+        if present != expected {
+            let missing = fnames.iter().filter(|f| !present.contains(*f)).join(", ");
+            let unexpected = self
+                .fields()
+                .map(|f| f.name.as_str())
+                .filter(|f| !expected.contains(f))
+                .join(", ");
+            return Err(anyhow!(
+                "Register `{}` fields mismatch. Missing: [{missing}]. Unexpected: [{unexpected}].",
+                self.name
+            ));
+        }
+        Ok(())
+    }
 }
 
 /// Collecting methods for processing register contents
@@ -47,12 +167,21 @@ pub trait RegisterExt {
         "_derive",
         "_strip",
         "_strip_end",
+        "_prefix",
+        "_suffix",
         "_clear",
+        "_clear_enum",
+        "_collapse_enums",
         "_modify",
         "_add",
         "_merge",
         "_split",
         "_array",
+        "_derive_field_enums",
+        "_recalc_reset_mask",
+        "_infer_size",
+        "_rename",
+        "_sort",
     ];
 
     /// Iterates over all fields that match fspec and live inside rtag
@@ -65,17 +194,38 @@ pub trait RegisterExt {
     fn process(&mut self, rmod: &Hash, bpath: &BlockPath, config: &Config) -> PatchResult;
 
     /// Add fname given by fadd to rtag
-    fn add_field(&mut self, fname: &str, fadd: &Hash, rpath: &RegisterPath) -> PatchResult;
+    fn add_field(
+        &mut self,
+        fname: &str,
+        fadd: &Hash,
+        rpath: &RegisterPath,
+        config: &Config,
+    ) -> PatchResult;
 
     /// Delete fields matched by fspec inside rtag
-    fn delete_field(&mut self, fspec: &str) -> PatchResult;
+    fn delete_field(&mut self, fspec: &str, config: &Config) -> PatchResult;
 
     /// Clear field from rname and mark it as derivedFrom rderive.
     fn derive_field(&mut self, fname: &str, fderive: &Yaml, rpath: &RegisterPath) -> PatchResult;
 
+    /// Make enumeratedValues of fields matched by fspec derivedFrom enumeratedValues of fsource
+    fn derive_field_enums(
+        &mut self,
+        fspec: &str,
+        fsource: &str,
+        rpath: &RegisterPath,
+    ) -> PatchResult;
+
     /// Clear contents of fields matched by fspec inside rtag
     fn clear_field(&mut self, fspec: &str) -> PatchResult;
 
+    /// Clear enumeratedValues of fields matched by fspec inside rtag
+    fn clear_field_enum(&mut self, fspec: &str) -> PatchResult;
+
+    /// Replace identical read and write enumeratedValues of fields matched by fspec
+    /// with single read-write enumeratedValues
+    fn collapse_field_enums(&mut self, fspec: &str) -> PatchResult;
+
     /// Work through a field, handling either an enum or a range
     fn process_field(
         &mut self,
@@ -115,9 +265,29 @@ pub trait RegisterExt {
     /// Delete substring from the ending bitfield names inside rtag
     fn strip_end(&mut self, substr: &str) -> PatchResult;
 
+    /// Add prefix to names of fields matched by fspec and,
+    /// if `enums`, to names of their not derived enumeratedValues
+    fn add_prefix(&mut self, prefix: &str, fspec: &str, enums: bool) -> PatchResult;
+
+    /// Add suffix to names of fields matched by fspec and,
+    /// if `enums`, to names of their not derived enumeratedValues
+    fn add_suffix(&mut self, suffix: &str, fspec: &str, enums: bool) -> PatchResult;
+
+    /// Rename fields inside rtag using regex renames
+    fn rename(&mut self, renames: &[Rename]) -> PatchResult;
+
     /// Modify fspec inside rtag according to fmod
     fn modify_field(&mut self, fspec: &str, fmod: &Hash, rpath: &RegisterPath) -> PatchResult;
 
+    /// Modify one element of field array selected as `FIELD[INDEX]` by expanding the array.
+    /// Array is kept if element is left unchanged. Returns `false` if fspec is not such selector
+    fn modify_field_element(
+        &mut self,
+        fspec: &str,
+        fmod: &Hash,
+        rpath: &RegisterPath,
+    ) -> anyhow::Result<bool>;
+
     /// Merge all fspec in rtag.
     /// Support list of field to auto-merge, and dict with fspec or list of fspec
     fn merge_fields(
@@ -138,6 +308,9 @@ pub trait RegisterExt {
         fmod: &Hash,
         rpath: &RegisterPath,
     ) -> PatchResult;
+
+    /// Stable sort of fields by `offset` or `name`
+    fn sort_fields(&mut self, key: &str) -> PatchResult;
 }
 
 impl RegisterExt for Register {
@@ -158,7 +331,7 @@ impl RegisterExt for Register {
 
         // Handle deletions
         for fspec in rmod.str_vec_iter("_delete")? {
-            self.delete_field(fspec)
+            self.delete_field(fspec, config)
                 .with_context(|| format!("Deleting fields matched to `{fspec}`"))?;
         }
 
@@ -177,19 +350,34 @@ impl RegisterExt for Register {
             self.clear_field(fspec)
                 .with_context(|| format!("Clearing contents of fields matched to `{fspec}`"))?;
         }
+        for fspec in rmod.str_vec_iter("_clear_enum")? {
+            self.clear_field_enum(fspec).with_context(|| {
+                format!("Clearing enumeratedValues of fields matched to `{fspec}`")
+            })?;
+        }
 
         // Handle modifications
         for (fspec, fmod) in rmod.hash_iter("_modify") {
             let fspec = fspec.str()?;
-            self.modify_field(fspec, fmod.hash()?, &rpath)
-                .with_context(|| format!("Modifying fields matched to `{fspec}`"))?;
+            let fmod = fmod.hash()?;
+            match self.modify_field_element(fspec, fmod, &rpath) {
+                Ok(true) => Ok(()),
+                Ok(false) => self.modify_field(fspec, fmod, &rpath),
+                Err(e) => Err(e),
+            }
+            .with_context(|| format!("Modifying fields matched to `{fspec}`"))?;
         }
         // Handle additions
         for (fname, fadd) in rmod.hash_iter("_add") {
             let fname = fname.str()?;
-            self.add_field(fname, fadd.hash()?, &rpath)
+            self.add_field(fname, fadd.hash()?, &rpath, config)
                 .with_context(|| format!("Adding field `{fname}`"))?;
         }
+        // Handle regex renames
+        let renames = get_renames(rmod, "_rename")?;
+        if !renames.is_empty() {
+            self.rename(&renames).context("Renaming fields")?;
+        }
         // Handle derives
         for (fspec, fderive) in rmod.hash_iter("_derive") {
             let fspec = fspec.str()?;
@@ -255,6 +443,23 @@ impl RegisterExt for Register {
             }
         }
 
+        // Handle enum derives after enums of source field are set
+        for (fspec, fsource) in rmod.hash_iter("_derive_field_enums") {
+            let fspec = fspec.str()?;
+            let fsource = fsource.str()?;
+            self.derive_field_enums(fspec, fsource, &rpath)
+                .with_context(|| {
+                    format!("Deriving enums of fields matched to `{fspec}` from `{fsource}`")
+                })?;
+        }
+
+        // Handle enum collapses after enums are set
+        for fspec in rmod.str_vec_iter("_collapse_enums")? {
+            self.collapse_field_enums(fspec).with_context(|| {
+                format!("Collapsing enumeratedValues of fields matched to `{fspec}`")
+            })?;
+        }
+
         // Handle field arrays
         for (fspec, fmod) in rmod.hash_iter("_array") {
             let fspec = fspec.str()?;
@@ -262,6 +467,57 @@ impl RegisterExt for Register {
                 .with_context(|| format!("Collecting fields matched to `{fspec}` in array"))?;
         }
 
+        // Handle prefixes and suffixes of matched fields
+        for (prefix, val) in rmod.hash_iter("_prefix") {
+            let prefix = prefix.str()?;
+            let (fspec, enums) = affix_spec(val)?;
+            self.add_prefix(prefix, fspec, enums).with_context(|| {
+                format!("Adding prefix `{prefix}` to fields matched to `{fspec}`")
+            })?;
+        }
+        for (suffix, val) in rmod.hash_iter("_suffix") {
+            let suffix = suffix.str()?;
+            let (fspec, enums) = affix_spec(val)?;
+            self.add_suffix(suffix, fspec, enums).with_context(|| {
+                format!("Adding suffix `{suffix}` to fields matched to `{fspec}`")
+            })?;
+        }
+
+        // Handle field sorting
+        if let Some(key) = rmod.get_str("_sort")? {
+            self.sort_fields(key).context("Sorting fields")?;
+        }
+
+        // Set size from fields if it is absent
+        if rmod.get_bool("_infer_size")? == Some(true) {
+            self.infer_size();
+        }
+
+        // Mark bits not covered by fields as not reset
+        if rmod.get_bool("_recalc_reset_mask")? == Some(true) {
+            self.properties.reset_mask = Some(self.get_bitmask());
+        }
+
+        self.check_reset_width()?;
+        if config.check_duplicate_fields {
+            self.check_duplicate_fields()?;
+        }
+        if config.check_overlaps {
+            self.check_overlaps()?;
+        }
+
+        Ok(())
+    }
+
+    fn sort_fields(&mut self, key: &str) -> PatchResult {
+        let Some(fields) = self.fields.as_mut() else {
+            return Ok(());
+        };
+        match key {
+            "offset" => fields.sort_by_key(|f| f.bit_range.offset),
+            "name" => fields.sort_by(|a, b| a.name.cmp(&b.name)),
+            _ => return Err(anyhow!("Unknown sort key `{key}`")),
+        }
         Ok(())
     }
 
@@ -276,6 +532,19 @@ impl RegisterExt for Register {
         Ok(())
     }
 
+    fn rename(&mut self, renames: &[Rename]) -> PatchResult {
+        let mut used = vec![false; renames.len()];
+        let mut renamed = Vec::new();
+        for ftag in self.fields_mut() {
+            if let Some(i) = apply_renames(renames, &mut ftag.name) {
+                used[i] = true;
+                renamed.push(ftag.name.clone());
+            }
+        }
+        let names = self.fields().map(|f| f.name.as_str()).collect::<Vec<_>>();
+        check_renames(renames, &used, &renamed, &names, "fields")
+    }
+
     fn strip_end(&mut self, substr: &str) -> PatchResult {
         let len = substr.len();
         let glob = globset::Glob::new(&("*".to_string() + substr))?.compile_matcher();
@@ -288,6 +557,56 @@ impl RegisterExt for Register {
         Ok(())
     }
 
+    fn add_prefix(&mut self, prefix: &str, fspec: &str, enums: bool) -> PatchResult {
+        affix_fields(self, fspec, enums, |name| name.insert_str(0, prefix))
+    }
+
+    fn add_suffix(&mut self, suffix: &str, fspec: &str, enums: bool) -> PatchResult {
+        affix_fields(self, fspec, enums, |name| name.push_str(suffix))
+    }
+
+    fn modify_field_element(
+        &mut self,
+        fspec: &str,
+        fmod: &Hash,
+        rpath: &RegisterPath,
+    ) -> anyhow::Result<bool> {
+        static ELEMENT: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(\w+)\[(\w+)\]$").unwrap());
+        let Some(caps) = ELEMENT.captures(fspec) else {
+            return Ok(false);
+        };
+        let (base, idx) = (&caps[1], &caps[2]);
+        let Some(fields) = self.fields.as_mut() else {
+            return Ok(false);
+        };
+        let Some(pos) = fields
+            .iter()
+            .position(|f| f.is_array() && f.name.replace("[%s]", "").replace("%s", "") == base)
+        else {
+            return Ok(false);
+        };
+        let Field::Array(info, dim) = fields.remove(pos) else {
+            unreachable!()
+        };
+        let Some(i) = dim.indexes().position(|index| index == idx) else {
+            fields.insert(pos, Field::Array(info, dim));
+            return Err(anyhow!("Field array `{base}` has no element `{idx}`"));
+        };
+        let elements = svd_parser::svd::field::expand(&info, &dim)
+            .map(FieldInfo::single)
+            .collect::<Vec<_>>();
+        let original = elements[i].clone();
+        let len = elements.len();
+        fields.splice(pos..pos, elements);
+        self.modify_field(&original.name, fmod, rpath)?;
+
+        let fields = self.fields.as_mut().unwrap();
+        if fields[pos + i] == original {
+            fields.splice(pos..pos + len, [Field::Array(info, dim)]);
+        }
+        Ok(true)
+    }
+
     fn modify_field(&mut self, fspec: &str, fmod: &Hash, rpath: &RegisterPath) -> PatchResult {
         let (fspec, ignore) = fspec.spec();
         let ftags = self.iter_fields(fspec).collect::<Vec<_>>();
@@ -305,26 +624,12 @@ impl RegisterExt for Register {
                     .get_yaml("_write_constraint")
                     .or_else(|| fmod.get_yaml("writeConstraint"))
                 {
-                    let wc = match value {
-                        Yaml::String(s) if s == "none" => {
-                            // Completely remove the existing writeConstraint
-                            None
-                        }
-                        Yaml::String(s) if s == "enum" => {
-                            // Only allow enumerated values
-                            Some(WriteConstraint::UseEnumeratedValues(true))
-                        }
-                        Yaml::Array(a) => {
-                            // Allow a certain range
-                            Some(WriteConstraint::Range(WriteConstraintRange {
-                                min: a[0].i64()? as u64,
-                                max: a[1].i64()? as u64,
-                            }))
-                        }
-                        _ => return Err(anyhow!("Unknown writeConstraint type {value:?}")),
-                    };
+                    let wc = parse_write_constraint(value)?;
                     ftag.write_constraint = wc;
                 }
+                if fmod.get_bool("_dedup_enum")? == Some(true) {
+                    dedup_enums(ftag);
+                }
                 // For all other tags, just set the value
                 ftag.modify_from(field_builder.clone(), VAL_LVL)?;
                 if let Some("") = fmod.get_str("access")? {
@@ -335,7 +640,13 @@ impl RegisterExt for Register {
         Ok(())
     }
 
-    fn add_field(&mut self, fname: &str, fadd: &Hash, rpath: &RegisterPath) -> PatchResult {
+    fn add_field(
+        &mut self,
+        fname: &str,
+        fadd: &Hash,
+        rpath: &RegisterPath,
+        config: &Config,
+    ) -> PatchResult {
         if self.get_field(fname).is_some() {
             return Err(anyhow!("register {rpath} already has a field {fname}"));
         }
@@ -347,13 +658,32 @@ impl RegisterExt for Register {
         } else {
             fnew.single()
         };
+        if let Some(size) = self.properties.size.or(config.register_size) {
+            let end = match &fnew {
+                Field::Single(f) => f.bit_range.msb() + 1,
+                Field::Array(f, dim) => f.bit_range.msb() + 1 + (dim.dim - 1) * dim.dim_increment,
+            };
+            if end > size {
+                return Err(anyhow!(
+                    "field {fname} (bits {}..{end}) does not fit in {size}-bit register {rpath}",
+                    fnew.bit_range.offset
+                ));
+            }
+        }
         self.fields.get_or_insert_with(Default::default).push(fnew);
         Ok(())
     }
 
-    fn delete_field(&mut self, fspec: &str) -> PatchResult {
+    fn delete_field(&mut self, fspec: &str, config: &Config) -> PatchResult {
+        let (fspec, ignore) = fspec.spec();
+        let mut deleted = false;
         if let Some(fields) = self.fields.as_mut() {
+            let len = fields.len();
             fields.retain(|f| !(matchname(&f.name, fspec)));
+            deleted = fields.len() != len;
+        }
+        if !deleted && !ignore {
+            return delete_absent("field", fspec, config);
         }
         Ok(())
     }
@@ -427,12 +757,105 @@ impl RegisterExt for Register {
         Ok(())
     }
 
+    fn clear_field_enum(&mut self, fspec: &str) -> PatchResult {
+        for ftag in self.iter_fields(fspec) {
+            if ftag.derived_from.is_some() {
+                continue;
+            }
+            ftag.enumerated_values = Vec::new();
+        }
+        Ok(())
+    }
+
+    fn collapse_field_enums(&mut self, fspec: &str) -> PatchResult {
+        let mut renamed = Vec::new();
+        for ftag in self.iter_fields(fspec) {
+            if let [r, w] = ftag.enumerated_values.as_slice() {
+                let (r, w) = if r.usage == Some(Usage::Write) {
+                    (w, r)
+                } else {
+                    (r, w)
+                };
+                if r.usage == Some(Usage::Read)
+                    && w.usage == Some(Usage::Write)
+                    && r.derived_from == w.derived_from
+                    && r.values == w.values
+                {
+                    let mut evs = r.clone();
+                    evs.usage = Some(Usage::ReadWrite);
+                    if let (Some(wname), Some(rname)) = (w.name.as_ref(), r.name.as_ref()) {
+                        renamed.push((wname.clone(), rname.clone()));
+                    }
+                    ftag.enumerated_values = vec![evs];
+                }
+            }
+        }
+        // Retarget derivedFrom of dropped write enumeratedValues
+        for ftag in self.fields_mut() {
+            for evs in &mut ftag.enumerated_values {
+                if let Some(dname) = evs.derived_from.as_mut() {
+                    if let Some((_, rname)) = renamed.iter().find(|(wname, _)| wname == dname) {
+                        *dname = rname.clone();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn derive_field_enums(
+        &mut self,
+        fspec: &str,
+        fsource: &str,
+        rpath: &RegisterPath,
+    ) -> PatchResult {
+        let source = self
+            .fields_mut()
+            .find(|f| f.name == fsource)
+            .ok_or_else(|| anyhow!("{rpath}: source field {fsource} not found"))?;
+        if source.enumerated_values.is_empty() {
+            return Err(anyhow!("{rpath}: field {fsource} has no enumeratedValues"));
+        }
+        let mut evs = Vec::new();
+        for (i, ev) in source.enumerated_values.iter_mut().enumerate() {
+            let name = ev.name.get_or_insert_with(|| {
+                if i == 0 {
+                    fsource.to_string()
+                } else {
+                    format!("{fsource}_{i}")
+                }
+            });
+            evs.push(
+                EnumeratedValues::builder()
+                    .derived_from(Some(name.clone()))
+                    .usage(ev.usage)
+                    .build(VAL_LVL)?,
+            );
+        }
+        let (fspec, ignore) = fspec.spec();
+        let mut found = false;
+        for ftag in self.iter_fields(fspec) {
+            if ftag.name != fsource {
+                ftag.enumerated_values.clone_from(&evs);
+                found = true;
+            }
+        }
+        if !found && !ignore {
+            let present = self.present_fields();
+            return Err(anyhow!(
+                "Could not find `{rpath}:{fspec}. Present fields: {present}.`"
+            ));
+        }
+        Ok(())
+    }
+
     fn merge_fields(
         &mut self,
         key: &str,
         value: Option<&Yaml>,
         rpath: &RegisterPath,
     ) -> PatchResult {
+        let mut description = None;
         let (name, names) = match value {
             Some(Yaml::String(value)) => (
                 key.to_string(),
@@ -440,6 +863,14 @@ impl RegisterExt for Register {
                     .map(|f| f.name.to_string())
                     .collect(),
             ),
+            Some(Yaml::Hash(value)) => {
+                let mut names = Vec::new();
+                for fspec in value.str_vec_iter("fields")? {
+                    names.extend(self.iter_fields(fspec).map(|f| f.name.to_string()));
+                }
+                description = value.get_string("description")?;
+                (key.to_string(), names)
+            }
             Some(Yaml::Array(value)) => {
                 let mut names = Vec::new();
                 for fspec in value {
@@ -451,13 +882,14 @@ impl RegisterExt for Register {
             None => {
                 let names: Vec<String> =
                     self.iter_fields(key).map(|f| f.name.to_string()).collect();
-                let name = commands::util::longest_common_prefix(
-                    names.iter().map(|n| n.as_str()).collect(),
-                )
-                .to_string();
-                (name, names)
+                (COMMON.to_string(), names)
             }
         };
+        // Use longest common prefix of merged fields as name
+        let prefix =
+            commands::util::longest_common_prefix(names.iter().map(|n| n.as_str()).collect())
+                .to_string();
+        let name = if name == COMMON { prefix.clone() } else { name };
 
         if names.is_empty() {
             let present = self.present_fields();
@@ -469,19 +901,25 @@ impl RegisterExt for Register {
             let mut bitwidth = 0;
             let mut bitoffset = u32::MAX;
             let mut pos = usize::MAX;
-            let mut first = true;
-            let mut desc = None;
+            let mut descs = Vec::new();
+            let mut dim_index = Vec::new();
             for (i, f) in fields.iter_mut().enumerate() {
                 if names.contains(&f.name) {
-                    if first {
-                        desc.clone_from(&f.description);
-                        first = false;
-                    }
+                    descs.push(f.description.as_deref());
+                    dim_index.push(f.name[prefix.len()..].to_string());
                     bitwidth += f.bit_range.width;
                     bitoffset = bitoffset.min(f.bit_range.offset);
                     pos = pos.min(i);
                 }
             }
+            let desc = if description.is_some() {
+                description
+            } else if descs.len() > 1 && dim_index.iter().all(|idx| !idx.is_empty()) {
+                // Collapse descriptions which differ only in field index
+                common_description(&descs, &dim_index).unwrap_or_else(|| descs[0].map(String::from))
+            } else {
+                descs[0].map(String::from)
+            };
             fields.retain(|f| !names.contains(&f.name));
             fields.insert(
                 pos,
@@ -555,9 +993,13 @@ impl RegisterExt for Register {
             fields[0].name = if let Some(name) = fmod.get_str("name")? {
                 name.into()
             } else {
-                format!("{}%s{}", &fspec[..li], &fspec[fspec.len() - ri..])
+                let name = &fields[0].name;
+                format!("{}%s{}", &name[..li], &name[name.len() - ri..])
             };
-            if let Some(desc) = fmod.get_str("description")? {
+            if let Some(desc) = fmod.get_str("_force_description")? {
+                // Used as is, even if elements' descriptions differ by more than index
+                fields[0].description = Some(desc.into());
+            } else if let Some(desc) = fmod.get_str("description")? {
                 if desc != "_original" {
                     fields[0].description = Some(desc.into());
                 }
@@ -576,7 +1018,7 @@ impl RegisterExt for Register {
                 DimElement::builder()
                     .dim(dim as u32)
                     .dim_increment(dim_increment)
-                    .dim_index(Some(dim_index))
+                    .dim_index(explicit_dim_index(dim_index))
                     .build(VAL_LVL)?,
             );
             //field.process(fmod, &self.name, true);
@@ -614,14 +1056,25 @@ impl RegisterExt for Register {
                     first.description.clone()
                 };
                 let bitoffset = first.bit_range.offset;
+                let mut evs = None;
+                if let Some(emod) = fsplit.get_hash("enum")? {
+                    let ename = emod.get_str("_name")?.unwrap_or(&first.name).to_string();
+                    evs = Some((make_ev_array(emod)?.name(Some(ename.clone())), ename));
+                }
                 let mut fields = Vec::with_capacity(first.bit_range.width as _);
                 for i in 0..first.bit_range.width {
                     fields.push({
                         let is = i.to_string();
+                        let enumerated_values = match evs.as_ref() {
+                            Some((evs, _)) if i == 0 => vec![evs.clone().build(VAL_LVL)?],
+                            Some((_, ename)) => vec![make_derived_enumerated_values(ename)?],
+                            None => Vec::new(),
+                        };
                         FieldInfo::builder()
                             .name(name.replace("%s", &is))
                             .description(desc.clone().map(|d| d.replace("%s", &is)))
                             .bit_range(BitRange::from_offset_width(bitoffset + i, 1))
+                            .enumerated_values(enumerated_values)
                             .build(VAL_LVL)?
                             .single()
                     });
@@ -665,6 +1118,33 @@ impl RegisterExt for Register {
 
         match fmod {
             Yaml::Hash(fmod) => {
+                // Add absent field if bits are specified together with enumeratedValues
+                let bitless;
+                let fmod = if BIT_SPEC.iter().any(|k| fmod.contains_key(&k.to_yaml())) {
+                    let (fname, _) = fspec.spec();
+                    if self.iter_fields(fname).next().is_none() {
+                        if fname.contains(['*', '?', '[', ']', '{', '}', ',']) {
+                            return Err(anyhow!(
+                                "Could not find `{fname}`. Only single field can be added with bits"
+                            ));
+                        }
+                        self.add_field(fname, fmod, rpath, config)
+                            .with_context(|| format!("Adding field `{fname}`"))?;
+                    }
+                    bitless = fmod
+                        .iter()
+                        .filter(|(k, _)| {
+                            !BIT_SPEC
+                                .iter()
+                                .chain(&FIELD_SPEC)
+                                .any(|b| k.as_str() == Some(b))
+                        })
+                        .map(|(k, v)| (k.clone(), v.clone()))
+                        .collect::<Hash>();
+                    &bitless
+                } else {
+                    fmod
+                };
                 let is_read = READ.keys().any(|key| fmod.contains_key(&key.to_yaml()));
                 let is_write = WRITE.keys().any(|key| fmod.contains_key(&key.to_yaml()));
                 if !is_read && !is_write {
@@ -823,36 +1303,60 @@ impl RegisterExt for Register {
         let reg_access = self.properties.access;
         if let Some(d) = fmod.get_str("_derivedFrom")? {
             // This is a derived enumeratedValues => Try to find the
-            // original definition to extract its <usage>
-            let mut derived_enums = self
-                .fields()
-                .flat_map(|f| f.enumerated_values.iter())
-                .filter(|e| e.name.as_deref() == Some(d));
-            let orig_usage = match (derived_enums.next(), derived_enums.next()) {
-                (Some(e), None) => e.usage().ok_or_else(|| {
-                    anyhow!("{rpath}: multilevel derive for {d} is not supported")
-                })?,
-                (None, _) => return Err(anyhow!("{rpath}: enumeratedValues {d} can't be found")),
-                (Some(_), Some(_)) => {
-                    return Err(anyhow!(
-                        "{rpath}: enumeratedValues {d} was found multiple times"
-                    ));
-                }
+            // original definition to extract its <usage>.
+            // Dotted paths are resolved against whole device when
+            // all peripherals are processed
+            let orig_usage = if d.contains('.') {
+                None
+            } else {
+                let mut derived_enums = self
+                    .fields()
+                    .flat_map(|f| f.enumerated_values.iter())
+                    .filter(|e| e.name.as_deref() == Some(d));
+                Some(match (derived_enums.next(), derived_enums.next()) {
+                    (Some(e), None) => e.usage().ok_or_else(|| {
+                        anyhow!("{rpath}: multilevel derive for {d} is not supported")
+                    })?,
+                    (None, _) => {
+                        return Err(anyhow!(
+                        "{rpath}: enumeratedValues {d} can't be found in register (local lookup)"
+                    ))
+                    }
+                    (Some(_), Some(_)) => {
+                        return Err(anyhow!(
+                            "{rpath}: enumeratedValues {d} was found multiple times"
+                        ));
+                    }
+                })
             };
             let evs = make_derived_enumerated_values(d)?;
             for ftag in self.iter_fields(fspec) {
+                if orig_usage.is_none() {
+                    config
+                        .enum_derives
+                        .borrow_mut()
+                        .push((rpath.new_field(&ftag.name), d.into()));
+                }
                 let access = ftag.access.or(reg_access).unwrap_or_default();
                 let checked_usage = check_usage(access, usage)
                     .with_context(|| format!("In field {}", ftag.name))?;
-                if checked_usage != orig_usage {
-                    return Err(anyhow!(
-                        "enumeratedValues with different usage was found: {usage:?} != {orig_usage:?}"
-                    ));
+                if let Some(orig_usage) = orig_usage {
+                    if checked_usage != orig_usage {
+                        return Err(anyhow!(
+                            "enumeratedValues with different usage was found: {usage:?} != {orig_usage:?}"
+                        ));
+                    }
                 }
                 if ftag.name == d {
                     return Err(anyhow!("EnumeratedValues can't be derived from itself"));
                 }
-                set_enum(ftag, evs.clone(), orig_usage, true, access)?;
+                set_enum(
+                    ftag,
+                    evs.clone(),
+                    orig_usage.unwrap_or(checked_usage),
+                    true,
+                    access,
+                )?;
             }
         } else {
             let (fspec, ignore) = fspec.spec();
@@ -911,6 +1415,18 @@ impl RegisterExt for Register {
                     )?;
                 }
             }
+            // Read and write enumeratedValues of same field become different types in PAC
+            for ftag in self.fields().filter(|f| matchname(&f.name, fspec)) {
+                if let [v1, v2] = ftag.enumerated_values.as_slice() {
+                    if v1.name.is_some() && v1.name == v2.name {
+                        return Err(anyhow!(
+                            "{rpath}:{}: read and write enumeratedValues have same name `{}`",
+                            ftag.name,
+                            v1.name.as_deref().unwrap()
+                        ));
+                    }
+                }
+            }
             // Move field with derived enums before other
             if let Some(fields) = self.fields.as_mut() {
                 if *min_offset_pos != min_pos {
@@ -947,7 +1463,7 @@ impl RegisterExt for Register {
     }
 }
 
-fn check_usage(access: Access, usage: Option<Usage>) -> anyhow::Result<Usage> {
+pub(crate) fn check_usage(access: Access, usage: Option<Usage>) -> anyhow::Result<Usage> {
     Ok(match (access, usage) {
         (Access::ReadWrite | Access::ReadWriteOnce, usage) => usage.unwrap_or_default(),
         (Access::ReadOnly, None | Some(Usage::Read)) => Usage::Read,
@@ -960,6 +1476,171 @@ fn check_usage(access: Access, usage: Option<Usage>) -> anyhow::Result<Usage> {
     })
 }
 
+/// Parse value of `_prefix`/`_suffix` entry: either fspec
+/// or hash with `fields` spec and `enums` flag
+fn affix_spec(val: &Yaml) -> anyhow::Result<(&str, bool)> {
+    match val {
+        Yaml::Hash(h) => Ok((
+            h.get_str("fields")?
+                .ok_or_else(|| anyhow!("`fields` is required"))?,
+            h.get_bool("enums")?.unwrap_or(false),
+        )),
+        _ => Ok((val.str()?, false)),
+    }
+}
+
+/// Apply `affix` to names of fields matched by fspec and,
+/// if `enums`, to names of their not derived enumeratedValues.
+/// enumeratedValues of register derived from renamed ones are updated
+fn affix_fields(
+    rtag: &mut Register,
+    fspec: &str,
+    enums: bool,
+    affix: impl Fn(&mut String),
+) -> PatchResult {
+    let (fspec, ignore) = fspec.spec();
+    let mut found = false;
+    let mut renamed = Vec::new();
+    for ftag in rtag.iter_fields(fspec) {
+        affix(&mut ftag.name);
+        if enums {
+            for evs in &mut ftag.enumerated_values {
+                if evs.derived_from.is_some() {
+                    continue;
+                }
+                if let Some(name) = evs.name.as_mut() {
+                    let old = name.clone();
+                    affix(name);
+                    renamed.push((old, name.clone()));
+                }
+                for ev in &mut evs.values {
+                    affix(&mut ev.name);
+                }
+            }
+        }
+        found = true;
+    }
+    if !found && !ignore {
+        let present = rtag.present_fields();
+        return Err(anyhow!(
+            "Could not find `{fspec}`. Present fields: {present}."
+        ));
+    }
+    for ftag in rtag.fields_mut() {
+        for evs in &mut ftag.enumerated_values {
+            if let Some(dname) = evs.derived_from.as_mut() {
+                if let Some((_, new)) = renamed.iter().find(|(old, _)| old == dname) {
+                    *dname = new.clone();
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Remove enumerated values with duplicate value.
+/// First is kept unless only later one has description
+fn dedup_enums(ftag: &mut FieldInfo) {
+    for evs in &mut ftag.enumerated_values {
+        if evs.derived_from.is_some() {
+            continue;
+        }
+        let mut values: Vec<EnumeratedValue> = Vec::with_capacity(evs.values.len());
+        for ev in std::mem::take(&mut evs.values) {
+            let Some(value) = ev.value else {
+                values.push(ev);
+                continue;
+            };
+            match values.iter_mut().find(|v| v.value == Some(value)) {
+                Some(kept) => {
+                    if kept.description.as_deref().unwrap_or_default().is_empty()
+                        && !ev.description.as_deref().unwrap_or_default().is_empty()
+                    {
+                        *kept = ev;
+                    }
+                }
+                None => values.push(ev),
+            }
+        }
+        evs.values = values;
+    }
+}
+
+/// Names of fields which occur in register more than once
+pub(crate) fn duplicate_fields(r: &RegisterInfo) -> Vec<&str> {
+    let mut seen = HashSet::new();
+    let mut duplicates = Vec::new();
+    for f in r.fields() {
+        if !seen.insert(f.name.as_str()) && !duplicates.contains(&f.name.as_str()) {
+            duplicates.push(f.name.as_str());
+        }
+    }
+    duplicates
+}
+
+/// Returns enumerated values of field which are out of its writeConstraint range
+pub(crate) fn enums_out_of_range(f: &FieldInfo) -> Vec<&EnumeratedValue> {
+    let Some(WriteConstraint::Range(range)) = f.write_constraint.as_ref() else {
+        return Vec::new();
+    };
+    f.enumerated_values
+        .iter()
+        .flat_map(|evs| evs.values.iter())
+        .filter(|ev| matches!(ev.value, Some(v) if v < range.min || v > range.max))
+        .collect()
+}
+
+/// Describes `addressOffset` and `dimIncrement` of register which are not aligned to its `size`
+pub(crate) fn misaligned_offsets(r: &Register, size: u32) -> Vec<String> {
+    let align = (size / 8).max(1);
+    let mut problems = Vec::new();
+    if r.address_offset % align != 0 {
+        problems.push(format!(
+            "addressOffset {:#x} is not aligned to {size}-bit size",
+            r.address_offset
+        ));
+    }
+    if let Register::Array(_, d) = r {
+        if d.dim_increment % align != 0 {
+            problems.push(format!(
+                "dimIncrement {:#x} is not aligned to {size}-bit size",
+                d.dim_increment
+            ));
+        }
+    }
+    problems
+}
+
+/// Returns enumerated values of field which do not fit in its bit width
+pub(crate) fn enums_out_of_width(f: &FieldInfo) -> Vec<&EnumeratedValue> {
+    let max = u64::MAX >> (64 - f.bit_range.width);
+    f.enumerated_values
+        .iter()
+        .flat_map(|evs| evs.values.iter())
+        .filter(|ev| matches!(ev.value, Some(v) if v > max))
+        .collect()
+}
+
+/// Returns names of field properties contradicting its access
+pub(crate) fn access_inconsistencies(f: &FieldInfo, access: Access) -> Vec<&'static str> {
+    let mut problems = Vec::new();
+    if !access.can_read() && f.read_action.is_some() {
+        problems.push("readAction");
+    }
+    if !access.can_write() {
+        if !matches!(
+            f.modified_write_values,
+            None | Some(ModifiedWriteValues::Modify)
+        ) {
+            problems.push("modifiedWriteValues");
+        }
+        if f.write_constraint.is_some() {
+            problems.push("writeConstraint");
+        }
+    }
+    problems
+}
+
 #[allow(unused)]
 fn make_usage(access: Access, usage: Usage) -> Option<Usage> {
     match (access, usage) {
@@ -969,3 +1650,729 @@ fn make_usage(access: Access, usage: Usage) -> Option<Usage> {
         _ => Some(usage),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::patch::device::DeviceExt;
+    use crate::test_utils;
+    use std::path::Path;
+    use svd_parser::svd::Usage;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn modify_field_element() {
+        let (mut device, yaml) =
+            test_utils::get_patcher(Path::new("modify_field_element")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+        let trig = device
+            .get_peripheral("DAC1")
+            .unwrap()
+            .get_register("TRIG")
+            .unwrap();
+        let fields = trig
+            .fields()
+            .map(|f| (f.name.as_str(), f.is_array(), f.bit_range.offset))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            [
+                ("TSEL0", false, 0),
+                ("TSEL1", false, 4),
+                ("TSEL2", false, 8),
+                ("TSEL3", false, 12),
+                ("SWTRIG%s", true, 16),
+            ]
+        );
+        let tsel1 = trig.get_field("TSEL1").unwrap();
+        assert_eq!(tsel1.description.as_deref(), Some("Trigger selection 1"));
+        assert_eq!(tsel1.access, None);
+        assert!(tsel1.enumerated_values.is_empty());
+        let tsel2 = trig.get_field("TSEL2").unwrap();
+        assert_eq!(tsel2.access, Some(svd_parser::svd::Access::ReadOnly));
+        assert_eq!(tsel2.enumerated_values[0].values[0].name, "EXT");
+    }
+
+    #[test]
+    fn derive_field_enums() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("derive_field_enums")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+        let cr = device
+            .get_peripheral("DAC1")
+            .unwrap()
+            .get_register("CR")
+            .unwrap();
+        let en1 = cr.get_field("EN1").unwrap();
+        assert_eq!(en1.enumerated_values[0].values.len(), 2);
+        let name = en1.enumerated_values[0].name.as_deref().unwrap();
+        for f in ["EN2", "EN3"] {
+            let evs = &cr.get_field(f).unwrap().enumerated_values;
+            assert_eq!(evs.len(), 1);
+            assert_eq!(evs[0].derived_from.as_deref(), Some(name));
+        }
+    }
+
+    #[test]
+    fn clear_field_enum() {
+        use svd_parser::svd::{Device, WriteConstraint, WriteConstraintRange};
+        use yaml_rust::YamlLoader;
+
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("write_constraint")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+        let patch = YamlLoader::load_from_str(
+            "
+DAC1:
+  CR:
+    RANGE:
+      Low: [0, \"Low\"]
+      High: [2, \"High\"]
+",
+        )
+        .unwrap();
+        device
+            .process(patch[0].as_hash().unwrap(), &Default::default())
+            .unwrap();
+        let range = |device: &Device| {
+            device
+                .get_peripheral("DAC1")
+                .unwrap()
+                .get_register("CR")
+                .unwrap()
+                .get_field("RANGE")
+                .unwrap()
+                .clone()
+        };
+        assert_eq!(range(&device).enumerated_values.len(), 1);
+
+        let patch = YamlLoader::load_from_str(
+            "
+DAC1:
+  CR:
+    _clear_enum: [RANGE]
+",
+        )
+        .unwrap();
+        device
+            .process(patch[0].as_hash().unwrap(), &Default::default())
+            .unwrap();
+        let f = range(&device);
+        assert!(f.enumerated_values.is_empty());
+        assert_eq!(
+            f.write_constraint,
+            Some(WriteConstraint::Range(WriteConstraintRange {
+                min: 0,
+                max: 2
+            }))
+        );
+    }
+
+    #[test]
+    fn prefix_enums() {
+        let (mut device, _) = test_utils::get_patcher(Path::new("add")).unwrap();
+        let patch = YamlLoader::load_from_str(
+            "
+DAC1:
+  CR:
+    _add:
+      EN2:
+        bitOffset: 16
+        bitWidth: 1
+    EN1:
+      _name: EN
+      Disabled: [0, Disabled]
+      Enabled: [1, Enabled]
+    EN2:
+      _derivedFrom: EN
+    _prefix:
+      TX_:
+        fields: EN1
+        enums: true
+      RX_: EN2
+",
+        )
+        .unwrap();
+        device
+            .process(patch[0].as_hash().unwrap(), &Default::default())
+            .unwrap();
+        let cr = device
+            .get_peripheral("DAC1")
+            .unwrap()
+            .get_register("CR")
+            .unwrap();
+        let f = cr.get_field("TX_EN1").unwrap();
+        let evs = &f.enumerated_values[0];
+        assert_eq!(evs.name.as_deref(), Some("TX_EN"));
+        let names = evs
+            .values
+            .iter()
+            .map(|ev| ev.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["TX_Disabled", "TX_Enabled"]);
+        let f = cr.get_field("RX_EN2").unwrap();
+        assert_eq!(
+            f.enumerated_values[0].derived_from.as_deref(),
+            Some("TX_EN")
+        );
+    }
+
+    #[test]
+    fn array_dim_index_order() {
+        let (mut device, _) = test_utils::get_patcher(Path::new("add")).unwrap();
+        let patch = YamlLoader::load_from_str(
+            "
+DAC1:
+  CR:
+    _add:
+      F10:
+        bitOffset: 8
+        bitWidth: 1
+      F2:
+        bitOffset: 9
+        bitWidth: 1
+      F1:
+        bitOffset: 10
+        bitWidth: 1
+    _array:
+      F*: {}
+",
+        )
+        .unwrap();
+        device
+            .process(patch[0].as_hash().unwrap(), &Default::default())
+            .unwrap();
+        let cr = device
+            .get_peripheral("DAC1")
+            .unwrap()
+            .get_register("CR")
+            .unwrap();
+        match cr.get_field("F%s") {
+            Some(svd_rs::Field::Array(_, dim)) => assert_eq!(
+                dim.dim_index.as_deref(),
+                Some(&["10", "2", "1"].map(String::from)[..])
+            ),
+            _ => panic!("F%s array is not collected"),
+        }
+    }
+
+    #[test]
+    fn collapse_field_enums() {
+        let (mut device, _) = test_utils::get_patcher(Path::new("add")).unwrap();
+        let patch = YamlLoader::load_from_str(
+            "
+DAC1:
+  CR:
+    _add:
+      MODE:
+        bitOffset: 8
+        bitWidth: 1
+      TRIG:
+        bitOffset: 9
+        bitWidth: 1
+      LOAD:
+        bitOffset: 10
+        bitWidth: 1
+    MODE:
+      _read:
+        Off: [0, Off]
+        On: [1, On]
+      _write:
+        Off: [0, Off]
+        On: [1, On]
+    TRIG:
+      _read:
+        Idle: [0, Idle]
+      _write:
+        Start: [1, Start]
+    EN1:
+      _read:
+        _name: ENR
+        Off: [0, Off]
+      _write:
+        _name: ENW
+        Off: [0, Off]
+    LOAD:
+      _write:
+        _derivedFrom: ENW
+    _collapse_enums: [MODE, TRIG, EN1]
+",
+        )
+        .unwrap();
+        device
+            .process(patch[0].as_hash().unwrap(), &Default::default())
+            .unwrap();
+        let cr = device
+            .get_peripheral("DAC1")
+            .unwrap()
+            .get_register("CR")
+            .unwrap();
+        let mode = &cr.get_field("MODE").unwrap().enumerated_values;
+        assert_eq!(mode.len(), 1);
+        assert_eq!(mode[0].usage, Some(Usage::ReadWrite));
+        assert_eq!(mode[0].values.len(), 2);
+        assert_eq!(cr.get_field("TRIG").unwrap().enumerated_values.len(), 2);
+        // derivedFrom of dropped write set is retargeted to the kept one
+        let en1 = &cr.get_field("EN1").unwrap().enumerated_values;
+        assert_eq!(en1.len(), 1);
+        assert_eq!(en1[0].name.as_deref(), Some("ENR"));
+        let load = &cr.get_field("LOAD").unwrap().enumerated_values;
+        assert_eq!(load[0].derived_from.as_deref(), Some("ENR"));
+    }
+
+    #[test]
+    fn derive_enum_path() {
+        use crate::patch::VAL_LVL;
+        use svd_parser::svd::EnumeratedValues;
+        use yaml_rust::YamlLoader;
+
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("derive_enum_path")).unwrap();
+        let orig = device.clone();
+        device.process(&yaml, &Default::default()).unwrap();
+        let derived = |p, r, f| {
+            device
+                .get_peripheral(p)
+                .unwrap()
+                .get_register(r)
+                .unwrap()
+                .get_field(f)
+                .unwrap()
+                .enumerated_values[0]
+                .derived_from
+                .clone()
+        };
+        assert_eq!(
+            derived("DAC1", "SR", "STAT1").as_deref(),
+            Some("CR.EN1.ENABLE")
+        );
+        assert_eq!(
+            derived("DAC2", "CR", "EN1").as_deref(),
+            Some("DAC1.CR.EN1.ENABLE")
+        );
+
+        let patch = YamlLoader::load_from_str(
+            "
+DAC1:
+  CR:
+    EN1:
+      _derivedFrom: ENABLE
+",
+        )
+        .unwrap();
+        let err = orig
+            .clone()
+            .process(patch[0].as_hash().unwrap(), &Default::default())
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("can't be found in register (local lookup)"));
+
+        let patch = YamlLoader::load_from_str(
+            "
+DAC1:
+  CR:
+    EN1:
+      _derivedFrom: DAC2.CR.EN1.ENABLE
+",
+        )
+        .unwrap();
+        let err = orig
+            .clone()
+            .process(patch[0].as_hash().unwrap(), &Default::default())
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("can't be found in device (absolute lookup)"));
+
+        // Dotted derives not set by patch are not checked
+        let mut device = orig;
+        device
+            .get_mut_peripheral("DAC1")
+            .unwrap()
+            .get_mut_register("CR")
+            .unwrap()
+            .get_mut_field("EN1")
+            .unwrap()
+            .enumerated_values = vec![EnumeratedValues::builder()
+            .derived_from(Some("DAC9.CR.EN1.ENABLE".into()))
+            .build(VAL_LVL)
+            .unwrap()];
+        let patch = YamlLoader::load_from_str(
+            "
+DAC1:
+  CR:
+    _modify:
+      EN1:
+        description: Enable
+",
+        )
+        .unwrap();
+        device
+            .process(patch[0].as_hash().unwrap(), &Default::default())
+            .unwrap();
+    }
+
+    #[test]
+    fn write_constraint() {
+        use svd_parser::svd::{WriteConstraint, WriteConstraintRange};
+        use yaml_rust::YamlLoader;
+
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("write_constraint")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+        let cr = device
+            .get_peripheral("DAC1")
+            .unwrap()
+            .get_register("CR")
+            .unwrap();
+        let wc = |f| cr.get_field(f).unwrap().write_constraint;
+        assert_eq!(wc("EN1"), Some(WriteConstraint::WriteAsRead(true)));
+        assert_eq!(wc("ENUM"), Some(WriteConstraint::UseEnumeratedValues(true)));
+        assert_eq!(
+            wc("NOENUM"),
+            Some(WriteConstraint::UseEnumeratedValues(false))
+        );
+        assert_eq!(
+            wc("RANGE"),
+            Some(WriteConstraint::Range(WriteConstraintRange {
+                min: 0,
+                max: 2
+            }))
+        );
+
+        let svd = svd_encoder::encode(&device).unwrap();
+        assert!(svd.contains("<writeAsRead>true</writeAsRead>"));
+        assert!(svd.contains("<useEnumeratedValues>false</useEnumeratedValues>"));
+
+        let patch =
+            YamlLoader::load_from_str("DAC1: {CR: {_modify: {RANGE: {writeConstraint: none}}}}")
+                .unwrap();
+        device
+            .process(patch[0].as_hash().unwrap(), &Default::default())
+            .unwrap();
+        let cr = device
+            .get_peripheral("DAC1")
+            .unwrap()
+            .get_register("CR")
+            .unwrap();
+        assert_eq!(cr.get_field("RANGE").unwrap().write_constraint, None);
+    }
+
+    #[test]
+    fn split_enum() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("split_enum")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+        let cr = device
+            .get_peripheral("DAC1")
+            .unwrap()
+            .get_register("CR")
+            .unwrap();
+        let ch0 = &cr.get_field("CH0EN").unwrap().enumerated_values;
+        assert_eq!(ch0[0].name.as_deref(), Some("EN"));
+        assert_eq!(ch0[0].values.len(), 2);
+        for f in ["CH1EN", "CH2EN", "CH3EN"] {
+            let evs = &cr.get_field(f).unwrap().enumerated_values;
+            assert_eq!(evs[0].derived_from.as_deref(), Some("EN"));
+        }
+    }
+
+    #[test]
+    fn force_array_description() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("force_description")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+        let cr = device
+            .get_peripheral("DAC1")
+            .unwrap()
+            .get_register("CR")
+            .unwrap();
+        let en = cr.get_field("EN%s").unwrap();
+        assert_eq!(en.description.as_deref(), Some("Channel %s enable"));
+    }
+
+    #[test]
+    fn enum_name_collision() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("enum_name_collision")).unwrap();
+        let err = device.process(&yaml, &Default::default()).unwrap_err();
+        assert_eq!(
+            err.root_cause().to_string(),
+            "DAC1.CR:EN1: read and write enumeratedValues have same name `EN`"
+        );
+    }
+
+    #[test]
+    fn recalc_reset_mask() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("recalc_reset_mask")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+        let cr = device
+            .get_peripheral("DAC1")
+            .unwrap()
+            .get_register("CR")
+            .unwrap();
+        assert_eq!(cr.properties.reset_mask, Some(0x3_0001));
+    }
+
+    #[test]
+    fn add_field_out_of_bounds() {
+        use yaml_rust::YamlLoader;
+
+        let (device, _) = test_utils::get_patcher(Path::new("add")).unwrap();
+        let add = |offset| {
+            let patch = YamlLoader::load_from_str(&format!(
+                "
+DAC1:
+  CR:
+    _add:
+      NEW:
+        bitOffset: {offset}
+        bitWidth: 8
+"
+            ))
+            .unwrap();
+            device
+                .clone()
+                .process(patch[0].as_hash().unwrap(), &Default::default())
+        };
+        let err = add(30).unwrap_err();
+        assert_eq!(
+            err.root_cause().to_string(),
+            "field NEW (bits 30..38) does not fit in 32-bit register DAC1.CR"
+        );
+        add(24).unwrap();
+
+        // Size of register is inherited from device and skipped if unknown
+        let add = |dsize: &str| {
+            let patch = YamlLoader::load_from_str(&format!(
+                "
+{dsize}
+DAC1:
+  _add:
+    NOSIZE:
+      addressOffset: 0x100
+  NOSIZE:
+    _add:
+      NEW:
+        bitOffset: 12
+        bitWidth: 8
+"
+            ))
+            .unwrap();
+            device
+                .clone()
+                .process(patch[0].as_hash().unwrap(), &Default::default())
+        };
+        let err = add("_modify: {size: 16}").unwrap_err();
+        assert_eq!(
+            err.root_cause().to_string(),
+            "field NEW (bits 12..20) does not fit in 16-bit register DAC1.NOSIZE"
+        );
+        add("").unwrap();
+    }
+
+    #[test]
+    fn infer_size() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("infer_size")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+        let dac1 = device.get_peripheral("DAC1").unwrap();
+        assert_eq!(dac1.get_register("SR").unwrap().properties.size, Some(16));
+        assert_eq!(dac1.get_register("CR").unwrap().properties.size, Some(32));
+    }
+
+    #[test]
+    fn check_duplicate_fields() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("duplicate_fields")).unwrap();
+        let config = crate::patch::Config {
+            check_duplicate_fields: true,
+            ..Default::default()
+        };
+        let err = device.process(&yaml, &config).unwrap_err();
+        assert_eq!(
+            err.root_cause().to_string(),
+            "Register `CR` has duplicate fields: EN1"
+        );
+    }
+
+    #[test]
+    fn check_overlaps() {
+        use yaml_rust::YamlLoader;
+
+        let (mut device, _) = test_utils::get_patcher(Path::new("add")).unwrap();
+        let patch = YamlLoader::load_from_str(
+            "
+DAC1:
+  CR:
+    _add:
+      EN2:
+        bitOffset: 4
+        bitWidth: 4
+      MODE:
+        bitOffset: 6
+        bitWidth: 2
+",
+        )
+        .unwrap();
+        let patch = patch[0].as_hash().unwrap();
+        device.clone().process(patch, &Default::default()).unwrap();
+        let config = crate::patch::Config {
+            check_overlaps: true,
+            ..Default::default()
+        };
+        let err = device.process(patch, &config).unwrap_err();
+        assert_eq!(
+            err.root_cause().to_string(),
+            "Fields `EN2` and `MODE` of register `CR` overlap"
+        );
+    }
+
+    #[test]
+    fn check_reset_width() {
+        // Parser validation rejects such SVD, so use the patcher's reader without it
+        let dir = test_utils::res_dir().join("reset_width");
+        let patch = crate::patch::load_patch(&dir.join("patch.yaml")).unwrap();
+        let svd = std::fs::File::open(dir.join("stm32l4x2.svd")).unwrap();
+        let err =
+            crate::patch::process_reader(svd, &patch, &Default::default(), &Default::default())
+                .err()
+                .unwrap();
+        assert_eq!(
+            err.root_cause().to_string(),
+            "resetValue 0x1ff of register `CR` does not fit in 8 bits"
+        );
+    }
+
+    #[test]
+    fn dedup_enum() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("dedup_enum")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+        let en1 = device
+            .get_peripheral("DAC1")
+            .unwrap()
+            .get_register("CR")
+            .unwrap()
+            .get_field("EN1")
+            .unwrap();
+        let names = en1.enumerated_values[0]
+            .values
+            .iter()
+            .map(|ev| ev.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Off", "Enabled"]);
+    }
+
+    #[test]
+    fn add_field_with_enums() {
+        let (mut device, _) = test_utils::get_patcher(Path::new("add")).unwrap();
+        let patch = YamlLoader::load_from_str(
+            "
+DAC1:
+  CR:
+    NEWF:
+      bitOffset: 8
+      bitWidth: 2
+      description: New field
+      access: read-write
+      _read:
+        Idle: [0, Idle]
+        Busy: [1, Busy]
+      _write:
+        Stop: [0, Stop]
+        Start: [1, Start]
+",
+        )
+        .unwrap();
+        device
+            .process(patch[0].as_hash().unwrap(), &Default::default())
+            .unwrap();
+        let newf = device
+            .get_peripheral("DAC1")
+            .unwrap()
+            .get_register("CR")
+            .unwrap()
+            .get_field("NEWF")
+            .unwrap();
+        assert_eq!((newf.bit_offset(), newf.bit_width()), (8, 2));
+        assert_eq!(newf.description.as_deref(), Some("New field"));
+        let evs = newf
+            .enumerated_values
+            .iter()
+            .map(|ev| (ev.usage, ev.values[1].name.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            evs,
+            [(Some(Usage::Read), "Busy"), (Some(Usage::Write), "Start")]
+        );
+
+        let patch = YamlLoader::load_from_str(
+            "
+DAC1:
+  CR:
+    OTHER*:
+      bitOffset: 12
+      bitWidth: 1
+      Off: [0, Off]
+      On: [1, On]
+",
+        )
+        .unwrap();
+        let err = device
+            .process(patch[0].as_hash().unwrap(), &Default::default())
+            .unwrap_err();
+        assert_eq!(
+            err.root_cause().to_string(),
+            "Could not find `OTHER*`. Only single field can be added with bits"
+        );
+    }
+
+    #[test]
+    fn merge_description() {
+        let (mut device, _) = test_utils::get_patcher(Path::new("add")).unwrap();
+        let patch = YamlLoader::load_from_str(
+            "
+DAC1:
+  CR:
+    _add:
+      PADDR0:
+        description: Port address bit 0
+        bitOffset: 16
+        bitWidth: 1
+      PADDR1:
+        description: Port address bit 1
+        bitOffset: 17
+        bitWidth: 1
+      MODE0:
+        description: Mode bit 0
+        bitOffset: 18
+        bitWidth: 1
+      MODE1:
+        description: Mode select
+        bitOffset: 19
+        bitWidth: 1
+    _merge:
+      PADDR: PADDR?
+      MODE:
+        fields: MODE?
+        description: Mode
+",
+        )
+        .unwrap();
+        device
+            .process(patch[0].as_hash().unwrap(), &Default::default())
+            .unwrap();
+        let cr = device
+            .get_peripheral("DAC1")
+            .unwrap()
+            .get_register("CR")
+            .unwrap();
+        let paddr = cr.get_field("PADDR").unwrap();
+        assert_eq!(paddr.description.as_deref(), Some("Port address bit %s"));
+        assert_eq!((paddr.bit_offset(), paddr.bit_width()), (16, 2));
+        let mode = cr.get_field("MODE").unwrap();
+        assert_eq!(mode.description.as_deref(), Some("Mode"));
+    }
+
+    #[test]
+    fn merge_common_name() {
+        let (mut device, yaml) = test_utils::get_patcher(Path::new("merge_common")).unwrap();
+        device.process(&yaml, &Default::default()).unwrap();
+        let cr = device
+            .get_peripheral("DAC1")
+            .unwrap()
+            .get_register("CR")
+            .unwrap();
+        let names = cr.fields().map(|f| f.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["EN1", "TSEL"]);
+        let tsel = cr.get_field("TSEL").unwrap();
+        assert_eq!((tsel.bit_offset(), tsel.bit_width()), (3, 3));
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+
+use svd_parser::svd::{Device, Field, Register, RegisterCluster};
+use yaml_rust::{yaml::Hash, Yaml};
+
+use super::yaml_ext::ToYaml;
+
+/// Generate patch skeleton with `_modify` stubs of all registers and fields of `device`
+/// filled with their current values. Peripherals, registers and fields are sorted by name
+pub fn skeleton(device: &Device) -> Hash {
+    let mut peripherals = BTreeMap::new();
+    for p in &device.peripherals {
+        if p.derived_from.is_some() {
+            continue;
+        }
+        let pmod = block_skeleton(p.registers.as_deref().unwrap_or_default());
+        if !pmod.is_empty() {
+            peripherals.insert(p.name.as_str(), pmod);
+        }
+    }
+    peripherals
+        .into_iter()
+        .map(|(pname, pmod)| (pname.to_yaml(), Yaml::Hash(pmod)))
+        .collect()
+}
+
+fn block_skeleton(children: &[RegisterCluster]) -> Hash {
+    let mut rmods = BTreeMap::new();
+    let mut fmods = BTreeMap::new();
+    let mut clusters = BTreeMap::new();
+    for rc in children {
+        match rc {
+            RegisterCluster::Register(r) => {
+                if r.derived_from.is_some() {
+                    continue;
+                }
+                rmods.insert(r.name.as_str(), register_stub(r));
+                let fields = r
+                    .fields()
+                    .map(|f| (f.name.as_str(), field_stub(f)))
+                    .collect::<BTreeMap<_, _>>();
+                if !fields.is_empty() {
+                    fmods.insert(r.name.as_str(), modify(fields));
+                }
+            }
+            RegisterCluster::Cluster(c) => {
+                if c.derived_from.is_some() {
+                    continue;
+                }
+                let cmod = block_skeleton(&c.children);
+                if !cmod.is_empty() {
+                    clusters.insert(c.name.as_str(), Yaml::Hash(cmod));
+                }
+            }
+        }
+    }
+    let mut h = Hash::new();
+    if !rmods.is_empty() {
+        h.extend(modify(rmods));
+    }
+    if !clusters.is_empty() {
+        h.insert(
+            "_clusters".to_yaml(),
+            Yaml::Hash(
+                clusters
+                    .into_iter()
+                    .map(|(k, v)| (k.to_yaml(), v))
+                    .collect(),
+            ),
+        );
+    }
+    h.extend(fmods.into_iter().map(|(k, v)| (k.to_yaml(), Yaml::Hash(v))));
+    h
+}
+
+fn modify(items: BTreeMap<&str, Hash>) -> Hash {
+    let items = items
+        .into_iter()
+        .map(|(k, v)| (k.to_yaml(), Yaml::Hash(v)))
+        .collect();
+    let mut h = Hash::new();
+    h.insert("_modify".to_yaml(), Yaml::Hash(items));
+    h
+}
+
+fn register_stub(r: &Register) -> Hash {
+    let mut h = Hash::new();
+    h.insert(
+        "description".to_yaml(),
+        r.description.as_deref().unwrap_or_default().to_yaml(),
+    );
+    h
+}
+
+fn field_stub(f: &Field) -> Hash {
+    let mut h = Hash::new();
+    h.insert(
+        "description".to_yaml(),
+        f.description.as_deref().unwrap_or_default().to_yaml(),
+    );
+    h.insert(
+        "bitOffset".to_yaml(),
+        Yaml::Integer(f.bit_range.offset as i64),
+    );
+    h.insert(
+        "bitWidth".to_yaml(),
+        Yaml::Integer(f.bit_range.width as i64),
+    );
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::device::DeviceExt;
+    use crate::test_utils;
+    use std::path::Path;
+    use yaml_rust::YamlEmitter;
+
+    #[test]
+    fn skeleton_fixture() {
+        let (device, _) = test_utils::get_patcher(Path::new("skeleton")).unwrap();
+        let mut out = String::new();
+        YamlEmitter::new(&mut out)
+            .dump(&Yaml::Hash(skeleton(&device)))
+            .unwrap();
+        let expected =
+            std::fs::read_to_string(test_utils::res_dir().join("skeleton/expected.yaml")).unwrap();
+        assert_eq!(out.trim_end(), expected.trim_end());
+
+        // skeleton applied as is changes nothing
+        let mut patched = device.clone();
+        patched
+            .process(&skeleton(&device), &Default::default())
+            .unwrap();
+        assert_eq!(patched, device);
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use svd_parser::svd::{Device, Field, Peripheral, Register, RegisterCluster};
+
+/// Numbers of added, modified and deleted items of one kind
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub added: usize,
+    pub modified: usize,
+    pub deleted: usize,
+}
+
+/// Summary of changes made in device by patch
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub peripherals: Counts,
+    pub registers: Counts,
+    pub fields: Counts,
+}
+
+impl Summary {
+    /// Compare devices before and after patching.
+    /// Items are identified by their full paths
+    pub fn new(before: &Device, after: &Device) -> Self {
+        let (pb, rb, fb) = collect(before);
+        let (pa, ra, fa) = collect(after);
+        Self {
+            peripherals: count(&pb, &pa),
+            registers: count(&rb, &ra),
+            fields: count(&fb, &fa),
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (kind, c) in [
+            ("peripherals", self.peripherals),
+            ("registers", self.registers),
+            ("fields", self.fields),
+        ] {
+            writeln!(
+                f,
+                "{kind}: {} added, {} modified, {} deleted",
+                c.added, c.modified, c.deleted
+            )?;
+        }
+        Ok(())
+    }
+}
+
+type Items<'a, T> = BTreeMap<String, &'a T>;
+
+fn collect(device: &Device) -> (Items<'_, Peripheral>, Items<'_, Register>, Items<'_, Field>) {
+    let mut peripherals = BTreeMap::new();
+    let mut registers = BTreeMap::new();
+    let mut fields = BTreeMap::new();
+    for p in &device.peripherals {
+        peripherals.insert(p.name.clone(), p);
+        collect_block(
+            &p.name,
+            p.registers.as_deref().unwrap_or_default(),
+            &mut registers,
+            &mut fields,
+        );
+    }
+    (peripherals, registers, fields)
+}
+
+fn collect_block<'a>(
+    path: &str,
+    children: &'a [RegisterCluster],
+    registers: &mut Items<'a, Register>,
+    fields: &mut Items<'a, Field>,
+) {
+    for rc in children {
+        match rc {
+            RegisterCluster::Register(r) => {
+                let rpath = format!("{path}.{}", r.name);
+                for f in r.fields() {
+                    fields.insert(format!("{rpath}.{}", f.name), f);
+                }
+                registers.insert(rpath, r);
+            }
+            RegisterCluster::Cluster(c) => collect_block(
+                &format!("{path}.{}", c.name),
+                &c.children,
+                registers,
+                fields,
+            ),
+        }
+    }
+}
+
+fn count<T: PartialEq>(before: &Items<T>, after: &Items<T>) -> Counts {
+    let mut counts = Counts::default();
+    for (k, a) in after {
+        match before.get(k) {
+            None => counts.added += 1,
+            Some(b) if b != a => counts.modified += 1,
+            _ => {}
+        }
+    }
+    counts.deleted = before.keys().filter(|k| !after.contains_key(*k)).count();
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::device::DeviceExt;
+    use crate::test_utils;
+    use std::path::Path;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn summary_counts() {
+        let (device, _) = test_utils::get_patcher(Path::new("add")).unwrap();
+        let patch = YamlLoader::load_from_str(
+            "
+DAC1:
+  _add:
+    SR:
+      addressOffset: 0x4
+  CR:
+    _delete: [EN1]
+",
+        )
+        .unwrap();
+        let mut patched = device.clone();
+        patched
+            .process(patch[0].as_hash().unwrap(), &Default::default())
+            .unwrap();
+        let summary = Summary::new(&device, &patched);
+        assert_eq!(
+            summary,
+            Summary {
+                peripherals: Counts {
+                    added: 0,
+                    modified: 1,
+                    deleted: 0
+                },
+                registers: Counts {
+                    added: 1,
+                    modified: 1,
+                    deleted: 0
+                },
+                fields: Counts {
+                    added: 0,
+                    modified: 0,
+                    deleted: 1
+                },
+            }
+        );
+        assert_eq!(
+            summary.to_string(),
+            "peripherals: 0 added, 1 modified, 0 deleted\n\
+             registers: 1 added, 1 modified, 0 deleted\n\
+             fields: 0 added, 0 modified, 1 deleted\n"
+        );
+    }
+}
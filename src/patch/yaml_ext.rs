@@ -212,12 +212,8 @@ pub trait GetVal {
         Ok(OptIter::new(match yaml {
             None => None,
             Some(Yaml::String(_)) => Some(OverStringIter(yaml.unwrap(), None)),
-            Some(Yaml::Array(y)) => {
-                if y.iter().all(|x| x.as_str().is_some()) {
-                    Some(OverStringIter(yaml.unwrap(), None))
-                } else {
-                    return Err(anyhow!("`{k}` requires string value or array of strings"));
-                }
+            Some(Yaml::Array(y)) if y.iter().all(|x| x.as_str().is_some()) => {
+                Some(OverStringIter(yaml.unwrap(), None))
             }
             _ => return Err(anyhow!("`{k}` requires string value or array of strings")),
         }))
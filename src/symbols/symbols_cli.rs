@@ -0,0 +1,96 @@
+use crate::common::{str_utils, svd_reader};
+use crate::cstruct::cstruct_cli::collect_registers;
+use crate::mmap::mmap_cli::get_periph_registers;
+use anyhow::{Context, Result};
+use std::fmt::Write;
+use std::{fs::File, io::Read, path::Path};
+use svd_parser::svd::{self, Peripheral, PeripheralInfo};
+
+/// Write linker script with `PROVIDE` statement for address of every register
+pub fn symbols(svd_file: &Path, out_path: &Path) -> Result<()> {
+    let mut file =
+        File::open(svd_file).with_context(|| format!("Opening {}", svd_file.display()))?;
+    let text = get_symbols(&mut file).with_context(|| format!("Parsing {}", svd_file.display()))?;
+    std::fs::write(out_path, text).with_context(|| format!("Writing {}", out_path.display()))
+}
+
+fn get_symbols<R: Read>(svd: &mut R) -> Result<String> {
+    let peripherals = svd_reader::peripherals(svd)?;
+    Ok(to_symbols(&peripherals))
+}
+
+fn to_symbols(peripherals: &[Peripheral]) -> String {
+    let mut out = String::new();
+    for p in peripherals {
+        match p {
+            Peripheral::Single(p) => get_registers(p, peripherals, &mut out),
+            Peripheral::Array(p, d) => {
+                for pi in svd::peripheral::expand(p, d) {
+                    get_registers(&pi, peripherals, &mut out);
+                }
+            }
+        }
+    }
+    out
+}
+
+fn get_registers(peripheral: &PeripheralInfo, peripherals: &[Peripheral], out: &mut String) {
+    let mut regs = Vec::new();
+    if let Some(registers) = get_periph_registers(peripheral, peripherals) {
+        collect_registers(registers, peripheral.base_address, "", &mut regs);
+    }
+    regs.sort_by_key(|r| r.0);
+    for (addr, _, rname) in regs {
+        let symbol = sanitize(&format!("{}_{rname}", peripheral.name));
+        let addr = str_utils::format_address(addr);
+        writeln!(out, "PROVIDE({symbol} = {addr});").unwrap();
+    }
+}
+
+/// Replace characters which are not allowed in linker symbol
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SVD: &str = r#"
+<device>
+    <name>dev</name>
+    <peripherals>
+        <peripheral>
+            <name>GPIOA</name>
+            <baseAddress>0x48000000</baseAddress>
+            <registers>
+                <register>
+                    <name>MODER</name>
+                    <addressOffset>0x0</addressOffset>
+                </register>
+                <register>
+                    <dim>2</dim>
+                    <dimIncrement>4</dimIncrement>
+                    <name>AFR[%s]</name>
+                    <addressOffset>0x20</addressOffset>
+                </register>
+            </registers>
+        </peripheral>
+        <peripheral derivedFrom="GPIOA">
+            <name>GPIOB</name>
+            <baseAddress>0x48000400</baseAddress>
+        </peripheral>
+    </peripherals>
+</device>"#;
+
+    #[test]
+    fn symbols() {
+        let mut svd = SVD.as_bytes();
+        let symbols = get_symbols(&mut svd).unwrap();
+        assert!(symbols.contains("PROVIDE(GPIOA_MODER = 0x48000000);\n"));
+        assert!(symbols.contains("PROVIDE(GPIOA_AFR1 = 0x48000024);\n"));
+        assert!(symbols.contains("PROVIDE(GPIOB_MODER = 0x48000400);\n"));
+    }
+}